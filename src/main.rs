@@ -3,7 +3,6 @@
 use bevy::{
     diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
     prelude::*,
-    window::{PresentMode, Window, WindowPlugin, WindowTheme}, //ecs::schedule,
 };
 /*
     global data
@@ -13,6 +12,7 @@ const TXT_CLR: Color = Color::srgb(0.9, 0.9, 0.9);
 enum GameState {
     #[default]
     Splash,
+    Profiles,
     Menu,
     Game,
     Pause,
@@ -27,6 +27,102 @@ enum DisplayQuality {
 }
 #[derive(Resource, Debug, Component, PartialEq, Eq, Clone, Copy)]
 struct Volume(u32);
+#[derive(Resource, Debug, Component, PartialEq, Eq, Clone, Copy)]
+struct MouseSensitivity(u32);
+#[derive(Resource, Debug, Component, PartialEq, Eq, Clone, Copy)]
+struct InvertMouse(bool);
+#[derive(Resource, Debug, Component, PartialEq, Eq, Clone, Copy)]
+struct Subtitles(bool);
+#[derive(Resource, Debug, Component, PartialEq, Eq, Clone, Copy)]
+struct RouteGuidance(bool);
+/// whether gameplay events are written to the local telemetry log, off unless the player opts in
+#[derive(Resource, Debug, Component, PartialEq, Eq, Clone, Copy)]
+struct Telemetry(bool);
+#[derive(Resource, Debug, Component, PartialEq, Eq, Clone, Copy)]
+enum TimeOfDay {
+    Cycle,
+    Dawn,
+    Noon,
+    Dusk,
+    Night,
+}
+#[derive(Resource, Debug, Component, PartialEq, Eq, Clone, Copy)]
+pub enum WeatherChoice {
+    Forecast,
+    Clear,
+    Rain,
+    Fog,
+    Wind,
+}
+#[derive(Resource, Debug, Component, PartialEq, Eq, Clone, Copy)]
+pub enum GameMode {
+    Timed,
+    Endless,
+    Zen,
+    Survival,
+}
+#[derive(Resource, Debug, Component, PartialEq, Eq, Clone, Copy)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+/// length of a timed round, in seconds
+#[derive(Resource, Debug, Component, PartialEq, Eq, Clone, Copy)]
+pub struct RoundLength(u32);
+impl GameMode {
+    /// seconds on the clock at the start of a run, or None when the mode has no timer
+    fn starting_secs(self, length: RoundLength) -> Option<f32> {
+        match self {
+            GameMode::Timed => Some(length.0 as f32),
+            GameMode::Endless => Some(60.0),
+            GameMode::Zen | GameMode::Survival => None,
+        }
+    }
+    /// whether running into a building or the wall ends the run
+    fn crashes(self) -> bool {
+        self != GameMode::Zen
+    }
+    /// the name shown for the mode, timed rounds include their length
+    fn label(self, length: RoundLength) -> String {
+        match self {
+            GameMode::Timed => format!("Timed {}s", length.0),
+            _ => format!("{self:?}"),
+        }
+    }
+    /// the key high scores are kept under, timed rounds of each length are kept apart
+    fn score_key(self, length: RoundLength, difficulty: Difficulty) -> String {
+        let mode = match self {
+            GameMode::Timed => format!("timed_{}", length.0),
+            _ => format!("{self:?}").to_lowercase(),
+        };
+        format!("{mode}_{difficulty:?}").to_lowercase()
+    }
+}
+impl WeatherChoice {
+    /// the weather to hold for the whole run, or None to follow the run's forecast
+    fn fixed(self) -> Option<atmosphere::Weather> {
+        match self {
+            WeatherChoice::Forecast => None,
+            WeatherChoice::Clear => Some(atmosphere::Weather::Clear),
+            WeatherChoice::Rain => Some(atmosphere::Weather::Rain),
+            WeatherChoice::Fog => Some(atmosphere::Weather::Fog),
+            WeatherChoice::Wind => Some(atmosphere::Weather::Wind),
+        }
+    }
+}
+impl TimeOfDay {
+    /// the hour the clock is held at, or None when time passes normally
+    fn locked_hour(self) -> Option<f32> {
+        match self {
+            TimeOfDay::Cycle => None,
+            TimeOfDay::Dawn => Some(6.5),
+            TimeOfDay::Noon => Some(12.0),
+            TimeOfDay::Dusk => Some(18.5),
+            TimeOfDay::Night => Some(0.0),
+        }
+    }
+}
 #[derive(Component)]
 pub struct RotatableCamera {
     radius: f32,
@@ -46,31 +142,73 @@ pub struct CameraState {
 /// # main function
 /// This function initializes the nannou framework app
 fn main() {
+    // the command line is read first, since the window can't be changed once the app is built
+    let options = match cli::LaunchOptions::parse(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", cli::USAGE);
+            return;
+        }
+        Err(error) => {
+            eprintln!("{error}\n\n{}", cli::USAGE);
+            std::process::exit(2);
+        }
+    };
     App::new()
         .add_plugins((
-            DefaultPlugins.set(WindowPlugin {
-                primary_window: Some(Window {
-                    title: "The Future of Mail".into(),
-                    name: Some("bevy.app".into()),
-                    resolution: (1280., 720.).into(),
-                    present_mode: PresentMode::AutoVsync,
-                    window_theme: Some(WindowTheme::Dark),
-                    ..default()
-                }),
-                ..default()
-            }),
+            options.default_plugins(),
             LogDiagnosticsPlugin::default(),
             FrameTimeDiagnosticsPlugin,
         ))
+        .insert_resource(options)
         //.add_plugins(DefaultPlugins)
         .insert_resource(DisplayQuality::Medium)
         .insert_resource(Volume(7))
+        .insert_resource(MouseSensitivity(5))
+        .insert_resource(InvertMouse(false))
+        .insert_resource(Subtitles(true))
+        .insert_resource(RouteGuidance(true))
+        .insert_resource(Telemetry(false))
+        .insert_resource(GameMode::Timed)
+        .insert_resource(RoundLength(60))
+        .insert_resource(Difficulty::Normal)
+        .insert_resource(TimeOfDay::Cycle)
+        .insert_resource(game::SelectedMap::default())
+        .insert_resource(game::MAPS[0].weather)
         .insert_resource(PlayerState::default())
         .insert_resource(CameraState::default())
         .insert_resource(PlayerPoints(0))
         .init_state::<GameState>()
         .add_systems(Startup, setup)
-        .add_plugins((splash::splash_plugin, menu::menu_plugin, game::game_plugin))
+        .add_plugins((
+            splash::splash_plugin,
+            menu::menu_plugin,
+            game::game_plugin,
+            camera::camera_plugin,
+            settings::settings_plugin,
+            atmosphere::atmosphere_plugin,
+            music::music_plugin,
+            audio::audio_plugin,
+            people::people_plugin,
+            difficulty::difficulty_plugin,
+            dispatch::dispatch_plugin,
+        ))
+        .add_plugins((
+            contracts::contracts_plugin,
+            guidance::guidance_plugin,
+            parcels::parcels_plugin,
+            upgrades::upgrades_plugin,
+            garage::garage_plugin,
+            profiles::profiles_plugin,
+            achievements::achievements_plugin,
+            stats::stats_plugin,
+            crash_log::crash_log_plugin,
+            telemetry::telemetry_plugin,
+            console::console_plugin,
+            debug_overlay::debug_overlay_plugin,
+            replay::replay_plugin,
+            cli::cli_plugin,
+        ))
         .run();
 }
 fn setup(mut commands: Commands, query: Query<Entity, With<Camera>>) {
@@ -126,27 +264,83 @@ mod splash {
         mut timer: ResMut<SplashTimer>,
     ) {
         if timer.tick(time.delta()).finished() {
-            game_state.set(GameState::Menu);
+            game_state.set(GameState::Profiles);
         }
     }
 }
 mod game {
 
     use super::{despawn_screen, GameState, PlayerPoints};
+    use crate::atmosphere::AtmosphereModel;
+    use crate::audio::{self, Sound, SoundEvent};
+    use crate::camera::{
+        camera_target, CameraMode, CameraOcclusion, CameraSettings, CameraTransition,
+    };
+    use crate::console::{AppConsoleExt, ConsoleCommand};
+    use crate::parcels::ParcelKind;
+    use crate::people::PersonAppearance;
+    use crate::profiles::ActiveProfile;
+    use crate::replay::ReplayRun;
+    use crate::settings::{read_key_values, write_key_values};
+    use crate::upgrades::{Battery, Progress};
     use crate::{CameraState, PlayerState, RotatableCamera, TXT_CLR};
-    use bevy::{input::ButtonInput, prelude::*};
-    use rand::{prelude::SliceRandom, thread_rng};
+    use crate::{Difficulty, GameMode, RoundLength, WeatherChoice};
+    use bevy::{ecs::system::SystemParam, input::ButtonInput, prelude::*, time::Stopwatch};
+    use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
+    use std::{collections::HashMap, time::Duration};
     pub fn game_plugin(app: &mut App) {
-        app.add_systems(OnEnter(GameState::Game), game_setup)
+        app.insert_resource(RunRng(StdRng::from_entropy()))
+            .init_resource::<Deliveries>()
+            .add_event::<ParcelPickedUp>()
+            .add_event::<DeliveryMade>()
+            .add_event::<RobotCrashed>()
+            .add_event::<RobotBumped>()
+            .add_event::<RobotDrove>()
+            .init_resource::<HighScores>()
+            .init_resource::<DevCheats>()
+            .init_resource::<RunSeed>()
+            .add_console_command(TeleportCommand)
+            .add_console_command(SetCommand)
+            .add_console_command(TimerCommand)
+            .add_console_command(StateCommand)
+            .add_console_command(GodCommand)
+            .add_console_command(NoclipCommand)
+            .add_console_command(SeedCommand)
+            .add_systems(
+                Update,
+                load_high_scores.run_if(resource_changed::<ActiveProfile>),
+            )
+            .add_systems(OnEnter(GameState::Game), game_setup)
+            .add_systems(
+                OnEnter(GameState::Menu),
+                (
+                    end_run,
+                    reset_cheats,
+                    despawn_screen::<CheckPointCube>,
+                    despawn_screen::<PlatformModel>,
+                ),
+            )
+            .add_systems(
+                OnEnter(GameState::Lose),
+                (
+                    end_run,
+                    despawn_screen::<CheckPointCube>,
+                    despawn_screen::<PlatformModel>,
+                ),
+            )
             .add_systems(
                 Update,
                 (
                     game.run_if(in_state(GameState::Game)),
                     rotate_camera.run_if(in_state(GameState::Game)),
                     move_player.run_if(in_state(GameState::Game)),
+                    measure_drive.run_if(in_state(GameState::Game)),
                     return_to_main.run_if(in_state(GameState::Game)),
                     detect_collisions.run_if(in_state(GameState::Game)),
                     update_scoreboard.run_if(in_state(GameState::Game)),
+                    idle_chatter.run_if(in_state(GameState::Game)),
+                    apply_mode_rules.run_if(in_state(GameState::Game)),
+                    update_deadline.run_if(in_state(GameState::Game)),
                 ),
             )
             .add_systems(
@@ -158,51 +352,328 @@ mod game {
     #[derive(Component)]
     struct OnGameScreen;
     #[derive(Resource, Deref, DerefMut)]
-    struct GameTimer(Timer);
+    pub struct GameTimer(Timer);
+    /// seconds an Endless run gains for every delivery
+    const ENDLESS_BONUS_SECS: u64 = 10;
+    /// how much faster the robot gets with every Survival delivery, and the most it can reach
+    const SURVIVAL_SPEEDUP: f32 = 0.06;
+    const SURVIVAL_MAX_SPEED: f32 = 2.0;
+    /// Survival pays one more point per delivery for every this many deliveries made
+    const SURVIVAL_LEVEL_DELIVERIES: u32 = 5;
+    /// the robot's top speed before upgrades, the battery or the mode change it
+    const BASE_SPEED: f32 = 50.0;
+    const HIGH_SCORES_FILE: &str = "highscores.cfg";
+    /// how long the robot can sit still before the waiting sender calls out to it
+    const IDLE_SECS: f32 = 8.0;
+    /// how far away a person can be and still shout about a crash
+    const CRASH_HEARING_DISTANCE: f32 = 150.0;
+    /// the model for each building on the island, and where it stands in `BUILDING_COORDS`
+    pub const BUILDING_MODELS: [&str; 43] = [
+        ("Models/building1.glb#Scene0"),
+        ("Models/building1.glb#Scene0"),
+        ("Models/building1.glb#Scene0"),
+        ("Models/building1.glb#Scene0"),
+        ("Models/building1.glb#Scene0"),
+        ("Models/building1.glb#Scene0"),
+        ("Models/building1.glb#Scene0"),
+        ("Models/building1.glb#Scene0"),
+        ("Models/building1.glb#Scene0"),
+        ("Models/building2.glb#Scene0"),
+        ("Models/building2.glb#Scene0"),
+        ("Models/building2.glb#Scene0"),
+        ("Models/building2.glb#Scene0"),
+        ("Models/building2.glb#Scene0"),
+        ("Models/building2.glb#Scene0"),
+        ("Models/building2.glb#Scene0"),
+        ("Models/building2.glb#Scene0"),
+        ("Models/building3.glb#Scene0"),
+        ("Models/building3.glb#Scene0"),
+        ("Models/building3.glb#Scene0"),
+        ("Models/building3.glb#Scene0"),
+        ("Models/building3.glb#Scene0"),
+        ("Models/building3.glb#Scene0"),
+        ("Models/building3.glb#Scene0"),
+        ("Models/building3.glb#Scene0"),
+        ("Models/building3.glb#Scene0"),
+        ("Models/building4.glb#Scene0"),
+        ("Models/building4.glb#Scene0"),
+        ("Models/building4.glb#Scene0"),
+        ("Models/building4.glb#Scene0"),
+        ("Models/building4.glb#Scene0"),
+        ("Models/building4.glb#Scene0"),
+        ("Models/building4.glb#Scene0"),
+        ("Models/building4.glb#Scene0"),
+        ("Models/building4.glb#Scene0"),
+        ("Models/building5.glb#Scene0"),
+        ("Models/building5.glb#Scene0"),
+        ("Models/building5.glb#Scene0"),
+        ("Models/building5.glb#Scene0"),
+        ("Models/building5.glb#Scene0"),
+        ("Models/building5.glb#Scene0"),
+        ("Models/building5.glb#Scene0"),
+        ("Models/building5.glb#Scene0"),
+    ];
+    /// where each building stands, x, y and z and then its rotation
+    pub const BUILDING_COORDS: [(f32, f32, f32, f32, f32, f32); 43] = [
+        // coordinates for building type 1
+        (25.0, 1.1, 25.0, 0.0, 0.0, 0.0),
+        (70.0, 1.1, 25.0, 0.0, 0.0, 0.0),
+        (-70.0, 1.1, -25.0, 0.0, 0.0, 0.0),
+        (-25.0, 1.1, -170.0, 0.0, 0.0, 0.0),
+        (-70.0, 1.1, 75.0, 0.0, 0.0, 0.0),
+        (-75.0, 1.1, -125.0, 0.0, 0.0, 0.0),
+        (-165.0, 1.1, -25.0, 0.0, 0.0, 0.0),
+        (-165.0, 1.1, 75.0, 0.0, 0.0, 0.0),
+        (120.0, 1.1, -125.0, 0.0, 0.0, 0.0),
+        // coordinates for building type 2
+        (70.0, 1.1, -40.0, 0.0, 0.0, 0.0),
+        (25.0, 1.1, 90.0, 0.0, 0.0, 0.0),
+        (-120.0, 1.1, -40.0, 0.0, 0.0, 0.0),
+        (-25.0, 1.1, 90.0, 0.0, 0.0, 0.0),
+        (70.0, 1.1, -120.0, 0.0, 0.0, 0.0),
+        (-120.0, 1.1, 90.0, 0.0, 0.0, 0.0),
+        (120.0, 1.1, 90.0, 0.0, 0.0, 0.0),
+        (165.0, 1.1, 45.0, 0.0, 0.0, 0.0),
+        // coordinates for building type 3
+        (-25.0, 1.1, 25.0, 0.0, 0.0, 0.0),
+        (25.0, 1.1, 155.0, 0.0, 0.0, 0.0),
+        (25.0, 1.1, -170.0, 0.0, 0.0, 0.0),
+        (-25.0, 1.1, 155.0, 0.0, 0.0, 0.0),
+        (-70.0, 1.1, -75.0, 0.0, 0.0, 0.0),
+        (-120.0, 1.1, -105.0, 0.0, 0.0, 0.0),
+        (160.0, 1.1, -25.0, 0.0, 0.0, 0.0),
+        (120.0, 1.1, 25.0, 0.0, 0.0, 0.0),
+        (160.0, 1.1, -75.0, 0.0, 0.0, 0.0),
+        // coordinates for building type 4
+        (25.0, 1.1, -25.0, 0.0, 0.0, 0.0),
+        (25.0, 1.1, -75.0, 0.0, 0.0, 0.0),
+        (-25.0, 1.1, -75.0, 0.0, 0.0, 0.0),
+        (-70.0, 1.1, 25.0, 0.0, 0.0, 0.0),
+        (70.0, 1.1, 75.0, 0.0, 0.0, 0.0),
+        (-70.0, 1.1, 120.0, 0.0, 0.0, 0.0),
+        (120.0, 1.1, -25.0, 0.0, 0.0, 0.0),
+        (-165.0, 1.1, 25.0, 0.0, 0.0, 0.0),
+        (-70.0, 1.1, 165.0, 0.0, 0.0, 0.0),
+        //coordinates for building type 5
+        (-25.0, 1.1, -25.0, 0.0, 0.0, 0.0),
+        (25.0, 1.1, -125.0, 0.0, 0.0, 0.0),
+        (-25.0, 1.1, -125.0, 0.0, 0.0, 0.0),
+        (-120.0, 1.1, 25.0, 0.0, 0.0, 0.0),
+        (70.0, 1.1, 120.0, 0.0, 0.0, 0.0),
+        (120.0, 1.1, -75.0, 0.0, 0.0, 0.0),
+        (-160.0, 1.1, -75.0, 0.0, 0.0, 0.0),
+        (70.0, 1.1, 165.0, 0.0, 0.0, 0.0),
+    ];
+    /// how close the robot can get to the middle of a building before it crashes into it
+    pub const BUILDING_CRASH_RADIUS: f32 = 20.0;
+    /// how far the robot can get from the middle of the island before it hits the ring wall
+    pub const WALL_RADIUS: f32 = 200.0;
+    /// developer toggles from the console: god keeps crashes from ending the run and noclip
+    /// drives straight through buildings, the ring wall and anything in the road
+    #[derive(Resource, Default)]
+    pub struct DevCheats {
+        pub god: bool,
+        pub noclip: bool,
+    }
+    /// the seed the run under way was started from, and one to start the next run from instead
+    /// of a random one
+    #[derive(Resource, Default)]
+    pub struct RunSeed {
+        pub current: u64,
+        pub next: Option<u64>,
+    }
+    /// present while a run is under way, so coming back from the pause menu carries on with it
+    #[derive(Resource)]
+    pub struct RunInProgress;
+    /// the console was used during the run, so nothing from it is kept: no high score, coins,
+    /// stats, achievements or logs. Cleared when the next run starts
+    #[derive(Resource)]
+    pub struct CheatedRun;
+    /// whether what happens in the run should be saved to the profile and the logs, which it
+    /// isn't for a cheated run or one being played back from a replay
+    pub fn keeps_run(cheated: Option<Res<CheatedRun>>, replay: Option<Res<ReplayRun>>) -> bool {
+        cheated.is_none() && replay.is_none()
+    }
+    /// parcels delivered so far this run
+    #[derive(Resource, Default)]
+    pub struct Deliveries(pub u32);
+    /// sent when the robot collects a parcel from its sender
+    #[derive(Event)]
+    pub struct ParcelPickedUp {
+        pub at: Vec3,
+    }
+    /// sent whenever a parcel reaches its drop-off point, with how long it took from pickup
+    #[derive(Event)]
+    pub struct DeliveryMade {
+        pub secs: f32,
+        pub late: bool,
+    }
+    /// sent when the robot crashes and the run is lost, with the building it hit if it was one
+    #[derive(Event)]
+    pub struct RobotCrashed {
+        pub at: Vec3,
+        pub cause: CrashCause,
+        pub building: Option<Entity>,
+    }
+    /// what the robot crashed into
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+    pub enum CrashCause {
+        Building,
+        Wall,
+        Pedestrian,
+        Cart,
+    }
+    impl CrashCause {
+        pub const ALL: [Self; 4] = [
+            CrashCause::Building,
+            CrashCause::Wall,
+            CrashCause::Pedestrian,
+            CrashCause::Cart,
+        ];
+        /// the name the cause is written under in the crash log
+        pub fn key(self) -> String {
+            format!("{self:?}").to_lowercase()
+        }
+        pub fn label(self) -> &'static str {
+            match self {
+                CrashCause::Building => "Building",
+                CrashCause::Wall => "Ring wall",
+                CrashCause::Pedestrian => "Pedestrian",
+                CrashCause::Cart => "Cart",
+            }
+        }
+    }
+    /// sent when the robot gets pushed back out of something instead of crashing into it
+    #[derive(Event)]
+    pub struct RobotBumped;
+    /// sent every frame with how far the robot drove, the one measure of distance everything
+    /// that counts it goes by
+    #[derive(Event)]
+    pub struct RobotDrove {
+        pub distance: f32,
+    }
+    /// the best score for each game mode, see `GameMode::score_key`
+    #[derive(Resource, Default)]
+    pub struct HighScores(HashMap<String, usize>);
+    impl HighScores {
+        pub fn best(&self, mode: GameMode, length: RoundLength, difficulty: Difficulty) -> usize {
+            self.0
+                .get(&mode.score_key(length, difficulty))
+                .copied()
+                .unwrap_or_default()
+        }
+    }
+    /// how the last run went, for the results screen
+    #[derive(Resource)]
+    pub struct LastRun {
+        pub mode: String,
+        pub difficulty: Difficulty,
+        pub score: usize,
+        pub best: usize,
+        pub new_best: bool,
+        /// the clock ran out, rather than the robot crashing
+        pub out_of_time: bool,
+    }
+    /// random numbers for the run, reseeded at the start of each one so a seed always plays the same
+    #[derive(Resource, Deref, DerefMut)]
+    pub struct RunRng(StdRng);
+    /// a map that can be played, with the scene it loads and the weather it gets by default
+    pub struct MapInfo {
+        pub name: &'static str,
+        pub scene: &'static str,
+        pub weather: WeatherChoice,
+    }
+    pub const MAPS: [MapInfo; 1] = [MapInfo {
+        name: "island",
+        scene: "Models/island.glb#Scene0",
+        weather: WeatherChoice::Forecast,
+    }];
+    #[derive(Resource, Default, Clone, Copy)]
+    pub struct SelectedMap(pub usize);
+    impl SelectedMap {
+        pub fn info(self) -> &'static MapInfo {
+            &MAPS[self.0]
+        }
+    }
     #[derive(Component, Default)]
     pub struct AtmosphereCamera;
-    #[derive(Resource, Default)]
-    pub struct AtmosphereModel;
     #[derive(Component)]
     struct SpawnedModel;
     #[derive(Component)]
     pub struct PlayerModel;
+    /// roughly the top of the robot model, where parcels and accessories sit
+    pub const ROBOT_TOP: f32 = 12.0;
+    /// the robot's velocity, which lags behind the keys when the road is slippery
+    #[derive(Component, Default)]
+    pub struct PlayerMotion {
+        pub velocity: Vec3,
+    }
     #[derive(Component)]
     pub struct PeopleModel;
+    /// the people out in the city, kept apart from the robot so its transform can be changed
+    type PeopleQuery<'w, 's> =
+        Query<'w, 's, (Entity, &'static Transform), (With<PeopleModel>, Without<PlayerModel>)>;
     #[derive(Component)]
-    struct BuildingModel;
+    pub struct BuildingModel;
+    /// the buildings the robot can crash into, kept apart from the robot's own transform
+    type BuildingQuery<'w, 's> =
+        Query<'w, 's, (Entity, &'static Transform), (With<BuildingModel>, Without<PlayerModel>)>;
+    /// which building in the island's layout this is, see `BUILDING_MODELS`
+    #[derive(Component)]
+    pub struct BuildingSlot(pub usize);
     #[derive(Component)]
     struct PlatformModel;
+    /// the parcel's drop-off point, remembering what kind of parcel it is and counting down the
+    /// time the sender gave for it to arrive, which stands still while the game is paused
+    #[derive(Component)]
+    pub struct CheckPointCube {
+        parcel: ParcelKind,
+        on_board: Stopwatch,
+        deadline_secs: f32,
+    }
+    impl CheckPointCube {
+        pub fn parcel(&self) -> ParcelKind {
+            self.parcel
+        }
+    }
     #[derive(Component)]
-    struct CheckPointCube;
+    struct DeadlineText;
     #[derive(Component)]
     struct Scoreboard;
 
     fn rotate_camera(
         keyboard_input: Res<ButtonInput<KeyCode>>,
         mut param_set: ParamSet<(
-            Query<(&mut Transform, &mut RotatableCamera)>,
+            Query<(&mut Transform, &mut RotatableCamera, &mut Projection)>,
             Query<&Transform, With<PlayerModel>>,
         )>,
         time: Res<Time>,
+        camera_mode: Res<CameraMode>,
+        camera_settings: Res<CameraSettings>,
+        mut transition: ResMut<CameraTransition>,
+        mut occlusion: CameraOcclusion,
     ) {
         let player_transform = param_set.p1().get_single().ok().cloned(); // Fetch player transform first
+        let blend = transition.advance(time.delta());
 
         let mut camera_query = param_set.p0();
-        for (mut transform, mut camera) in camera_query.iter_mut() {
+        for (mut transform, mut camera, mut projection) in camera_query.iter_mut() {
             let speed = 1.5 * time.delta_secs();
 
-            if keyboard_input.pressed(KeyCode::ArrowLeft) {
-                camera.yaw += speed;
-            }
-            if keyboard_input.pressed(KeyCode::ArrowRight) {
-                camera.yaw -= speed;
-            }
-            if keyboard_input.pressed(KeyCode::ArrowUp) {
-                camera.pitch += speed;
-            }
-            if keyboard_input.pressed(KeyCode::ArrowDown) {
-                camera.pitch -= speed;
+            // the arrow keys only steer the free orbiting camera
+            if *camera_mode == CameraMode::Orbit {
+                if keyboard_input.pressed(KeyCode::ArrowLeft) {
+                    camera.yaw += speed;
+                }
+                if keyboard_input.pressed(KeyCode::ArrowRight) {
+                    camera.yaw -= speed;
+                }
+                if keyboard_input.pressed(KeyCode::ArrowUp) {
+                    camera.pitch += speed;
+                }
+                if keyboard_input.pressed(KeyCode::ArrowDown) {
+                    camera.pitch -= speed;
+                }
             }
 
             camera.pitch = camera.pitch.clamp(
@@ -211,25 +682,74 @@ mod game {
             );
 
             if let Some(player_transform) = player_transform {
-                let x = player_transform.translation.x
-                    + camera.radius * camera.yaw.cos() * camera.pitch.cos();
-                let y = player_transform.translation.y + camera.radius * camera.pitch.sin();
-                let z = player_transform.translation.z
-                    + camera.radius * camera.yaw.sin() * camera.pitch.cos();
-
-                transform.translation = Vec3::new(x, y, z);
-                transform.look_at(player_transform.translation, Vec3::Y);
+                let mut target =
+                    camera_target(*camera_mode, &camera, &camera_settings, &player_transform);
+                if *camera_mode == CameraMode::FirstPerson {
+                    occlusion.clear();
+                } else {
+                    // aim a little above the robot's base so the ground doesn't count as cover
+                    let focus = player_transform.translation + Vec3::Y * 5.0;
+                    occlusion.resolve(focus, &mut target, &camera_settings, time.delta_secs());
+                }
+                let target_fov = camera_settings.mode(*camera_mode).fov;
+                let fov = if let Some(blend) = blend {
+                    // blend from where the camera was when the mode was switched
+                    transform.translation = transition.translation.lerp(target.translation, blend);
+                    transform.rotation = transition.rotation.slerp(target.rotation, blend);
+                    transition.fov.lerp(target_fov, blend)
+                } else if *camera_mode == CameraMode::Chase {
+                    let t = 1.0 - (-camera_settings.chase_stiffness * time.delta_secs()).exp();
+                    transform.translation = transform.translation.lerp(target.translation, t);
+                    transform.rotation = transform.rotation.slerp(target.rotation, t);
+                    target_fov
+                } else {
+                    transform.translation = target.translation;
+                    transform.rotation = target.rotation;
+                    target_fov
+                };
+                if let Projection::Perspective(perspective) = projection.as_mut() {
+                    perspective.fov = fov;
+                }
+            }
+        }
+    }
+    /// everything that goes into how fast the robot can drive
+    #[derive(SystemParam)]
+    pub struct TopSpeed<'w> {
+        progress: Res<'w, Progress>,
+        battery: Res<'w, Battery>,
+        game_mode: Res<'w, GameMode>,
+        deliveries: Res<'w, Deliveries>,
+    }
+    impl TopSpeed<'_> {
+        /// how fast the robot can drive on its own right now, before the parcel it carries and
+        /// the wind are taken into account
+        pub fn get(&self) -> f32 {
+            let mut speed = BASE_SPEED * self.progress.speed_scale() * self.battery.speed_scale();
+            if *self.game_mode == GameMode::Survival {
+                speed *=
+                    (1.0 + SURVIVAL_SPEEDUP * self.deliveries.0 as f32).min(SURVIVAL_MAX_SPEED);
             }
+            speed
         }
     }
     fn move_player(
         keyboard_input: Res<ButtonInput<KeyCode>>,
-        mut query: Query<(&mut Transform, &mut PlayerModel)>,
+        mut query: Query<(&mut Transform, &mut PlayerMotion), With<PlayerModel>>,
         time: Res<Time>,
+        atmosphere: Res<AtmosphereModel>,
+        top_speed: TopSpeed,
+        cube_query: Query<&CheckPointCube>,
+        progress: Res<Progress>,
     ) {
-        let speed = 50.0;
-        let rotation_speed = 3.0;
-        for (mut transform, _player_model) in query.iter_mut() {
+        let mut speed = top_speed.get();
+        for cube in cube_query.iter() {
+            speed *= cube.parcel.speed_scale();
+        }
+        let rotation_speed = 3.0 * progress.handling_scale();
+        // how quickly the robot reaches the speed the keys ask for on a dry road
+        let grip = 20.0 * progress.handling_scale();
+        for (mut transform, mut motion) in query.iter_mut() {
             let mut direction = Vec3::ZERO;
             if keyboard_input.pressed(KeyCode::KeyW) {
                 direction.x += 1.0;
@@ -243,9 +763,12 @@ mod game {
             if keyboard_input.pressed(KeyCode::KeyD) {
                 direction.z += 1.0;
             }
+            direction = direction.normalize_or_zero();
+            // wet roads make the robot slide towards where it's being steered
+            let response = 1.0 - (-grip * atmosphere.traction * time.delta_secs()).exp();
+            motion.velocity = motion.velocity.lerp(direction * speed, response);
+            transform.translation += (motion.velocity + atmosphere.wind) * time.delta_secs();
             if direction != Vec3::ZERO {
-                direction = direction.normalize();
-                transform.translation += direction * speed * time.delta_secs();
                 if keyboard_input.pressed(KeyCode::KeyA) || keyboard_input.pressed(KeyCode::KeyD) {
                     let target_rotation = Quat::from_rotation_arc(Vec3::Z, direction);
                     transform.rotation = transform
@@ -255,60 +778,148 @@ mod game {
             }
         }
     }
+    /// the robot's own driving, so wind and teleports don't count
+    fn measure_drive(
+        time: Res<Time>,
+        player_query: Query<&PlayerMotion, With<PlayerModel>>,
+        mut drove_events: EventWriter<RobotDrove>,
+    ) {
+        if let Ok(motion) = player_query.get_single() {
+            let distance = motion.velocity.with_y(0.0).length() * time.delta_secs();
+            if distance > 0.0 {
+                drove_events.send(RobotDrove { distance });
+            }
+        }
+    }
+    /// everything the robot running into things can set off
+    type CollisionEvents<'w> = (
+        EventWriter<'w, SoundEvent>,
+        EventWriter<'w, ParcelPickedUp>,
+        EventWriter<'w, DeliveryMade>,
+        EventWriter<'w, RobotBumped>,
+        EventWriter<'w, RobotCrashed>,
+    );
     fn detect_collisions(
         mut commands: Commands,
-        mut game_state: ResMut<NextState<GameState>>,
-        mut player_points: ResMut<PlayerPoints>,
-        player_query: Query<&Transform, With<PlayerModel>>,
-        person_query: Query<(Entity, &Transform), With<PeopleModel>>,
-        cube_query: Query<(Entity, &Transform), With<CheckPointCube>>,
-        building_query: Query<&Transform, With<BuildingModel>>,
-        asset_server: Res<AssetServer>,
+        (mut game_state, game_mode): (ResMut<NextState<GameState>>, Res<GameMode>),
+        mut player_query: Query<&mut Transform, With<PlayerModel>>,
+        person_query: PeopleQuery,
+        cube_query: Query<(Entity, &Transform, &CheckPointCube), Without<PlayerModel>>,
+        building_query: BuildingQuery,
+        (mut player_points, atmosphere, progress, cheats): (
+            ResMut<PlayerPoints>,
+            Res<AtmosphereModel>,
+            Res<Progress>,
+            Res<DevCheats>,
+        ),
+        (
+            mut sound_events,
+            mut pickup_events,
+            mut delivery_events,
+            mut bump_events,
+            mut crash_events,
+        ): CollisionEvents,
     ) {
-        if let Ok(player_transform) = player_query.get_single() {
+        if let Ok(mut player_transform) = player_query.get_single_mut() {
             for (entity, person_transform) in person_query.iter() {
                 let distance = player_transform
                     .translation
                     .distance(person_transform.translation);
-                if distance < 20.0 {
+                if distance < progress.magnet_range() {
                     // Collision with person model
                     commands.entity(entity).despawn_recursive();
-                    commands.spawn(AudioPlayer::new(
-                        asset_server.load("Audio/INeedThisDelivered.ogg"),
-                    ));
-
-                    spawn_light_blue_cube(&mut commands, &asset_server);
+                    pickup_events.send(ParcelPickedUp {
+                        at: person_transform.translation,
+                    });
                     return;
                 }
             }
 
-            for (entity, cube_transform) in cube_query.iter() {
+            for (entity, cube_transform, cube) in cube_query.iter() {
                 let distance = player_transform
                     .translation
                     .distance(cube_transform.translation);
-                if distance < 20.0 {
+                if distance < progress.magnet_range() {
                     // Collision with light-blue cube
                     commands.entity(entity).despawn_recursive();
-                    commands.spawn(AudioPlayer::new(asset_server.load("Audio/GoodJobPal.ogg")));
-                    player_points.0 += 1;
-                    spawn_person_model(&mut commands, &asset_server);
+                    let secs = cube.on_board.elapsed_secs();
+                    let late = secs > cube.deadline_secs;
+                    let sound = if late {
+                        Sound::LateDelivery
+                    } else {
+                        Sound::Delivery
+                    };
+                    sound_events.send(SoundEvent::at(sound, cube_transform.translation));
+                    // deliveries after dark pay a bonus point
+                    let mut points = if atmosphere.is_night() { 2 } else { 1 };
+                    // parcels that make their deadline pay a point on top
+                    if !late {
+                        points += 1;
+                    }
+                    player_points.0 += cube.parcel.pay(points);
+                    delivery_events.send(DeliveryMade { secs, late });
                     return;
                 }
             }
-            for building_transform in building_query.iter() {
+            if cheats.noclip {
+                return;
+            }
+            let crashes = game_mode.crashes() && !cheats.god;
+            // an oversized parcel sticks out past the robot and won't go through the tight gaps
+            let clearance = BUILDING_CRASH_RADIUS
+                + cube_query
+                    .iter()
+                    .map(|(_, _, cube)| cube.parcel.extra_clearance())
+                    .sum::<f32>();
+            for (building, building_transform) in building_query.iter() {
                 let distance = player_transform
                     .translation
                     .distance(building_transform.translation);
-                if distance < 20.0 {
+                if distance < clearance && (distance >= BUILDING_CRASH_RADIUS || !crashes) {
+                    // nothing breaks in Zen, the robot just bumps off the building
+                    let away = (player_transform.translation - building_transform.translation)
+                        .with_y(0.0)
+                        .normalize_or(Vec3::X);
+                    let bumped = building_transform.translation + away * clearance;
+                    player_transform.translation.x = bumped.x;
+                    player_transform.translation.z = bumped.z;
+                    bump_events.send(RobotBumped);
+                } else if distance < BUILDING_CRASH_RADIUS {
                     // Collision with building model
+                    shout_about_crash(
+                        player_transform.translation,
+                        &person_query,
+                        &mut sound_events,
+                    );
+                    crash_events.send(RobotCrashed {
+                        at: player_transform.translation,
+                        cause: CrashCause::Building,
+                        building: Some(building),
+                    });
                     game_state.set(GameState::Lose);
                     return;
                 }
             }
 
             let player_distance_from_center = player_transform.translation.length();
-            if player_distance_from_center > 200.0 {
+            if player_distance_from_center > WALL_RADIUS && !crashes {
+                let height = player_transform.translation.y;
+                player_transform.translation = (player_transform.translation * WALL_RADIUS
+                    / player_distance_from_center)
+                    .with_y(height);
+                bump_events.send(RobotBumped);
+            } else if player_distance_from_center > WALL_RADIUS {
                 // Collision with ring around the area
+                shout_about_crash(
+                    player_transform.translation,
+                    &person_query,
+                    &mut sound_events,
+                );
+                crash_events.send(RobotCrashed {
+                    at: player_transform.translation,
+                    cause: CrashCause::Wall,
+                    building: None,
+                });
                 game_state.set(GameState::Lose);
             }
         }
@@ -333,7 +944,19 @@ mod game {
         asset_server: Res<AssetServer>,
         query: Query<Entity, With<PlayerModel>>,
         query2: Query<Entity, With<RotatableCamera>>,
-        //player_points: Res<PlayerPoints>,
+        (camera_mode, camera_settings): (Res<CameraMode>, Res<CameraSettings>),
+        (run_in_progress, selected_map, mut run_rng, mut run_seed): (
+            Option<Res<RunInProgress>>,
+            Res<SelectedMap>,
+            ResMut<RunRng>,
+            ResMut<RunSeed>,
+        ),
+        (mut player_points, game_mode, round_length, progress): (
+            ResMut<PlayerPoints>,
+            Res<GameMode>,
+            Res<RoundLength>,
+            Res<Progress>,
+        ),
     ) {
         // Spawn the atmosphere camera component
         if query2.is_empty() {
@@ -345,19 +968,23 @@ mod game {
                         order: 1,
                         ..default()
                     },
+                    Projection::Perspective(PerspectiveProjection {
+                        fov: camera_settings.mode(*camera_mode).fov,
+                        ..default()
+                    }),
                     RotatableCamera {
-                        radius: 350.0,
+                        radius: camera_settings.orbit.radius,
                         yaw: std::f32::consts::PI,
                         pitch: 1.0,
                     },
+                    audio::listener(),
                 ))
                 .insert(AtmosphereCamera);
         }
-        // Insert the default atmosphere model
-        commands.insert_resource(AtmosphereModel);
         commands
             .spawn((
                 Scoreboard,
+                OnGameScreen,
                 Text::new("Points: "),
                 TextFont {
                     font_size: 40.0,
@@ -378,118 +1005,50 @@ mod game {
                 TextColor(TXT_CLR),
             ));
 
-        // Load and spawn the 3D model
-        let building_list = [
-            ("Models/building1.glb#Scene0"),
-            ("Models/building1.glb#Scene0"),
-            ("Models/building1.glb#Scene0"),
-            ("Models/building1.glb#Scene0"),
-            ("Models/building1.glb#Scene0"),
-            ("Models/building1.glb#Scene0"),
-            ("Models/building1.glb#Scene0"),
-            ("Models/building1.glb#Scene0"),
-            ("Models/building1.glb#Scene0"),
-            ("Models/building2.glb#Scene0"),
-            ("Models/building2.glb#Scene0"),
-            ("Models/building2.glb#Scene0"),
-            ("Models/building2.glb#Scene0"),
-            ("Models/building2.glb#Scene0"),
-            ("Models/building2.glb#Scene0"),
-            ("Models/building2.glb#Scene0"),
-            ("Models/building2.glb#Scene0"),
-            ("Models/building3.glb#Scene0"),
-            ("Models/building3.glb#Scene0"),
-            ("Models/building3.glb#Scene0"),
-            ("Models/building3.glb#Scene0"),
-            ("Models/building3.glb#Scene0"),
-            ("Models/building3.glb#Scene0"),
-            ("Models/building3.glb#Scene0"),
-            ("Models/building3.glb#Scene0"),
-            ("Models/building3.glb#Scene0"),
-            ("Models/building4.glb#Scene0"),
-            ("Models/building4.glb#Scene0"),
-            ("Models/building4.glb#Scene0"),
-            ("Models/building4.glb#Scene0"),
-            ("Models/building4.glb#Scene0"),
-            ("Models/building4.glb#Scene0"),
-            ("Models/building4.glb#Scene0"),
-            ("Models/building4.glb#Scene0"),
-            ("Models/building4.glb#Scene0"),
-            ("Models/building5.glb#Scene0"),
-            ("Models/building5.glb#Scene0"),
-            ("Models/building5.glb#Scene0"),
-            ("Models/building5.glb#Scene0"),
-            ("Models/building5.glb#Scene0"),
-            ("Models/building5.glb#Scene0"),
-            ("Models/building5.glb#Scene0"),
-            ("Models/building5.glb#Scene0"),
-        ];
-        let building_coords = [
-            // coordinates for building type 1
-            (25.0, 1.1, 25.0, 0.0, 0.0, 0.0),
-            (70.0, 1.1, 25.0, 0.0, 0.0, 0.0),
-            (-70.0, 1.1, -25.0, 0.0, 0.0, 0.0),
-            (-25.0, 1.1, -170.0, 0.0, 0.0, 0.0),
-            (-70.0, 1.1, 75.0, 0.0, 0.0, 0.0),
-            (-75.0, 1.1, -125.0, 0.0, 0.0, 0.0),
-            (-165.0, 1.1, -25.0, 0.0, 0.0, 0.0),
-            (-165.0, 1.1, 75.0, 0.0, 0.0, 0.0),
-            (120.0, 1.1, -125.0, 0.0, 0.0, 0.0),
-            // coordinates for building type 2
-            (70.0, 1.1, -40.0, 0.0, 0.0, 0.0),
-            (25.0, 1.1, 90.0, 0.0, 0.0, 0.0),
-            (-120.0, 1.1, -40.0, 0.0, 0.0, 0.0),
-            (-25.0, 1.1, 90.0, 0.0, 0.0, 0.0),
-            (70.0, 1.1, -120.0, 0.0, 0.0, 0.0),
-            (-120.0, 1.1, 90.0, 0.0, 0.0, 0.0),
-            (120.0, 1.1, 90.0, 0.0, 0.0, 0.0),
-            (165.0, 1.1, 45.0, 0.0, 0.0, 0.0),
-            // coordinates for building type 3
-            (-25.0, 1.1, 25.0, 0.0, 0.0, 0.0),
-            (25.0, 1.1, 155.0, 0.0, 0.0, 0.0),
-            (25.0, 1.1, -170.0, 0.0, 0.0, 0.0),
-            (-25.0, 1.1, 155.0, 0.0, 0.0, 0.0),
-            (-70.0, 1.1, -75.0, 0.0, 0.0, 0.0),
-            (-120.0, 1.1, -105.0, 0.0, 0.0, 0.0),
-            (160.0, 1.1, -25.0, 0.0, 0.0, 0.0),
-            (120.0, 1.1, 25.0, 0.0, 0.0, 0.0),
-            (160.0, 1.1, -75.0, 0.0, 0.0, 0.0),
-            // coordinates for building type 4
-            (25.0, 1.1, -25.0, 0.0, 0.0, 0.0),
-            (25.0, 1.1, -75.0, 0.0, 0.0, 0.0),
-            (-25.0, 1.1, -75.0, 0.0, 0.0, 0.0),
-            (-70.0, 1.1, 25.0, 0.0, 0.0, 0.0),
-            (70.0, 1.1, 75.0, 0.0, 0.0, 0.0),
-            (-70.0, 1.1, 120.0, 0.0, 0.0, 0.0),
-            (120.0, 1.1, -25.0, 0.0, 0.0, 0.0),
-            (-165.0, 1.1, 25.0, 0.0, 0.0, 0.0),
-            (-70.0, 1.1, 165.0, 0.0, 0.0, 0.0),
-            //coordinates for building type 5
-            (-25.0, 1.1, -25.0, 0.0, 0.0, 0.0),
-            (25.0, 1.1, -125.0, 0.0, 0.0, 0.0),
-            (-25.0, 1.1, -125.0, 0.0, 0.0, 0.0),
-            (-120.0, 1.1, 25.0, 0.0, 0.0, 0.0),
-            (70.0, 1.1, 120.0, 0.0, 0.0, 0.0),
-            (120.0, 1.1, -75.0, 0.0, 0.0, 0.0),
-            (-160.0, 1.1, -75.0, 0.0, 0.0, 0.0),
-            (70.0, 1.1, 165.0, 0.0, 0.0, 0.0),
-        ];
-
-        let model_handle = asset_server.load("Models/island.glb#Scene0");
         commands.spawn((
-            SceneRoot(model_handle),
-            Transform::from_xyz(0.0, 0.0, 0.0),
-            PlatformModel,
+            DeadlineText,
+            OnGameScreen,
+            Text::new(""),
+            TextFont {
+                font_size: 30.0,
+                ..default()
+            },
+            TextColor(TXT_CLR),
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(50.0),
+                ..default()
+            },
         ));
-        if query.is_empty() {
+
+        // Load and spawn the 3D model
+
+        if run_in_progress.is_none() {
+            let model_handle = asset_server.load(selected_map.info().scene);
+            commands.spawn((
+                SceneRoot(model_handle),
+                Transform::from_xyz(0.0, 0.0, 0.0),
+                PlatformModel,
+            ));
+            // a new run puts the robot back at the start rather than where the last one ended
+            for entity in query.iter() {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        if query.is_empty() || run_in_progress.is_none() {
             let player_model = asset_server.load("Models/bot_main.glb#Scene0");
             commands.spawn((
                 SceneRoot(player_model),
                 Transform::from_xyz(0.0, 1.1, 0.0),
                 PlayerModel,
+                PlayerMotion::default(),
             ));
         }
-        for (model, coords) in building_list.iter().zip(building_coords.iter()) {
+        for (slot, (model, coords)) in BUILDING_MODELS
+            .iter()
+            .zip(BUILDING_COORDS.iter())
+            .enumerate()
+        {
             let building = asset_server.load(*model);
             let rotation = Quat::from_euler(EulerRot::XYZ, coords.3, coords.4, coords.5);
             commands.spawn((
@@ -501,90 +1060,233 @@ mod game {
                 },
                 SpawnedModel,
                 BuildingModel,
+                BuildingSlot(slot),
             ));
         }
 
-        //commands.insert_resource(PlayerPoints::default());
-        spawn_person_model(&mut commands, &asset_server);
-        commands.insert_resource(GameTimer(Timer::from_seconds(60.0, TimerMode::Once)));
+        if run_in_progress.is_none() {
+            player_points.0 = 0;
+            commands.insert_resource(Deliveries::default());
+            commands.insert_resource(Battery::full(&progress));
+            let seed = run_seed.next.take().unwrap_or_else(|| thread_rng().gen());
+            run_seed.current = seed;
+            **run_rng = StdRng::seed_from_u64(seed);
+            // Insert the default atmosphere model
+            commands.insert_resource(AtmosphereModel::new(seed));
+            commands.insert_resource(RunInProgress);
+            commands.remove_resource::<CheatedRun>();
+        }
+        if let Some(secs) = game_mode.starting_secs(*round_length) {
+            commands.insert_resource(GameTimer(Timer::from_seconds(secs, TimerMode::Once)));
+        }
+    }
+    /// wraps up the run, if one was going, and records its score
+    pub fn end_run(
+        mut commands: Commands,
+        (run_in_progress, timer): (Option<Res<RunInProgress>>, Option<Res<GameTimer>>),
+        (cheated, replay): (Option<Res<CheatedRun>>, Option<Res<ReplayRun>>),
+        player_points: Res<PlayerPoints>,
+        (game_mode, round_length, difficulty): (Res<GameMode>, Res<RoundLength>, Res<Difficulty>),
+        mut high_scores: ResMut<HighScores>,
+        profile: Res<ActiveProfile>,
+    ) {
+        if run_in_progress.is_none() {
+            return;
+        }
+        commands.remove_resource::<RunInProgress>();
+        commands.remove_resource::<GameTimer>();
+
+        let previous_best = high_scores.best(*game_mode, *round_length, *difficulty);
+        let new_best = cheated.is_none() && replay.is_none() && player_points.0 > previous_best;
+        if new_best {
+            high_scores.0.insert(
+                game_mode.score_key(*round_length, *difficulty),
+                player_points.0,
+            );
+            let entries: Vec<(&str, String)> = high_scores
+                .0
+                .iter()
+                .map(|(key, score)| (key.as_str(), score.to_string()))
+                .collect();
+            write_key_values(&profile.path(HIGH_SCORES_FILE), &entries);
+        }
+        commands.insert_resource(LastRun {
+            mode: game_mode.label(*round_length),
+            difficulty: *difficulty,
+            score: player_points.0,
+            best: if new_best {
+                player_points.0
+            } else {
+                previous_best
+            },
+            new_best,
+            out_of_time: timer.is_some_and(|timer| timer.finished()),
+        });
+    }
+    /// cheats only last for the run they were turned on in
+    fn reset_cheats(mut cheats: ResMut<DevCheats>) {
+        *cheats = DevCheats::default();
+    }
+    fn load_high_scores(mut commands: Commands, profile: Res<ActiveProfile>) {
+        let scores = read_key_values(&profile.path(HIGH_SCORES_FILE))
+            .into_iter()
+            .filter_map(|(key, value)| Some((key, value.parse().ok()?)))
+            .collect();
+        commands.insert_resource(HighScores(scores));
+    }
+    /// the parts of each game mode that happen on a delivery
+    fn apply_mode_rules(
+        mut delivery_events: EventReader<DeliveryMade>,
+        game_mode: Res<GameMode>,
+        mut deliveries: ResMut<Deliveries>,
+        mut player_points: ResMut<PlayerPoints>,
+        timer: Option<ResMut<GameTimer>>,
+    ) {
+        let delivered = delivery_events.read().count() as u32;
+        if delivered == 0 {
+            return;
+        }
+        deliveries.0 += delivered;
+        match *game_mode {
+            GameMode::Endless => {
+                if let Some(mut timer) = timer {
+                    let duration = timer.duration()
+                        + Duration::from_secs(ENDLESS_BONUS_SECS * delivered as u64);
+                    timer.set_duration(duration);
+                }
+            }
+            GameMode::Survival => {
+                // the longer a Survival run lasts, the more each delivery is worth
+                player_points.0 += (deliveries.0 / SURVIVAL_LEVEL_DELIVERIES) as usize;
+            }
+            GameMode::Timed | GameMode::Zen => {}
+        }
     }
+    /// clears the island away when leaving the game, the drop-off stays for a paused run to
+    /// pick back up and is cleared once the run ends instead
     fn despawn_models(
         mut commands: Commands,
-        query: Query<(Entity, Option<&PlayerModel>), With<SpawnedModel>>,
+        query: Query<(Entity, Has<PlayerModel>, Has<CheckPointCube>), With<SpawnedModel>>,
     ) {
-        for (entity, is_player) in query.iter() {
-            if is_player.is_none() {
+        for (entity, is_player, is_drop_off) in query.iter() {
+            if !is_player && !is_drop_off {
                 // Only despawn non-player models
                 commands.entity(entity).despawn_recursive();
             }
         }
     }
-    fn spawn_person_model(commands: &mut Commands, asset_server: &Res<AssetServer>) {
-        let people_coords = [
-            (165.0, 1.1, 3.0),
-            (145.0, 1.1, 110.0),
-            (145.0, 1.1, -100.0),
-            (95.0, 1.1, 155.0),
-            (95.0, 1.1, -150.0),
-            (45.0, 1.1, 180.0),
-            (50.0, 1.1, -180.0),
-            (0.0, 1.1, -190.0),
-            (0.0, 1.1, 190.0),
-            (-50.0, 1.1, 180.0),
-            (-45.0, 1.1, -180.0),
-            (-95.0, 1.1, 150.0),
-            (-95.0, 1.1, -155.0),
-            (-145.0, 1.1, 100.0),
-            (-145.0, 1.1, -110.0),
-            (-165.0, 1.1, 0.0),
-        ];
+    /// puts a sender out at the spot the dispatcher picked, waiting for the robot
+    pub fn spawn_person_model(
+        commands: &mut Commands,
+        asset_server: &Res<AssetServer>,
+        position: Vec3,
+        appearance: PersonAppearance,
+    ) {
         let person_model = asset_server.load("Models/person.glb#Scene0");
-        let mut rng = thread_rng();
-        let random_coord = people_coords.choose(&mut rng).unwrap();
         commands.spawn((
             SceneRoot(person_model),
-            Transform::from_xyz(random_coord.0, random_coord.1, random_coord.2),
+            Transform::from_translation(position),
+            appearance,
             PeopleModel,
             SpawnedModel,
         ));
     }
-    fn spawn_light_blue_cube(commands: &mut Commands, asset_server: &Res<AssetServer>) {
-        let people_coords = [
-            (165.0, 1.1, 3.0),
-            (145.0, 1.1, 110.0),
-            (145.0, 1.1, -100.0),
-            (95.0, 1.1, 155.0),
-            (95.0, 1.1, -150.0),
-            (45.0, 1.1, 180.0),
-            (50.0, 1.1, -180.0),
-            (0.0, 1.1, -190.0),
-            (0.0, 1.1, 190.0),
-            (-50.0, 1.1, 180.0),
-            (-45.0, 1.1, -180.0),
-            (-95.0, 1.1, 150.0),
-            (-95.0, 1.1, -155.0),
-            (-145.0, 1.1, 100.0),
-            (-145.0, 1.1, -110.0),
-            (-165.0, 1.1, 0.0),
-        ];
-        let mut rng = thread_rng();
-        let random_coord = people_coords.choose(&mut rng).unwrap();
+    /// the closest person within earshot reacts to the robot crashing
+    fn shout_about_crash(
+        crash: Vec3,
+        person_query: &PeopleQuery,
+        sound_events: &mut EventWriter<SoundEvent>,
+    ) {
+        let nearest = person_query
+            .iter()
+            .map(|(_, transform)| transform.translation)
+            .filter(|position| position.distance(crash) < CRASH_HEARING_DISTANCE)
+            .min_by(|a, b| a.distance(crash).total_cmp(&b.distance(crash)));
+        if let Some(position) = nearest {
+            sound_events.send(SoundEvent::at(Sound::CrashNearby, position));
+        }
+    }
+    /// the waiting sender calls out when the robot has been sitting still for a while
+    fn idle_chatter(
+        time: Res<Time>,
+        player_query: Query<&PlayerMotion, With<PlayerModel>>,
+        person_query: Query<&Transform, With<PeopleModel>>,
+        mut sound_events: EventWriter<SoundEvent>,
+        mut idle_secs: Local<f32>,
+    ) {
+        let moving = player_query
+            .iter()
+            .any(|motion| motion.velocity.length() > 1.0);
+        if moving {
+            *idle_secs = 0.0;
+            return;
+        }
+        *idle_secs += time.delta_secs();
+        if *idle_secs < IDLE_SECS {
+            return;
+        }
+        *idle_secs = 0.0;
+        if let Ok(person_transform) = person_query.get_single() {
+            sound_events.send(SoundEvent::at(Sound::Idle, person_transform.translation));
+        }
+    }
+    /// marks where the parcel that was just picked up has to go
+    pub fn spawn_light_blue_cube(
+        commands: &mut Commands,
+        asset_server: &Res<AssetServer>,
+        position: Vec3,
+        parcel: ParcelKind,
+        deadline_secs: f32,
+    ) {
         let cube_model = asset_server.load("Models/checkCube.glb#Scene0");
         commands.spawn((
             SceneRoot(cube_model),
-            Transform::from_xyz(random_coord.0, random_coord.1, random_coord.2),
-            CheckPointCube,
+            Transform::from_translation(position),
+            CheckPointCube {
+                parcel,
+                on_board: Stopwatch::new(),
+                deadline_secs,
+            },
             SpawnedModel,
         ));
     }
 
+    /// counts down the time left to get the parcel to its drop-off
+    fn update_deadline(
+        time: Res<Time>,
+        mut cube_query: Query<&mut CheckPointCube>,
+        mut text_query: Query<&mut Text, With<DeadlineText>>,
+    ) {
+        let label = match cube_query.get_single_mut() {
+            Ok(mut cube) => {
+                cube.on_board.tick(time.delta());
+                let left = cube.deadline_secs - cube.on_board.elapsed_secs();
+                let parcel = cube.parcel.label();
+                if left > 0.0 {
+                    format!("{parcel}: deliver within {}s", left.ceil())
+                } else {
+                    format!("{parcel}: late!")
+                }
+            }
+            Err(_) => String::new(),
+        };
+        for mut text in text_query.iter_mut() {
+            if text.0 != label {
+                text.0.clone_from(&label);
+            }
+        }
+    }
     fn game(
         time: Res<Time>,
         mut game_state: ResMut<NextState<GameState>>,
-        mut timer: ResMut<GameTimer>,
+        timer: Option<ResMut<GameTimer>>,
     ) {
+        // modes without a clock only end on a crash, or when the player quits
+        let Some(mut timer) = timer else {
+            return;
+        };
         if timer.tick(time.delta()).finished() {
-            game_state.set(GameState::Menu);
+            game_state.set(GameState::Lose);
         }
     }
     fn return_to_main(
@@ -625,58 +1327,866 @@ mod game {
             }
         }
     }
-}
 
-mod menu {
-    use super::CameraState;
-    use super::PlayerState;
-    use super::RotatableCamera;
-    use crate::game::PlayerModel;
-    use bevy::{app::AppExit, color::palettes::css::CRIMSON, prelude::*};
+    /// `teleport x z` moves the robot to that spot on the map
+    struct TeleportCommand;
+    impl ConsoleCommand for TeleportCommand {
+        fn name(&self) -> &'static str {
+            "teleport"
+        }
+        fn usage(&self) -> &'static str {
+            "x z"
+        }
+        fn run(&self, args: &[&str], world: &mut World) -> Result<String, String> {
+            let [x, z] = args else {
+                return Err("expected two coordinates".to_string());
+            };
+            let (Ok(x), Ok(z)) = (x.parse::<f32>(), z.parse::<f32>()) else {
+                return Err("coordinates have to be numbers".to_string());
+            };
+            let mut query =
+                world.query_filtered::<(&mut Transform, &mut PlayerMotion), With<PlayerModel>>();
+            let Ok((mut transform, mut motion)) = query.get_single_mut(world) else {
+                return Err("there's no robot to move".to_string());
+            };
+            transform.translation.x = x;
+            transform.translation.z = z;
+            motion.velocity = Vec3::ZERO;
+            Ok(format!("robot moved to {x} {z}"))
+        }
+    }
+    /// `set points N` changes the score
+    struct SetCommand;
+    impl ConsoleCommand for SetCommand {
+        fn name(&self) -> &'static str {
+            "set"
+        }
+        fn usage(&self) -> &'static str {
+            "points N"
+        }
+        fn run(&self, args: &[&str], world: &mut World) -> Result<String, String> {
+            let ["points", points] = args else {
+                return Err("only points can be set".to_string());
+            };
+            let points = points
+                .parse()
+                .map_err(|_| "points have to be a whole number".to_string())?;
+            world.resource_mut::<PlayerPoints>().0 = points;
+            Ok(format!("points set to {points}"))
+        }
+    }
+    /// `timer +30` adds time to the clock, `-30` takes it away and a plain number sets what's left
+    struct TimerCommand;
+    impl ConsoleCommand for TimerCommand {
+        fn name(&self) -> &'static str {
+            "timer"
+        }
+        fn usage(&self) -> &'static str {
+            "+secs | -secs | secs"
+        }
+        fn run(&self, args: &[&str], world: &mut World) -> Result<String, String> {
+            let [change] = args else {
+                return Err("expected a number of seconds".to_string());
+            };
+            let secs: f32 = change
+                .parse()
+                .map_err(|_| "seconds have to be a number".to_string())?;
+            let Some(mut timer) = world.get_resource_mut::<GameTimer>() else {
+                return Err("this run has no clock".to_string());
+            };
+            let remaining = if change.starts_with(['+', '-']) {
+                timer.remaining_secs() + secs
+            } else {
+                secs
+            };
+            let duration = timer.elapsed_secs() + remaining.max(0.0);
+            timer.set_duration(Duration::from_secs_f32(duration));
+            Ok(format!("{:.0}s left on the clock", timer.remaining_secs()))
+        }
+    }
+    /// `state lose` jumps to another game state
+    struct StateCommand;
+    impl ConsoleCommand for StateCommand {
+        fn name(&self) -> &'static str {
+            "state"
+        }
+        fn usage(&self) -> &'static str {
+            "lose | pause | menu"
+        }
+        fn run(&self, args: &[&str], world: &mut World) -> Result<String, String> {
+            let state = match args {
+                ["lose"] => GameState::Lose,
+                ["pause"] => GameState::Pause,
+                ["menu"] => GameState::Menu,
+                _ => return Err("expected lose, pause or menu".to_string()),
+            };
+            world.resource_mut::<NextState<GameState>>().set(state);
+            Ok(format!("going to {state:?}"))
+        }
+    }
+    struct GodCommand;
+    impl ConsoleCommand for GodCommand {
+        fn name(&self) -> &'static str {
+            "god"
+        }
+        fn usage(&self) -> &'static str {
+            ""
+        }
+        fn run(&self, _args: &[&str], world: &mut World) -> Result<String, String> {
+            let mut cheats = world.resource_mut::<DevCheats>();
+            cheats.god = !cheats.god;
+            Ok(format!(
+                "god mode {}",
+                if cheats.god { "on" } else { "off" }
+            ))
+        }
+    }
+    struct NoclipCommand;
+    impl ConsoleCommand for NoclipCommand {
+        fn name(&self) -> &'static str {
+            "noclip"
+        }
+        fn usage(&self) -> &'static str {
+            ""
+        }
+        fn run(&self, _args: &[&str], world: &mut World) -> Result<String, String> {
+            let mut cheats = world.resource_mut::<DevCheats>();
+            cheats.noclip = !cheats.noclip;
+            Ok(format!(
+                "noclip {}",
+                if cheats.noclip { "on" } else { "off" }
+            ))
+        }
+    }
+    /// `seed N` reseeds the run, so what gets drawn from here on can be played again
+    struct SeedCommand;
+    impl ConsoleCommand for SeedCommand {
+        fn name(&self) -> &'static str {
+            "seed"
+        }
+        fn usage(&self) -> &'static str {
+            "N"
+        }
+        fn run(&self, args: &[&str], world: &mut World) -> Result<String, String> {
+            let [seed] = args else {
+                return Err("expected a seed".to_string());
+            };
+            let seed: u64 = seed
+                .parse()
+                .map_err(|_| "the seed has to be a whole number".to_string())?;
+            **world.resource_mut::<RunRng>() = StdRng::seed_from_u64(seed);
+            Ok(format!("run reseeded with {seed}"))
+        }
+    }
+}
 
-    use super::{despawn_screen, GameState, TXT_CLR};
+mod camera {
+    use super::{GameState, InvertMouse, MouseSensitivity, RotatableCamera};
+    use crate::game::BuildingModel;
+    use bevy::{
+        ecs::system::SystemParam,
+        input::mouse::{AccumulatedMouseMotion, AccumulatedMouseScroll, MouseScrollUnit},
+        picking::mesh_picking::ray_cast::{MeshRayCast, RayCastSettings},
+        prelude::*,
+        window::{CursorGrabMode, PrimaryWindow},
+    };
+    use std::time::Duration;
 
-    // This plugin manages the menu, with 5 different screens:
-    // - a main menu with "New Game", "Settings", "Quit"
-    // - a settings menu with two submenus and a back button
-    // - two settings screen with a setting that can be set and a back button
-    pub fn menu_plugin(app: &mut App) {
-        app.init_state::<MenuState>()
-            .add_systems(OnEnter(GameState::Menu), menu_setup)
-            // Systems to handle the main menu screen
-            .add_systems(OnEnter(MenuState::Main), main_menu_setup)
-            .add_systems(OnExit(MenuState::Main), despawn_screen::<OnMainMenuScreen>)
-            // Systems to handle the help menu screen
-            .add_systems(OnEnter(MenuState::Help), help_menu_setup)
-            .add_systems(OnExit(MenuState::Help), despawn_screen::<OnHelpMenuScreen>)
-            // Systems to handle the pause menu screen
-            .add_systems(OnEnter(GameState::Pause), pause_menu_setup)
-            .add_systems(
-                OnExit(GameState::Pause),
-                despawn_screen::<OnPauseMenuScreen>,
-            )
-            .add_systems(OnEnter(GameState::Lose), lose_menu_setup)
-            .add_systems(OnExit(GameState::Lose), despawn_screen::<OnLoseMenuScreen>)
-            // Common systems to all screens that handles buttons behavior
-            .add_systems(
-                Update,
-                (menu_action, button_system).run_if(in_state(GameState::Menu)),
-            )
-            .add_systems(
-                Update,
-                (menu_action, button_system).run_if(in_state(GameState::Pause)),
-            )
+    // This plugin manages the different ways the game camera can follow the robot:
+    // - orbit, the original fixed radius orbit steered with the arrow keys
+    // - chase, which trails behind the robot's heading
+    // - top down, a tactical view straight down onto the city
+    // - first person, from the robot's cockpit
+    // the actual placement of the camera is done by `rotate_camera` in the game module
+    pub fn camera_plugin(app: &mut App) {
+        app.init_resource::<CameraMode>()
+            .init_resource::<CameraSettings>()
+            .init_resource::<CameraTransition>()
+            .init_resource::<OcclusionState>()
+            .init_resource::<OrbitSmoothing>()
+            .add_systems(OnEnter(GameState::Game), grab_cursor)
+            .add_systems(OnExit(GameState::Game), release_cursor)
             .add_systems(
                 Update,
-                (menu_action, button_system).run_if(in_state(GameState::Lose)),
+                (
+                    cycle_camera_mode.run_if(in_state(GameState::Game)),
+                    mouse_orbit.run_if(in_state(GameState::Game)),
+                    fade_occluders.run_if(in_state(GameState::Game)),
+                ),
             );
     }
 
-    // State used for the current menu screen
-    #[derive(Clone, Copy, Default, Eq, PartialEq, Debug, Hash, States)]
+    #[derive(Resource, Debug, Component, PartialEq, Eq, Clone, Copy, Default)]
+    pub enum CameraMode {
+        #[default]
+        Orbit,
+        Chase,
+        TopDown,
+        FirstPerson,
+    }
+    impl CameraMode {
+        pub const ALL: [CameraMode; 4] = [
+            CameraMode::Orbit,
+            CameraMode::Chase,
+            CameraMode::TopDown,
+            CameraMode::FirstPerson,
+        ];
+        /// the name shown in the camera settings
+        pub fn label(self) -> &'static str {
+            match self {
+                CameraMode::Orbit => "Orbit",
+                CameraMode::Chase => "Chase",
+                CameraMode::TopDown => "Top Down",
+                CameraMode::FirstPerson => "First Person",
+            }
+        }
+        /// the name the mode's settings are saved under
+        pub fn key(self) -> &'static str {
+            match self {
+                CameraMode::Orbit => "orbit",
+                CameraMode::Chase => "chase",
+                CameraMode::TopDown => "top_down",
+                CameraMode::FirstPerson => "first_person",
+            }
+        }
+        /// the mode the camera switches to when the hotkey is pressed
+        pub fn next(self) -> Self {
+            match self {
+                CameraMode::Orbit => CameraMode::Chase,
+                CameraMode::Chase => CameraMode::TopDown,
+                CameraMode::TopDown => CameraMode::FirstPerson,
+                CameraMode::FirstPerson => CameraMode::Orbit,
+            }
+        }
+    }
+    #[derive(Debug, Clone, Copy)]
+    pub struct CameraModeSettings {
+        /// distance between the camera and the robot, the orbit mode uses this as its starting radius
+        pub radius: f32,
+        /// height of the camera above the robot, unused by the orbit mode which uses its pitch instead
+        pub height: f32,
+        /// multiplier applied to both the radius and the height
+        pub zoom: f32,
+        /// vertical field of view in radians
+        pub fov: f32,
+    }
+    impl CameraModeSettings {
+        pub const MIN_ZOOM: f32 = 0.5;
+        pub const MAX_ZOOM: f32 = 2.0;
+        /// narrowest and widest field of view the settings allow, in degrees
+        pub const MIN_FOV: f32 = 40.0;
+        pub const MAX_FOV: f32 = 110.0;
+        /// sets the zoom, kept to hundredths so stepping it doesn't build up rounding errors
+        pub fn set_zoom(&mut self, zoom: f32) {
+            self.zoom = ((zoom * 100.0).round() / 100.0).clamp(Self::MIN_ZOOM, Self::MAX_ZOOM);
+        }
+        /// sets the field of view from degrees, which is how it's shown and saved
+        pub fn set_fov_degrees(&mut self, degrees: f32) {
+            self.fov = degrees.clamp(Self::MIN_FOV, Self::MAX_FOV).to_radians();
+        }
+        pub fn fov_degrees(&self) -> f32 {
+            self.fov.to_degrees()
+        }
+    }
+    #[derive(Resource, Debug, Clone, Copy)]
+    pub struct CameraSettings {
+        pub orbit: CameraModeSettings,
+        pub chase: CameraModeSettings,
+        pub top_down: CameraModeSettings,
+        pub first_person: CameraModeSettings,
+        /// how quickly the chase camera catches up with the robot
+        pub chase_stiffness: f32,
+        /// how long switching from one mode to another takes, in seconds
+        pub transition_secs: f32,
+        /// what happens when a building sits between the camera and the robot
+        pub occlusion: OcclusionMode,
+        /// gap left between a building and the camera when it gets pulled in
+        pub occlusion_padding: f32,
+        /// opacity buildings fade down to when they block the view
+        pub fade_alpha: f32,
+        /// closest the scroll wheel can bring the orbit camera
+        pub min_orbit_radius: f32,
+        /// furthest the scroll wheel can push the orbit camera out
+        pub max_orbit_radius: f32,
+        /// change in orbit radius for each notch of the scroll wheel
+        pub zoom_step: f32,
+        /// how quickly mouse drags and scrolling are eased into the orbit camera
+        pub mouse_smoothing: f32,
+    }
+    #[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+    pub enum OcclusionMode {
+        Off,
+        #[default]
+        PullIn,
+        Fade,
+    }
+    impl OcclusionMode {
+        pub fn next(self) -> Self {
+            match self {
+                OcclusionMode::Off => OcclusionMode::PullIn,
+                OcclusionMode::PullIn => OcclusionMode::Fade,
+                OcclusionMode::Fade => OcclusionMode::Off,
+            }
+        }
+    }
+    impl Default for CameraSettings {
+        fn default() -> Self {
+            CameraSettings {
+                orbit: CameraModeSettings {
+                    radius: 350.0,
+                    height: 0.0,
+                    zoom: 1.0,
+                    fov: std::f32::consts::FRAC_PI_4,
+                },
+                chase: CameraModeSettings {
+                    radius: 60.0,
+                    height: 25.0,
+                    zoom: 1.0,
+                    fov: 60.0_f32.to_radians(),
+                },
+                top_down: CameraModeSettings {
+                    radius: 0.0,
+                    height: 450.0,
+                    zoom: 1.0,
+                    fov: std::f32::consts::FRAC_PI_4,
+                },
+                first_person: CameraModeSettings {
+                    radius: 4.0,
+                    height: 10.0,
+                    zoom: 1.0,
+                    fov: 75.0_f32.to_radians(),
+                },
+                chase_stiffness: 4.0,
+                transition_secs: 0.6,
+                occlusion: OcclusionMode::default(),
+                occlusion_padding: 6.0,
+                fade_alpha: 0.25,
+                min_orbit_radius: 100.0,
+                max_orbit_radius: 600.0,
+                zoom_step: 25.0,
+                mouse_smoothing: 15.0,
+            }
+        }
+    }
+    impl CameraSettings {
+        pub fn mode(&self, mode: CameraMode) -> &CameraModeSettings {
+            match mode {
+                CameraMode::Orbit => &self.orbit,
+                CameraMode::Chase => &self.chase,
+                CameraMode::TopDown => &self.top_down,
+                CameraMode::FirstPerson => &self.first_person,
+            }
+        }
+        pub fn mode_mut(&mut self, mode: CameraMode) -> &mut CameraModeSettings {
+            match mode {
+                CameraMode::Orbit => &mut self.orbit,
+                CameraMode::Chase => &mut self.chase,
+                CameraMode::TopDown => &mut self.top_down,
+                CameraMode::FirstPerson => &mut self.first_person,
+            }
+        }
+    }
+    /// the camera pose captured when the mode was switched, blended towards the new mode's pose
+    #[derive(Resource, Default)]
+    pub struct CameraTransition {
+        timer: Option<Timer>,
+        pub translation: Vec3,
+        pub rotation: Quat,
+        pub fov: f32,
+    }
+    impl CameraTransition {
+        fn start(&mut self, transform: &Transform, fov: f32, secs: f32) {
+            self.timer = Some(Timer::from_seconds(secs, TimerMode::Once));
+            self.translation = transform.translation;
+            self.rotation = transform.rotation;
+            self.fov = fov;
+        }
+        /// ticks the transition and returns how far along it is, or None when no transition is running
+        pub fn advance(&mut self, delta: Duration) -> Option<f32> {
+            let timer = self.timer.as_mut()?;
+            timer.tick(delta);
+            let t = timer.fraction();
+            if timer.finished() {
+                self.timer = None;
+            }
+            // ease in and out so the camera doesn't jerk at either end
+            Some(t * t * (3.0 - 2.0 * t))
+        }
+    }
+
+    /// how far the camera is currently pulled in and which buildings are in the way
+    #[derive(Resource, Default)]
+    pub struct OcclusionState {
+        distance: Option<f32>,
+        occluders: Vec<Entity>,
+    }
+    /// put on the meshes of a faded building, keeping the shared material to restore later
+    #[derive(Component)]
+    struct FadedMaterial {
+        building: Entity,
+        original: Handle<StandardMaterial>,
+        alpha: f32,
+    }
+    /// everything `rotate_camera` needs to keep buildings from blocking the view of the robot
+    #[derive(SystemParam)]
+    pub struct CameraOcclusion<'w, 's> {
+        ray_cast: MeshRayCast<'w, 's>,
+        parents: Query<'w, 's, &'static Parent>,
+        buildings: Query<'w, 's, (), With<BuildingModel>>,
+        state: ResMut<'w, OcclusionState>,
+    }
+    impl CameraOcclusion<'_, '_> {
+        /// casts a ray from the robot towards where the camera wants to be and either pulls the
+        /// camera in front of the first building hit or remembers the buildings in the way so
+        /// `fade_occluders` can make them see through
+        pub fn resolve(
+            &mut self,
+            focus: Vec3,
+            target: &mut Transform,
+            camera_settings: &CameraSettings,
+            delta_secs: f32,
+        ) {
+            self.state.occluders.clear();
+            let offset = target.translation - focus;
+            let full_distance = offset.length();
+            let Ok(direction) = Dir3::new(offset) else {
+                self.state.distance = None;
+                return;
+            };
+            if camera_settings.occlusion == OcclusionMode::Off {
+                self.state.distance = None;
+                return;
+            }
+            let CameraOcclusion {
+                ray_cast,
+                parents,
+                buildings,
+                state,
+            } = self;
+            let filter = |entity| building_root(entity, parents, buildings).is_some();
+            let settings = RayCastSettings::default().with_filter(&filter);
+            let hits = ray_cast.cast_ray(Ray3d::new(focus, direction), &settings);
+            let blocking = hits.iter().filter(|(_, hit)| hit.distance < full_distance);
+            match camera_settings.occlusion {
+                OcclusionMode::PullIn => {
+                    let padding = camera_settings.occlusion_padding;
+                    let limit = blocking
+                        .map(|(_, hit)| (hit.distance - padding).max(padding))
+                        .fold(full_distance, f32::min);
+                    // snap in straight away so we never see inside a wall, then ease back out
+                    let current = state.distance.unwrap_or(full_distance);
+                    let eased =
+                        current + (full_distance - current) * (1.0 - (-4.0 * delta_secs).exp());
+                    let distance = eased.min(limit);
+                    state.distance = (distance < full_distance - 0.01).then_some(distance);
+                    target.translation = focus + direction * distance;
+                }
+                OcclusionMode::Fade => {
+                    state.distance = None;
+                    for (entity, _) in blocking {
+                        if let Some(root) = building_root(*entity, parents, buildings) {
+                            if !state.occluders.contains(&root) {
+                                state.occluders.push(root);
+                            }
+                        }
+                    }
+                }
+                OcclusionMode::Off => {}
+            }
+        }
+        /// forgets about any occlusion, used by modes where nothing can get in the way
+        pub fn clear(&mut self) {
+            self.state.distance = None;
+            self.state.occluders.clear();
+        }
+    }
+    /// walks up the scene hierarchy from a mesh to the building it belongs to
+    fn building_root(
+        mut entity: Entity,
+        parents: &Query<&Parent>,
+        buildings: &Query<(), With<BuildingModel>>,
+    ) -> Option<Entity> {
+        loop {
+            if buildings.contains(entity) {
+                return Some(entity);
+            }
+            entity = parents.get(entity).ok()?.get();
+        }
+    }
+    fn fade_occluders(
+        mut commands: Commands,
+        camera_settings: Res<CameraSettings>,
+        state: Res<OcclusionState>,
+        children: Query<&Children>,
+        mut meshes: Query<(
+            Entity,
+            &mut MeshMaterial3d<StandardMaterial>,
+            Option<&mut FadedMaterial>,
+        )>,
+        mut materials: ResMut<Assets<StandardMaterial>>,
+        time: Res<Time>,
+    ) {
+        // the building scenes share their materials, so each faded mesh gets its own copy
+        for building in &state.occluders {
+            for entity in children.iter_descendants(*building) {
+                let Ok((_, mut material, None)) = meshes.get_mut(entity) else {
+                    continue;
+                };
+                let Some(mut translucent) = materials.get(&material.0).cloned() else {
+                    continue;
+                };
+                translucent.alpha_mode = AlphaMode::Blend;
+                let original = std::mem::replace(&mut material.0, materials.add(translucent));
+                commands.entity(entity).insert(FadedMaterial {
+                    building: *building,
+                    original,
+                    alpha: 1.0,
+                });
+            }
+        }
+        let step = 3.0 * time.delta_secs();
+        for (entity, mut material, faded) in meshes.iter_mut() {
+            let Some(mut faded) = faded else {
+                continue;
+            };
+            if state.occluders.contains(&faded.building) {
+                faded.alpha = (faded.alpha - step).max(camera_settings.fade_alpha);
+            } else {
+                faded.alpha += step;
+                if faded.alpha >= 1.0 {
+                    // fully visible again, hand the shared material back
+                    material.0 = faded.original.clone();
+                    commands.entity(entity).remove::<FadedMaterial>();
+                    continue;
+                }
+            }
+            if let Some(translucent) = materials.get_mut(&material.0) {
+                translucent.base_color.set_alpha(faded.alpha);
+            }
+        }
+    }
+
+    /// mouse movement and scrolling not yet applied to the orbit camera
+    #[derive(Resource, Default)]
+    struct OrbitSmoothing {
+        yaw: f32,
+        pitch: f32,
+        radius: f32,
+    }
+    #[derive(SystemParam)]
+    struct MouseLook<'w> {
+        buttons: Res<'w, ButtonInput<MouseButton>>,
+        motion: Res<'w, AccumulatedMouseMotion>,
+        scroll: Res<'w, AccumulatedMouseScroll>,
+        sensitivity: Res<'w, MouseSensitivity>,
+        invert: Res<'w, InvertMouse>,
+    }
+    /// keeps the cursor inside the window while driving
+    fn grab_cursor(mut windows: Query<&mut Window, With<PrimaryWindow>>) {
+        if let Ok(mut window) = windows.get_single_mut() {
+            window.cursor_options.grab_mode = CursorGrabMode::Confined;
+            window.cursor_options.visible = true;
+        }
+    }
+    /// hands the cursor back for the menus
+    fn release_cursor(mut windows: Query<&mut Window, With<PrimaryWindow>>) {
+        if let Ok(mut window) = windows.get_single_mut() {
+            window.cursor_options.grab_mode = CursorGrabMode::None;
+            window.cursor_options.visible = true;
+        }
+    }
+    /// orbits the camera while the right mouse button is held and zooms it with the scroll wheel
+    fn mouse_orbit(
+        mouse: MouseLook,
+        camera_mode: Res<CameraMode>,
+        camera_settings: Res<CameraSettings>,
+        mut smoothing: ResMut<OrbitSmoothing>,
+        mut query: Query<&mut RotatableCamera>,
+        mut windows: Query<&mut Window, With<PrimaryWindow>>,
+        time: Res<Time>,
+    ) {
+        let dragging =
+            *camera_mode == CameraMode::Orbit && mouse.buttons.pressed(MouseButton::Right);
+        if let Ok(mut window) = windows.get_single_mut() {
+            // lock the cursor in place while dragging so it can't leave the window mid orbit
+            let grab_mode = if dragging {
+                CursorGrabMode::Locked
+            } else {
+                CursorGrabMode::Confined
+            };
+            if window.cursor_options.grab_mode != grab_mode {
+                window.cursor_options.grab_mode = grab_mode;
+                window.cursor_options.visible = !dragging;
+            }
+        }
+        if *camera_mode != CameraMode::Orbit {
+            *smoothing = OrbitSmoothing::default();
+            return;
+        }
+        if dragging {
+            let scale = 0.001 * mouse.sensitivity.0 as f32;
+            let vertical = if mouse.invert.0 { -1.0 } else { 1.0 };
+            smoothing.yaw += mouse.motion.delta.x * scale;
+            smoothing.pitch += mouse.motion.delta.y * scale * vertical;
+        }
+        let notches = match mouse.scroll.unit {
+            MouseScrollUnit::Line => mouse.scroll.delta.y,
+            MouseScrollUnit::Pixel => mouse.scroll.delta.y / 16.0,
+        };
+        smoothing.radius -= notches * camera_settings.zoom_step;
+
+        // ease a fraction of whatever is left in each frame
+        let t = 1.0 - (-camera_settings.mouse_smoothing * time.delta_secs()).exp();
+        let (yaw, pitch, radius) = (smoothing.yaw * t, smoothing.pitch * t, smoothing.radius * t);
+        smoothing.yaw -= yaw;
+        smoothing.pitch -= pitch;
+        smoothing.radius -= radius;
+        for mut camera in query.iter_mut() {
+            camera.yaw += yaw;
+            camera.pitch += pitch;
+            let zoomed = camera.radius + radius;
+            camera.radius = zoomed.clamp(
+                camera_settings.min_orbit_radius,
+                camera_settings.max_orbit_radius,
+            );
+            if camera.radius != zoomed {
+                // no point easing further into the limit
+                smoothing.radius = 0.0;
+            }
+        }
+    }
+
+    fn cycle_camera_mode(
+        keyboard_input: Res<ButtonInput<KeyCode>>,
+        mut camera_mode: ResMut<CameraMode>,
+        mut camera_settings: ResMut<CameraSettings>,
+        mut transition: ResMut<CameraTransition>,
+        query: Query<(&Transform, &Projection), With<RotatableCamera>>,
+    ) {
+        if keyboard_input.just_pressed(KeyCode::KeyC) {
+            if let Ok((transform, projection)) = query.get_single() {
+                let fov = match projection {
+                    Projection::Perspective(perspective) => perspective.fov,
+                    Projection::Orthographic(_) => camera_settings.mode(*camera_mode).fov,
+                };
+                transition.start(transform, fov, camera_settings.transition_secs);
+            }
+            *camera_mode = camera_mode.next();
+        }
+        if keyboard_input.just_pressed(KeyCode::KeyO) {
+            camera_settings.occlusion = camera_settings.occlusion.next();
+        }
+    }
+
+    /// works out where the camera should sit for the given mode and which way it should face
+    pub fn camera_target(
+        mode: CameraMode,
+        camera: &RotatableCamera,
+        camera_settings: &CameraSettings,
+        player_transform: &Transform,
+    ) -> Transform {
+        let settings = camera_settings.mode(mode);
+        let player = player_transform.translation;
+        // the robot is turned so that its local z axis points the way it is driving
+        let heading = (player_transform.rotation * Vec3::Z)
+            .with_y(0.0)
+            .try_normalize()
+            .unwrap_or(Vec3::X);
+        match mode {
+            CameraMode::Orbit => {
+                let radius = camera.radius * settings.zoom;
+                let offset = Vec3::new(
+                    radius * camera.yaw.cos() * camera.pitch.cos(),
+                    radius * camera.pitch.sin(),
+                    radius * camera.yaw.sin() * camera.pitch.cos(),
+                );
+                Transform::from_translation(player + offset).looking_at(player, Vec3::Y)
+            }
+            CameraMode::Chase => {
+                let position = player - heading * settings.radius * settings.zoom
+                    + Vec3::Y * settings.height * settings.zoom;
+                Transform::from_translation(position)
+                    .looking_at(player + Vec3::Y * settings.height * 0.25, Vec3::Y)
+            }
+            CameraMode::TopDown => {
+                // keep driving forward (+x) pointing up the screen
+                Transform::from_translation(player + Vec3::Y * settings.height * settings.zoom)
+                    .looking_at(player, Vec3::X)
+            }
+            CameraMode::FirstPerson => {
+                let position = player
+                    + heading * settings.radius * settings.zoom
+                    + Vec3::Y * settings.height * settings.zoom;
+                Transform::from_translation(position).looking_at(position + heading, Vec3::Y)
+            }
+        }
+    }
+}
+
+mod menu {
+    use super::CameraState;
+    use super::PlayerState;
+    use super::RotatableCamera;
+    use crate::camera::{CameraMode, CameraModeSettings, CameraSettings};
+    use crate::game::PlayerModel;
+    use bevy::{app::AppExit, color::palettes::css::CRIMSON, prelude::*};
+
+    use super::{
+        despawn_screen, Difficulty, GameMode, GameState, InvertMouse, MouseSensitivity,
+        RoundLength, RouteGuidance, Subtitles, Telemetry, TimeOfDay, Volume, WeatherChoice,
+        TXT_CLR,
+    };
+    use crate::achievements::{Achievement, AchievementProgress};
+    use crate::crash_log::{CrashMapExported, ExportCrashMap, EXPORT_DIR};
+    use crate::game::CrashCause;
+    use crate::game::{end_run, HighScores, LastRun};
+    use crate::garage::{
+        spawn_preview, GaragePreview, RobotAntenna, RobotCargoBox, RobotDecal, RobotPaint,
+    };
+    use crate::profiles::ActiveProfile;
+    use crate::stats::{LifetimeStats, RunStats};
+    use crate::upgrades::{Progress, Upgrade, UPGRADES};
+
+    // This plugin manages the menu, with 5 different screens:
+    // - a main menu with "New Game", "Settings", "Quit"
+    // - a settings menu with two submenus and a back button
+    // - two settings screen with a setting that can be set and a back button
+    pub fn menu_plugin(app: &mut App) {
+        app.init_state::<MenuState>()
+            .add_systems(OnEnter(GameState::Menu), menu_setup)
+            // Systems to handle the main menu screen
+            .add_systems(OnEnter(MenuState::Main), main_menu_setup)
+            .add_systems(OnExit(MenuState::Main), despawn_screen::<OnMainMenuScreen>)
+            // Systems to handle the help menu screen
+            .add_systems(OnEnter(MenuState::Help), help_menu_setup)
+            .add_systems(OnExit(MenuState::Help), despawn_screen::<OnHelpMenuScreen>)
+            // Systems to handle the game mode screen
+            .add_systems(OnEnter(MenuState::Modes), modes_menu_setup)
+            .add_systems(
+                OnExit(MenuState::Modes),
+                despawn_screen::<OnModesMenuScreen>,
+            )
+            .add_systems(
+                Update,
+                (
+                    setting_button::<GameMode>,
+                    setting_button::<RoundLength>,
+                    setting_button::<Difficulty>,
+                    update_mode_best,
+                )
+                    .run_if(in_state(MenuState::Modes)),
+            )
+            // Systems to handle the upgrade shop
+            .add_systems(OnEnter(MenuState::Upgrades), upgrades_menu_setup)
+            .add_systems(
+                OnExit(MenuState::Upgrades),
+                despawn_screen::<OnUpgradesMenuScreen>,
+            )
+            .add_systems(
+                Update,
+                (buy_upgrade, update_upgrades_text).run_if(in_state(MenuState::Upgrades)),
+            )
+            // Systems to handle the achievements screen
+            .add_systems(OnEnter(MenuState::Achievements), achievements_menu_setup)
+            .add_systems(
+                OnExit(MenuState::Achievements),
+                despawn_screen::<OnAchievementsMenuScreen>,
+            )
+            // Systems to handle the statistics screen
+            .add_systems(OnEnter(MenuState::Statistics), statistics_menu_setup)
+            .add_systems(
+                Update,
+                export_crash_map_button.run_if(in_state(MenuState::Statistics)),
+            )
+            .add_systems(
+                OnExit(MenuState::Statistics),
+                despawn_screen::<OnStatisticsMenuScreen>,
+            )
+            // Systems to handle the garage
+            .add_systems(OnEnter(MenuState::Garage), garage_menu_setup)
+            .add_systems(
+                OnExit(MenuState::Garage),
+                (
+                    despawn_screen::<OnGarageMenuScreen>,
+                    despawn_screen::<GaragePreview>,
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    setting_button::<RobotPaint>,
+                    setting_button::<RobotDecal>,
+                    setting_button::<RobotAntenna>,
+                    setting_button::<RobotCargoBox>,
+                )
+                    .run_if(in_state(MenuState::Garage)),
+            )
+            // Systems to handle the settings menu screen
+            .add_systems(OnEnter(MenuState::Settings), settings_menu_setup)
+            .add_systems(
+                OnExit(MenuState::Settings),
+                despawn_screen::<OnSettingsMenuScreen>,
+            )
+            .add_systems(
+                Update,
+                (
+                    setting_button::<Volume>,
+                    setting_button::<MouseSensitivity>,
+                    setting_button::<InvertMouse>,
+                    setting_button::<TimeOfDay>,
+                    setting_button::<WeatherChoice>,
+                    setting_button::<Subtitles>,
+                    setting_button::<RouteGuidance>,
+                    setting_button::<Telemetry>,
+                )
+                    .run_if(in_state(MenuState::Settings)),
+            )
+            // Systems to handle the camera settings screen
+            .add_systems(OnEnter(MenuState::CameraSettings), camera_menu_setup)
+            .add_systems(
+                Update,
+                camera_step_button.run_if(in_state(MenuState::CameraSettings)),
+            )
+            .add_systems(
+                OnExit(MenuState::CameraSettings),
+                despawn_screen::<OnCameraSettingsMenuScreen>,
+            )
+            // Systems to handle the pause menu screen
+            .add_systems(OnEnter(GameState::Pause), pause_menu_setup)
+            .add_systems(
+                OnExit(GameState::Pause),
+                despawn_screen::<OnPauseMenuScreen>,
+            )
+            .add_systems(OnEnter(GameState::Lose), lose_menu_setup.after(end_run))
+            .add_systems(OnExit(GameState::Lose), despawn_screen::<OnLoseMenuScreen>)
+            // Common systems to all screens that handles buttons behavior
+            .add_systems(
+                Update,
+                (menu_action, button_system).run_if(in_state(GameState::Menu)),
+            )
+            .add_systems(
+                Update,
+                (menu_action, button_system).run_if(in_state(GameState::Pause)),
+            )
+            .add_systems(
+                Update,
+                (menu_action, button_system).run_if(in_state(GameState::Lose)),
+            )
+            .add_systems(Update, button_system.run_if(in_state(GameState::Profiles)));
+    }
+
+    // State used for the current menu screen
+    #[derive(Clone, Copy, Default, Eq, PartialEq, Debug, Hash, States)]
     enum MenuState {
         Main,
+        Modes,
+        Upgrades,
+        Garage,
+        Achievements,
+        Statistics,
         Help,
+        Settings,
+        CameraSettings,
         #[default]
         Disabled,
     }
@@ -687,24 +2197,88 @@ mod menu {
     #[derive(Component)]
     struct OnHelpMenuScreen;
     #[derive(Component)]
+    struct OnModesMenuScreen;
+    #[derive(Component)]
+    struct OnUpgradesMenuScreen;
+    #[derive(Component)]
+    struct OnGarageMenuScreen;
+    #[derive(Component)]
+    struct OnAchievementsMenuScreen;
+    #[derive(Component)]
+    struct OnStatisticsMenuScreen;
+    /// writes out the crash log and heatmap for level design
+    #[derive(Component)]
+    struct ExportCrashMapButton;
+    type ExportButtons<'w, 's> = Query<
+        'w,
+        's,
+        (&'static Interaction, &'static Children),
+        (Changed<Interaction>, With<ExportCrashMapButton>),
+    >;
+    #[derive(Component)]
+    struct OnSettingsMenuScreen;
+    #[derive(Component)]
+    struct OnCameraSettingsMenuScreen;
+    /// the per-mode camera settings that can be changed from the camera screen
+    #[derive(Clone, Copy, PartialEq)]
+    enum CameraOption {
+        Zoom,
+        Fov,
+    }
+    /// steps a camera mode's zoom or field of view up or down
+    #[derive(Component)]
+    struct CameraStepButton(CameraMode, CameraOption, f32);
+    /// shows a camera mode's current zoom or field of view
+    #[derive(Component)]
+    struct CameraOptionText(CameraMode, CameraOption);
+    #[derive(Component)]
     struct OnPauseMenuScreen;
     #[derive(Component)]
     struct OnLoseMenuScreen;
 
-    const NORMAL_BUTTON: Color = Color::srgb(0.15, 0.15, 0.15);
+    pub const NORMAL_BUTTON: Color = Color::srgb(0.15, 0.15, 0.15);
     const HOVERED_BUTTON: Color = Color::srgb(0.25, 0.25, 0.25);
     const HOVERED_PRESSED_BUTTON: Color = Color::srgb(0.25, 0.65, 0.25);
     const PRESSED_BUTTON: Color = Color::srgb(0.35, 0.75, 0.35);
 
     // Tag component used to mark which setting is currently selected
     #[derive(Component)]
-    struct SelectedOption;
+    pub struct SelectedOption;
+    // Tag component for the text showing the best score of the chosen game mode
+    #[derive(Component)]
+    struct ModeBestText;
+    // Tag component for the text showing how many coins there are to spend
+    #[derive(Component)]
+    struct CoinsText;
+    // The text showing an upgrade's level, the button buying its next one and the price on it
+    #[derive(Component)]
+    struct UpgradeLevelText(Upgrade);
+    #[derive(Component)]
+    struct BuyUpgradeButton(Upgrade);
+    #[derive(Component)]
+    struct UpgradePriceText(Upgrade);
+    /// the price texts, kept apart from the other texts on the upgrade screen
+    type PriceTextQuery<'w, 's> = Query<
+        'w,
+        's,
+        (&'static mut Text, &'static UpgradePriceText),
+        (Without<CoinsText>, Without<UpgradeLevelText>),
+    >;
 
     // All actions that can be triggered from a button click
     #[derive(Component)]
     enum MenuButtonAction {
+        ChooseMode,
         Play,
+        Upgrades,
+        Garage,
+        Achievements,
+        Statistics,
+        Profiles,
         Help,
+        Settings,
+        CameraSettings,
+        BackToSettings,
         BackToMainMenu,
         Quit,
     }
@@ -726,16 +2300,50 @@ mod menu {
         }
     }
 
+    /// the buttons for one setting, each holding the value it sets
+    type SettingButtons<'w, 's, T> = Query<
+        'w,
+        's,
+        (&'static Interaction, &'static T, Entity),
+        (Changed<Interaction>, With<Button>),
+    >;
+    /// whichever of a setting's buttons is currently selected
+    type SelectedSetting<'w, T> =
+        Single<'w, (Entity, &'static mut BackgroundColor), (With<SelectedOption>, With<T>)>;
+
+    // This system updates the settings when a new value for a setting is selected, and marks
+    // the button as the one currently selected
+    fn setting_button<T: Resource + Component + PartialEq + Copy>(
+        interaction_query: SettingButtons<T>,
+        selected_query: SelectedSetting<T>,
+        mut commands: Commands,
+        mut setting: ResMut<T>,
+    ) {
+        let (previous_button, mut previous_button_color) = selected_query.into_inner();
+        for (interaction, button_setting, entity) in &interaction_query {
+            if *interaction == Interaction::Pressed && *setting != *button_setting {
+                *previous_button_color = NORMAL_BUTTON.into();
+                commands.entity(previous_button).remove::<SelectedOption>();
+                commands.entity(entity).insert(SelectedOption);
+                *setting = *button_setting;
+            }
+        }
+    }
+
     fn menu_setup(mut menu_state: ResMut<NextState<MenuState>>) {
         menu_state.set(MenuState::Main);
     }
 
-    fn main_menu_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    fn main_menu_setup(
+        mut commands: Commands,
+        asset_server: Res<AssetServer>,
+        profile: Res<ActiveProfile>,
+    ) {
         // Common style for all buttons on the screen
         let button_node = Node {
             width: Val::Px(300.0),
-            height: Val::Px(65.0),
-            margin: UiRect::all(Val::Px(20.0)),
+            height: Val::Px(45.0),
+            margin: UiRect::all(Val::Px(6.0)),
             justify_content: JustifyContent::Center,
             align_items: AlignItems::Center,
             ..default()
@@ -784,13 +2392,17 @@ mod menu {
                             },
                             TextColor(TXT_CLR),
                             Node {
-                                margin: UiRect::all(Val::Px(50.0)),
+                                margin: UiRect::all(Val::Px(30.0)),
                                 ..default()
                             },
                         ));
 
-                        // Display three buttons for each action available from the main menu:
+                        // Display a button for each action available from the main menu:
                         // - new game
+                        // - upgrades
+                        // - garage
+                        // - achievements
+                        // - statistics
                         // - settings
                         // - Help
                         // - quit
@@ -799,7 +2411,7 @@ mod menu {
                                 Button,
                                 button_node.clone(),
                                 BackgroundColor(NORMAL_BUTTON),
-                                MenuButtonAction::Play,
+                                MenuButtonAction::ChooseMode,
                             ))
                             .with_children(|parent| {
                                 let icon = asset_server.load("Images/new_game.png");
@@ -815,13 +2427,11 @@ mod menu {
                                 Button,
                                 button_node.clone(),
                                 BackgroundColor(NORMAL_BUTTON),
-                                MenuButtonAction::Help,
+                                MenuButtonAction::Upgrades,
                             ))
                             .with_children(|parent| {
-                                let icon = asset_server.load("Images/Help.png");
-                                parent.spawn((ImageNode::new(icon), button_icon_node.clone()));
                                 parent.spawn((
-                                    Text::new("Help"),
+                                    Text::new("Upgrades"),
                                     button_text_font.clone(),
                                     TextColor(TXT_CLR),
                                 ));
@@ -831,48 +2441,148 @@ mod menu {
                                 Button,
                                 button_node.clone(),
                                 BackgroundColor(NORMAL_BUTTON),
-                                MenuButtonAction::Quit,
+                                MenuButtonAction::Garage,
                             ))
                             .with_children(|parent| {
-                                let icon = asset_server.load("Images/exit.png");
-                                parent.spawn((ImageNode::new(icon), button_icon_node.clone()));
                                 parent.spawn((
-                                    Text::new("Quit"),
+                                    Text::new("Garage"),
                                     button_text_font.clone(),
                                     TextColor(TXT_CLR),
                                 ));
                             });
-                    });
-            });
-    }
-    fn help_menu_setup(mut commands: Commands) {
-        let button_node = Node {
-            width: Val::Px(200.0),
-            height: Val::Px(65.0),
-            margin: UiRect::all(Val::Px(20.0)),
-            justify_content: JustifyContent::Center,
-            align_items: AlignItems::Center,
-            ..default()
-        };
-
-        let button_text_style = (
-            TextFont {
-                font_size: 33.0,
-                ..default()
-            },
-            TextColor(TXT_CLR),
-        );
-
-        let controls_text_style = (
-            TextFont {
-                font_size: 25.0,
-                ..default()
-            },
-            TextColor(TXT_CLR),
-        );
-
-        let controls = [
-            ("esc", "return to main menu"),
+                        parent
+                            .spawn((
+                                Button,
+                                button_node.clone(),
+                                BackgroundColor(NORMAL_BUTTON),
+                                MenuButtonAction::Achievements,
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn((
+                                    Text::new("Achievements"),
+                                    button_text_font.clone(),
+                                    TextColor(TXT_CLR),
+                                ));
+                            });
+                        parent
+                            .spawn((
+                                Button,
+                                button_node.clone(),
+                                BackgroundColor(NORMAL_BUTTON),
+                                MenuButtonAction::Statistics,
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn((
+                                    Text::new("Statistics"),
+                                    button_text_font.clone(),
+                                    TextColor(TXT_CLR),
+                                ));
+                            });
+                        parent
+                            .spawn((
+                                Button,
+                                button_node.clone(),
+                                BackgroundColor(NORMAL_BUTTON),
+                                MenuButtonAction::Help,
+                            ))
+                            .with_children(|parent| {
+                                let icon = asset_server.load("Images/Help.png");
+                                parent.spawn((ImageNode::new(icon), button_icon_node.clone()));
+                                parent.spawn((
+                                    Text::new("Help"),
+                                    button_text_font.clone(),
+                                    TextColor(TXT_CLR),
+                                ));
+                            });
+                        parent
+                            .spawn((
+                                Button,
+                                button_node.clone(),
+                                BackgroundColor(NORMAL_BUTTON),
+                                MenuButtonAction::Settings,
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn((
+                                    Text::new("Settings"),
+                                    button_text_font.clone(),
+                                    TextColor(TXT_CLR),
+                                ));
+                            });
+                        parent
+                            .spawn((
+                                Button,
+                                button_node.clone(),
+                                BackgroundColor(NORMAL_BUTTON),
+                                MenuButtonAction::Quit,
+                            ))
+                            .with_children(|parent| {
+                                let icon = asset_server.load("Images/exit.png");
+                                parent.spawn((ImageNode::new(icon), button_icon_node.clone()));
+                                parent.spawn((
+                                    Text::new("Quit"),
+                                    button_text_font.clone(),
+                                    TextColor(TXT_CLR),
+                                ));
+                            });
+
+                        // who's playing, and a way back to the profile screen to change it
+                        parent
+                            .spawn((
+                                Button,
+                                Node {
+                                    height: Val::Px(40.0),
+                                    padding: UiRect::horizontal(Val::Px(12.0)),
+                                    margin: UiRect::bottom(Val::Px(10.0)),
+                                    ..button_node.clone()
+                                },
+                                BackgroundColor(NORMAL_BUTTON),
+                                MenuButtonAction::Profiles,
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn((
+                                    Text::new(format!(
+                                        "Profile: {} (slot {})",
+                                        profile.name(),
+                                        profile.slot() + 1
+                                    )),
+                                    TextFont {
+                                        font_size: 25.0,
+                                        ..default()
+                                    },
+                                    TextColor(TXT_CLR),
+                                ));
+                            });
+                    });
+            });
+    }
+    fn help_menu_setup(mut commands: Commands) {
+        let button_node = Node {
+            width: Val::Px(200.0),
+            height: Val::Px(65.0),
+            margin: UiRect::all(Val::Px(20.0)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        };
+
+        let button_text_style = (
+            TextFont {
+                font_size: 33.0,
+                ..default()
+            },
+            TextColor(TXT_CLR),
+        );
+
+        let controls_text_style = (
+            TextFont {
+                font_size: 25.0,
+                ..default()
+            },
+            TextColor(TXT_CLR),
+        );
+
+        let controls = [
+            ("esc", "return to main menu"),
             ("w", "forward"),
             ("s", "backward"),
             ("a", "left"),
@@ -881,6 +2591,11 @@ mod menu {
             ("down arrow", "camera angle up"),
             ("left arrow", "camera angle right"),
             ("right arrow", "camera angle left"),
+            ("right mouse drag", "orbit camera"),
+            ("scroll wheel", "zoom camera"),
+            ("c", "switch camera mode"),
+            ("o", "pull in / fade / ignore blocking buildings"),
+            ("j, then 1-4", "take a contract, with shift to turn it down"),
         ];
 
         commands
@@ -967,39 +2682,27 @@ mod menu {
                     });
             });
     }
-    fn pause_menu_setup(
+    fn modes_menu_setup(
         mut commands: Commands,
-        asset_server: Res<AssetServer>,
-        query: Query<Entity, With<Camera>>,
+        game_mode: Res<GameMode>,
+        round_length: Res<RoundLength>,
+        difficulty: Res<Difficulty>,
     ) {
-        for entity in query.iter() {
-            commands.entity(entity).despawn_recursive();
-        }
-        commands.spawn((
-            Camera2d,
-            Camera {
-                order: 0, // Ensure unique order
-                ..default()
-            },
-        ));
         let button_node = Node {
-            width: Val::Px(300.0),
+            width: Val::Px(200.0),
             height: Val::Px(65.0),
             margin: UiRect::all(Val::Px(20.0)),
             justify_content: JustifyContent::Center,
             align_items: AlignItems::Center,
             ..default()
         };
-        let button_icon_node = Node {
-            width: Val::Px(30.0),
-            position_type: PositionType::Absolute,
-            left: Val::Px(10.0),
-            ..default()
-        };
-        let button_text_font = TextFont {
-            font_size: 33.0,
-            ..default()
-        };
+        let button_text_style = (
+            TextFont {
+                font_size: 33.0,
+                ..default()
+            },
+            TextColor(TXT_CLR),
+        );
 
         commands
             .spawn((
@@ -1010,7 +2713,7 @@ mod menu {
                     justify_content: JustifyContent::Center,
                     ..default()
                 },
-                OnPauseMenuScreen,
+                OnModesMenuScreen,
             ))
             .with_children(|parent| {
                 parent
@@ -1024,76 +2727,171 @@ mod menu {
                     ))
                     .with_children(|parent| {
                         parent.spawn((
-                            Text::new("Pause Menu"),
+                            Text::new("Game Mode"),
                             TextFont {
                                 font_size: 67.0,
                                 ..default()
                             },
                             TextColor(TXT_CLR),
                             Node {
-                                margin: UiRect::all(Val::Px(50.0)),
+                                margin: UiRect::all(Val::Px(30.0)),
                                 ..default()
                             },
                         ));
 
                         parent
                             .spawn((
-                                Button,
-                                button_node.clone(),
-                                BackgroundColor(NORMAL_BUTTON),
-                                MenuButtonAction::Play,
+                                Node {
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                BackgroundColor(CRIMSON.into()),
                             ))
                             .with_children(|parent| {
-                                let icon = asset_server.load("Images/new_game.png");
-                                parent.spawn((ImageNode::new(icon), button_icon_node.clone()));
-                                parent.spawn((
-                                    Text::new("Resume"),
-                                    button_text_font.clone(),
-                                    TextColor(TXT_CLR),
-                                ));
+                                for mode in [
+                                    GameMode::Timed,
+                                    GameMode::Endless,
+                                    GameMode::Zen,
+                                    GameMode::Survival,
+                                ] {
+                                    let mut entity = parent.spawn((
+                                        Button,
+                                        button_node.clone(),
+                                        BackgroundColor(NORMAL_BUTTON),
+                                        mode,
+                                    ));
+                                    entity.with_children(|parent| {
+                                        parent.spawn((
+                                            Text::new(format!("{mode:?}")),
+                                            button_text_style.clone(),
+                                        ));
+                                    });
+                                    if *game_mode == mode {
+                                        entity.insert(SelectedOption);
+                                    }
+                                }
                             });
 
+                        // only timed rounds use this, the other modes set their own clock
                         parent
                             .spawn((
-                                Button,
-                                button_node.clone(),
-                                BackgroundColor(NORMAL_BUTTON),
-                                MenuButtonAction::Help,
+                                Node {
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                BackgroundColor(CRIMSON.into()),
                             ))
                             .with_children(|parent| {
-                                let icon = asset_server.load("Images/Help.png");
-                                parent.spawn((ImageNode::new(icon), button_icon_node.clone()));
-                                parent.spawn((
-                                    Text::new("Help"),
-                                    button_text_font.clone(),
-                                    TextColor(TXT_CLR),
-                                ));
+                                parent
+                                    .spawn((Text::new("Round Length"), button_text_style.clone()));
+                                for secs in [60, 120, 180, 300] {
+                                    let mut entity = parent.spawn((
+                                        Button,
+                                        Node {
+                                            width: Val::Px(100.0),
+                                            ..button_node.clone()
+                                        },
+                                        BackgroundColor(NORMAL_BUTTON),
+                                        RoundLength(secs),
+                                    ));
+                                    entity.with_children(|parent| {
+                                        parent.spawn((
+                                            Text::new(format!("{}:{:02}", secs / 60, secs % 60)),
+                                            button_text_style.clone(),
+                                        ));
+                                    });
+                                    if *round_length == RoundLength(secs) {
+                                        entity.insert(SelectedOption);
+                                    }
+                                }
                             });
 
+                        // how quickly the run gets harder, see the difficulty module
                         parent
                             .spawn((
-                                Button,
-                                button_node.clone(),
-                                BackgroundColor(NORMAL_BUTTON),
-                                MenuButtonAction::Quit,
+                                Node {
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                BackgroundColor(CRIMSON.into()),
                             ))
                             .with_children(|parent| {
-                                let icon = asset_server.load("Images/exit.png");
-                                parent.spawn((ImageNode::new(icon), button_icon_node.clone()));
-                                parent.spawn((
-                                    Text::new("Quit"),
-                                    button_text_font.clone(),
-                                    TextColor(TXT_CLR),
-                                ));
+                                parent.spawn((Text::new("Difficulty"), button_text_style.clone()));
+                                for setting in
+                                    [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard]
+                                {
+                                    let mut entity = parent.spawn((
+                                        Button,
+                                        Node {
+                                            width: Val::Px(150.0),
+                                            ..button_node.clone()
+                                        },
+                                        BackgroundColor(NORMAL_BUTTON),
+                                        setting,
+                                    ));
+                                    entity.with_children(|parent| {
+                                        parent.spawn((
+                                            Text::new(format!("{setting:?}")),
+                                            button_text_style.clone(),
+                                        ));
+                                    });
+                                    if *difficulty == setting {
+                                        entity.insert(SelectedOption);
+                                    }
+                                }
                             });
+
+                        parent.spawn((Text::new(""), button_text_style.clone(), ModeBestText));
+
+                        parent.spawn(Node::default()).with_children(|parent| {
+                            parent
+                                .spawn((
+                                    Button,
+                                    button_node.clone(),
+                                    BackgroundColor(NORMAL_BUTTON),
+                                    MenuButtonAction::BackToMainMenu,
+                                ))
+                                .with_children(|parent| {
+                                    parent.spawn((Text::new("Back"), button_text_style.clone()));
+                                });
+                            parent
+                                .spawn((
+                                    Button,
+                                    button_node.clone(),
+                                    BackgroundColor(NORMAL_BUTTON),
+                                    MenuButtonAction::Play,
+                                ))
+                                .with_children(|parent| {
+                                    parent.spawn((Text::new("Start"), button_text_style.clone()));
+                                });
+                        });
                     });
             });
     }
-    fn lose_menu_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    fn update_mode_best(
+        game_mode: Res<GameMode>,
+        round_length: Res<RoundLength>,
+        difficulty: Res<Difficulty>,
+        high_scores: Res<HighScores>,
+        mut text_query: Query<&mut Text, With<ModeBestText>>,
+    ) {
+        for mut text in text_query.iter_mut() {
+            let best = format!(
+                "Best {} on {:?}: {}",
+                game_mode.label(*round_length),
+                *difficulty,
+                high_scores.best(*game_mode, *round_length, *difficulty)
+            );
+            if text.0 != best {
+                text.0 = best;
+            }
+        }
+    }
+    fn upgrades_menu_setup(mut commands: Commands) {
         let button_node = Node {
-            width: Val::Px(300.0),
-            height: Val::Px(65.0),
-            margin: UiRect::all(Val::Px(20.0)),
+            width: Val::Px(200.0),
+            height: Val::Px(55.0),
+            margin: UiRect::all(Val::Px(8.0)),
             justify_content: JustifyContent::Center,
             align_items: AlignItems::Center,
             ..default()
@@ -1115,7 +2913,7 @@ mod menu {
                     justify_content: JustifyContent::Center,
                     ..default()
                 },
-                OnLoseMenuScreen,
+                OnUpgradesMenuScreen,
             ))
             .with_children(|parent| {
                 parent
@@ -1128,80 +2926,7901 @@ mod menu {
                         BackgroundColor(CRIMSON.into()),
                     ))
                     .with_children(|parent| {
-                        let icon = asset_server.load("Images/Lose_Icon.png");
                         parent.spawn((
-                            ImageNode::new(icon),
+                            Text::new("Upgrades"),
+                            TextFont {
+                                font_size: 67.0,
+                                ..default()
+                            },
+                            TextColor(TXT_CLR),
                             Node {
-                                width: Val::Px(200.0),
-                                height: Val::Px(200.0),
+                                margin: UiRect::all(Val::Px(20.0)),
                                 ..default()
                             },
                         ));
+                        parent.spawn((Text::new(""), button_text_style.clone(), CoinsText));
+
+                        // one row per upgrade, its name and level and a button to buy the next one
+                        for upgrade in UPGRADES {
+                            parent
+                                .spawn(Node {
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                })
+                                .with_children(|parent| {
+                                    parent.spawn((
+                                        Text::new(""),
+                                        button_text_style.clone(),
+                                        Node {
+                                            width: Val::Px(380.0),
+                                            ..default()
+                                        },
+                                        UpgradeLevelText(upgrade),
+                                    ));
+                                    parent
+                                        .spawn((
+                                            Button,
+                                            button_node.clone(),
+                                            BackgroundColor(NORMAL_BUTTON),
+                                            BuyUpgradeButton(upgrade),
+                                        ))
+                                        .with_children(|parent| {
+                                            parent.spawn((
+                                                Text::new(""),
+                                                button_text_style.clone(),
+                                                UpgradePriceText(upgrade),
+                                            ));
+                                        });
+                                });
+                        }
 
                         parent
                             .spawn((
                                 Button,
                                 button_node.clone(),
                                 BackgroundColor(NORMAL_BUTTON),
-                                MenuButtonAction::Quit,
+                                MenuButtonAction::BackToMainMenu,
                             ))
                             .with_children(|parent| {
-                                parent.spawn((Text::new("Exit"), button_text_style.clone()));
+                                parent.spawn((Text::new("Back"), button_text_style.clone()));
                             });
                     });
             });
     }
-    fn menu_action(
-        interaction_query: Query<
-            (&Interaction, &MenuButtonAction),
-            (Changed<Interaction>, With<Button>),
-        >,
-        mut app_exit_events: EventWriter<AppExit>,
-        mut menu_state: ResMut<NextState<MenuState>>,
-        mut game_state: ResMut<NextState<GameState>>,
-        mut param_set: ParamSet<(
-            Query<&mut Transform, With<PlayerModel>>,
-            Query<&mut Transform, With<RotatableCamera>>,
-        )>,
-        player_state: Res<PlayerState>,
-        camera_state: Res<CameraState>,
-        mut is_resuming: Local<bool>,
+    fn buy_upgrade(
+        interaction_query: Query<(&Interaction, &BuyUpgradeButton), Changed<Interaction>>,
+        mut progress: ResMut<Progress>,
     ) {
-        for (interaction, menu_button_action) in &interaction_query {
+        for (interaction, button) in &interaction_query {
             if *interaction == Interaction::Pressed {
-                match menu_button_action {
-                    MenuButtonAction::Quit => {
-                        app_exit_events.send(AppExit::Success);
-                    }
-                    MenuButtonAction::Play => {
-                        if *is_resuming {
-                            // Restore saved positions
+                progress.buy(button.0);
+            }
+        }
+    }
+    // Keeps the coins, levels and prices on the upgrade screen up to date
+    fn update_upgrades_text(
+        progress: Res<Progress>,
+        mut coins_query: Query<&mut Text, With<CoinsText>>,
+        mut level_query: Query<(&mut Text, &UpgradeLevelText), Without<CoinsText>>,
+        mut price_query: PriceTextQuery,
+    ) {
+        let set = |text: &mut Text, label: String| {
+            if text.0 != label {
+                text.0 = label;
+            }
+        };
+        for mut text in coins_query.iter_mut() {
+            set(&mut text, format!("Coins: {}", progress.coins));
+        }
+        for (mut text, level_text) in level_query.iter_mut() {
+            let upgrade = level_text.0;
+            let level = format!(
+                "{} {}/{}",
+                upgrade.label(),
+                progress.level(upgrade),
+                Upgrade::MAX_LEVEL
+            );
+            set(&mut text, level);
+        }
+        for (mut text, price_text) in price_query.iter_mut() {
+            let price = match progress.cost(price_text.0) {
+                Some(cost) => format!("Buy: {cost}"),
+                None => "Maxed".to_string(),
+            };
+            set(&mut text, price);
+        }
+    }
+    fn statistics_menu_setup(
+        mut commands: Commands,
+        stats: Res<LifetimeStats>,
+        run_stats: Res<RunStats>,
+    ) {
+        let button_node = Node {
+            width: Val::Px(200.0),
+            height: Val::Px(65.0),
+            margin: UiRect::all(Val::Px(20.0)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        };
+        let button_text_style = (
+            TextFont {
+                font_size: 33.0,
+                ..default()
+            },
+            TextColor(TXT_CLR),
+        );
+        let line_style = (
+            TextFont {
+                font_size: 26.0,
+                ..default()
+            },
+            TextColor(TXT_CLR),
+            Node {
+                margin: UiRect::axes(Val::Px(30.0), Val::Px(3.0)),
+                ..default()
+            },
+        );
+
+        let mut lines = vec![
+            format!("Runs played: {}", stats.runs),
+            format!(
+                "Deliveries: {} ({} on time)",
+                stats.deliveries,
+                stats.deliveries - stats.late_deliveries
+            ),
+            format!(
+                "Average delivery time: {:.1}s",
+                stats.average_delivery_secs()
+            ),
+            format!("Most deliveries in a run: {}", stats.best_run_deliveries),
+            format!("Best on-time streak: {}", stats.best_streak),
+            format!("Distance driven: {:.2} km", stats.distance / 1000.0),
+        ];
+        if stats.runs > 0 {
+            lines.push(format!(
+                "Last run: {} deliveries, {:.0} m, streak of {}",
+                run_stats.deliveries, run_stats.distance, run_stats.best_streak
+            ));
+        }
+        let crashes: Vec<(&str, f32, String)> = CrashCause::ALL
+            .iter()
+            .map(|cause| {
+                let count = stats.crashes(*cause);
+                (cause.label(), count as f32, count.to_string())
+            })
+            .collect();
+        let play_time: Vec<(&str, f32, String)> = [
+            GameMode::Timed,
+            GameMode::Endless,
+            GameMode::Zen,
+            GameMode::Survival,
+        ]
+        .iter()
+        .map(|mode| {
+            let secs = stats.play_secs(*mode);
+            let label = match mode {
+                GameMode::Timed => "Timed",
+                GameMode::Endless => "Endless",
+                GameMode::Zen => "Zen",
+                GameMode::Survival => "Survival",
+            };
+            (label, secs, format_play_time(secs))
+        })
+        .collect();
+
+        commands
+            .spawn((
+                Node {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                OnStatisticsMenuScreen,
+            ))
+            .with_children(|parent| {
+                parent
+                    .spawn((
+                        Node {
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        BackgroundColor(CRIMSON.into()),
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn((
+                            Text::new("Statistics"),
+                            TextFont {
+                                font_size: 67.0,
+                                ..default()
+                            },
+                            TextColor(TXT_CLR),
+                            Node {
+                                margin: UiRect::all(Val::Px(20.0)),
+                                ..default()
+                            },
+                        ));
+                        for line in lines {
+                            parent.spawn((Text::new(line), line_style.clone()));
+                        }
+                        // the charts sit side by side to leave room for everything else
+                        parent.spawn(Node::default()).with_children(|parent| {
+                            for (title, rows) in [("Crashes", &crashes), ("Play time", &play_time)]
+                            {
+                                parent
+                                    .spawn(Node {
+                                        flex_direction: FlexDirection::Column,
+                                        align_items: AlignItems::Center,
+                                        margin: UiRect::horizontal(Val::Px(15.0)),
+                                        ..default()
+                                    })
+                                    .with_children(|parent| bar_chart(parent, title, rows));
+                            }
+                        });
+
+                        parent.spawn(Node::default()).with_children(|parent| {
+                            parent
+                                .spawn((
+                                    Button,
+                                    Node {
+                                        width: Val::Px(330.0),
+                                        ..button_node.clone()
+                                    },
+                                    BackgroundColor(NORMAL_BUTTON),
+                                    ExportCrashMapButton,
+                                ))
+                                .with_children(|parent| {
+                                    parent.spawn((
+                                        Text::new("Export Crash Map"),
+                                        button_text_style.clone(),
+                                    ));
+                                });
+                            parent
+                                .spawn((
+                                    Button,
+                                    button_node.clone(),
+                                    BackgroundColor(NORMAL_BUTTON),
+                                    MenuButtonAction::BackToMainMenu,
+                                ))
+                                .with_children(|parent| {
+                                    parent.spawn((Text::new("Back"), button_text_style.clone()));
+                                });
+                        });
+                    });
+            });
+    }
+    fn export_crash_map_button(
+        interaction_query: ExportButtons,
+        button_query: Query<&Children, With<ExportCrashMapButton>>,
+        mut text_query: Query<&mut Text>,
+        mut export_events: EventWriter<ExportCrashMap>,
+        mut exported_events: EventReader<CrashMapExported>,
+    ) {
+        for (interaction, _) in &interaction_query {
+            if *interaction == Interaction::Pressed {
+                export_events.send(ExportCrashMap);
+            }
+        }
+        for exported in exported_events.read() {
+            for children in &button_query {
+                if let Ok(mut text) = text_query.get_mut(children[0]) {
+                    text.0 = match &exported.0 {
+                        Ok(()) => format!("Saved to {EXPORT_DIR}/"),
+                        Err(error) => error.clone(),
+                    };
+                }
+            }
+        }
+    }
+    /// a titled chart with a bar for each row, each sized against the biggest value
+    fn bar_chart(parent: &mut ChildBuilder, title: &str, rows: &[(&str, f32, String)]) {
+        const BAR_WIDTH: f32 = 200.0;
+        let largest = rows.iter().map(|(_, value, _)| *value).fold(0.0, f32::max);
+        let text_font = TextFont {
+            font_size: 22.0,
+            ..default()
+        };
+        parent.spawn((
+            Text::new(title),
+            TextFont {
+                font_size: 30.0,
+                ..default()
+            },
+            TextColor(TXT_CLR),
+            Node {
+                margin: UiRect::top(Val::Px(12.0)),
+                ..default()
+            },
+        ));
+        for (label, value, shown) in rows {
+            parent
+                .spawn(Node {
+                    align_items: AlignItems::Center,
+                    margin: UiRect::vertical(Val::Px(2.0)),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        Text::new(*label),
+                        text_font.clone(),
+                        TextColor(TXT_CLR),
+                        Node {
+                            width: Val::Px(130.0),
+                            ..default()
+                        },
+                    ));
+                    // the track the bar fills, so an empty row still shows where it would be
+                    parent
+                        .spawn((
+                            Node {
+                                width: Val::Px(BAR_WIDTH),
+                                height: Val::Px(18.0),
+                                ..default()
+                            },
+                            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.3)),
+                        ))
+                        .with_children(|parent| {
+                            let fraction = if largest > 0.0 { value / largest } else { 0.0 };
+                            parent.spawn((
+                                Node {
+                                    width: Val::Percent(fraction * 100.0),
+                                    height: Val::Percent(100.0),
+                                    ..default()
+                                },
+                                BackgroundColor(Color::srgb(0.95, 0.75, 0.3)),
+                            ));
+                        });
+                    parent.spawn((
+                        Text::new(shown.clone()),
+                        text_font.clone(),
+                        TextColor(TXT_CLR),
+                        Node {
+                            width: Val::Px(90.0),
+                            margin: UiRect::left(Val::Px(10.0)),
+                            ..default()
+                        },
+                    ));
+                });
+        }
+    }
+    /// play time as hours and minutes, or minutes and seconds when it's under an hour
+    fn format_play_time(secs: f32) -> String {
+        let secs = secs as u32;
+        if secs >= 3600 {
+            format!("{}h {:02}m", secs / 3600, secs / 60 % 60)
+        } else {
+            format!("{}m {:02}s", secs / 60, secs % 60)
+        }
+    }
+    fn achievements_menu_setup(mut commands: Commands, progress: Res<AchievementProgress>) {
+        let button_node = Node {
+            width: Val::Px(200.0),
+            height: Val::Px(65.0),
+            margin: UiRect::all(Val::Px(20.0)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        };
+        let button_text_style = (
+            TextFont {
+                font_size: 33.0,
+                ..default()
+            },
+            TextColor(TXT_CLR),
+        );
+
+        commands
+            .spawn((
+                Node {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                OnAchievementsMenuScreen,
+            ))
+            .with_children(|parent| {
+                parent
+                    .spawn((
+                        Node {
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        BackgroundColor(CRIMSON.into()),
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn((
+                            Text::new("Achievements"),
+                            TextFont {
+                                font_size: 67.0,
+                                ..default()
+                            },
+                            TextColor(TXT_CLR),
+                            Node {
+                                margin: UiRect::all(Val::Px(30.0)),
+                                ..default()
+                            },
+                        ));
+                        for achievement in Achievement::ALL {
+                            let unlocked = progress.unlocked(achievement);
+                            let mut line = format!(
+                                "{} {}: {}",
+                                if unlocked { "[x]" } else { "[ ]" },
+                                achievement.title(),
+                                achievement.description()
+                            );
+                            if let Some(progress) = progress.progress_text(achievement) {
+                                line.push_str(&format!(" ({progress})"));
+                            }
+                            let color = if unlocked {
+                                TXT_CLR
+                            } else {
+                                Color::srgb(0.6, 0.6, 0.6)
+                            };
+                            parent.spawn((
+                                Text::new(line),
+                                TextFont {
+                                    font_size: 28.0,
+                                    ..default()
+                                },
+                                TextColor(color),
+                                Node {
+                                    margin: UiRect::axes(Val::Px(30.0), Val::Px(6.0)),
+                                    ..default()
+                                },
+                            ));
+                        }
+
+                        parent
+                            .spawn((
+                                Button,
+                                button_node.clone(),
+                                BackgroundColor(NORMAL_BUTTON),
+                                MenuButtonAction::BackToMainMenu,
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn((Text::new("Back"), button_text_style.clone()));
+                            });
+                    });
+            });
+    }
+    fn garage_menu_setup(
+        mut commands: Commands,
+        asset_server: Res<AssetServer>,
+        mut images: ResMut<Assets<Image>>,
+        (paint, decal, antenna, cargo_box): (
+            Res<RobotPaint>,
+            Res<RobotDecal>,
+            Res<RobotAntenna>,
+            Res<RobotCargoBox>,
+        ),
+    ) {
+        let button_node = Node {
+            width: Val::Px(120.0),
+            height: Val::Px(50.0),
+            margin: UiRect::all(Val::Px(5.0)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        };
+        let button_text_style = (
+            TextFont {
+                font_size: 25.0,
+                ..default()
+            },
+            TextColor(TXT_CLR),
+        );
+        let preview = spawn_preview(&mut commands, &asset_server, &mut images);
+
+        // one row of choices, the current one marked as selected
+        fn option_row<T: Component + PartialEq + Copy>(
+            parent: &mut ChildBuilder,
+            (button_node, button_text_style): (&Node, &(TextFont, TextColor)),
+            title: &str,
+            options: &[(T, &str)],
+            current: T,
+        ) {
+            parent
+                .spawn((
+                    Node {
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    BackgroundColor(CRIMSON.into()),
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        Text::new(title),
+                        button_text_style.clone(),
+                        Node {
+                            width: Val::Px(100.0),
+                            ..default()
+                        },
+                    ));
+                    for (option, label) in options {
+                        let mut entity = parent.spawn((
+                            Button,
+                            button_node.clone(),
+                            BackgroundColor(NORMAL_BUTTON),
+                            *option,
+                        ));
+                        entity.with_children(|parent| {
+                            parent.spawn((Text::new(*label), button_text_style.clone()));
+                        });
+                        if *option == current {
+                            entity.insert(SelectedOption);
+                        }
+                    }
+                });
+        }
+
+        commands
+            .spawn((
+                Node {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                OnGarageMenuScreen,
+            ))
+            .with_children(|parent| {
+                parent
+                    .spawn((
+                        Node {
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        BackgroundColor(CRIMSON.into()),
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn((
+                            Text::new("Garage"),
+                            TextFont {
+                                font_size: 67.0,
+                                ..default()
+                            },
+                            TextColor(TXT_CLR),
+                            Node {
+                                margin: UiRect::all(Val::Px(15.0)),
+                                ..default()
+                            },
+                        ));
+                        parent.spawn((
+                            ImageNode::new(preview),
+                            Node {
+                                width: Val::Px(240.0),
+                                height: Val::Px(240.0),
+                                ..default()
+                            },
+                        ));
+
+                        let style = (&button_node, &button_text_style);
+                        option_row(
+                            parent,
+                            style,
+                            "Paint",
+                            &RobotPaint::ALL.map(|option| (option, option.label())),
+                            *paint,
+                        );
+                        option_row(
+                            parent,
+                            style,
+                            "Decal",
+                            &RobotDecal::ALL.map(|option| (option, option.label())),
+                            *decal,
+                        );
+                        option_row(
+                            parent,
+                            style,
+                            "Antenna",
+                            &RobotAntenna::ALL.map(|option| (option, option.label())),
+                            *antenna,
+                        );
+                        option_row(
+                            parent,
+                            style,
+                            "Cargo",
+                            &RobotCargoBox::ALL.map(|option| (option, option.label())),
+                            *cargo_box,
+                        );
+
+                        parent
+                            .spawn((
+                                Button,
+                                Node {
+                                    width: Val::Px(200.0),
+                                    ..button_node.clone()
+                                },
+                                BackgroundColor(NORMAL_BUTTON),
+                                MenuButtonAction::BackToMainMenu,
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn((Text::new("Back"), button_text_style.clone()));
+                            });
+                    });
+            });
+    }
+    fn settings_menu_setup(
+        mut commands: Commands,
+        volume: Res<Volume>,
+        mouse_sensitivity: Res<MouseSensitivity>,
+        invert_mouse: Res<InvertMouse>,
+        time_of_day: Res<TimeOfDay>,
+        weather_choice: Res<WeatherChoice>,
+        (subtitles, route_guidance, telemetry): (
+            Res<Subtitles>,
+            Res<RouteGuidance>,
+            Res<Telemetry>,
+        ),
+    ) {
+        // tighter than the other screens so every setting fits on one page
+        let button_node = Node {
+            width: Val::Px(200.0),
+            height: Val::Px(50.0),
+            margin: UiRect::all(Val::Px(6.0)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        };
+        let option_node = Node {
+            width: Val::Px(40.0),
+            height: Val::Px(50.0),
+            ..button_node.clone()
+        };
+        let button_text_style = (
+            TextFont {
+                font_size: 33.0,
+                ..default()
+            },
+            TextColor(TXT_CLR),
+        );
+        // smaller so two digits fit on the narrow level buttons
+        let option_text_style = (
+            TextFont {
+                font_size: 24.0,
+                ..default()
+            },
+            TextColor(TXT_CLR),
+        );
+
+        commands
+            .spawn((
+                Node {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                OnSettingsMenuScreen,
+            ))
+            .with_children(|parent| {
+                parent
+                    .spawn((
+                        Node {
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        BackgroundColor(CRIMSON.into()),
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn((
+                            Text::new("Settings"),
+                            TextFont {
+                                font_size: 67.0,
+                                ..default()
+                            },
+                            TextColor(TXT_CLR),
+                            Node {
+                                margin: UiRect::all(Val::Px(20.0)),
+                                ..default()
+                            },
+                        ));
+
+                        parent
+                            .spawn((
+                                Node {
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                BackgroundColor(CRIMSON.into()),
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn((Text::new("Volume"), button_text_style.clone()));
+                                for level in 0..=9 {
+                                    let mut entity = parent.spawn((
+                                        Button,
+                                        option_node.clone(),
+                                        BackgroundColor(NORMAL_BUTTON),
+                                        Volume(level),
+                                    ));
+                                    entity.with_children(|parent| {
+                                        parent.spawn((
+                                            Text::new(level.to_string()),
+                                            option_text_style.clone(),
+                                        ));
+                                    });
+                                    if *volume == Volume(level) {
+                                        entity.insert(SelectedOption);
+                                    }
+                                }
+                            });
+
+                        // one button per sensitivity level, the current one is highlighted
+                        parent
+                            .spawn((
+                                Node {
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                BackgroundColor(CRIMSON.into()),
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn((
+                                    Text::new("Mouse Sensitivity"),
+                                    button_text_style.clone(),
+                                ));
+                                for level in 1..=10 {
+                                    let mut entity = parent.spawn((
+                                        Button,
+                                        option_node.clone(),
+                                        BackgroundColor(NORMAL_BUTTON),
+                                        MouseSensitivity(level),
+                                    ));
+                                    entity.with_children(|parent| {
+                                        parent.spawn((
+                                            Text::new(level.to_string()),
+                                            option_text_style.clone(),
+                                        ));
+                                    });
+                                    if *mouse_sensitivity == MouseSensitivity(level) {
+                                        entity.insert(SelectedOption);
+                                    }
+                                }
+                            });
+
+                        parent
+                            .spawn((
+                                Node {
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                BackgroundColor(CRIMSON.into()),
+                            ))
+                            .with_children(|parent| {
+                                parent
+                                    .spawn((Text::new("Invert Mouse"), button_text_style.clone()));
+                                for (label, invert) in [("Off", false), ("On", true)] {
+                                    let mut entity = parent.spawn((
+                                        Button,
+                                        Node {
+                                            width: Val::Px(100.0),
+                                            ..button_node.clone()
+                                        },
+                                        BackgroundColor(NORMAL_BUTTON),
+                                        InvertMouse(invert),
+                                    ));
+                                    entity.with_children(|parent| {
+                                        parent.spawn((Text::new(label), button_text_style.clone()));
+                                    });
+                                    if *invert_mouse == InvertMouse(invert) {
+                                        entity.insert(SelectedOption);
+                                    }
+                                }
+                            });
+
+                        parent
+                            .spawn((
+                                Node {
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                BackgroundColor(CRIMSON.into()),
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn((Text::new("Time of Day"), button_text_style.clone()));
+                                for setting in [
+                                    TimeOfDay::Cycle,
+                                    TimeOfDay::Dawn,
+                                    TimeOfDay::Noon,
+                                    TimeOfDay::Dusk,
+                                    TimeOfDay::Night,
+                                ] {
+                                    let mut entity = parent.spawn((
+                                        Button,
+                                        Node {
+                                            width: Val::Px(120.0),
+                                            ..button_node.clone()
+                                        },
+                                        BackgroundColor(NORMAL_BUTTON),
+                                        setting,
+                                    ));
+                                    entity.with_children(|parent| {
+                                        parent.spawn((
+                                            Text::new(format!("{setting:?}")),
+                                            button_text_style.clone(),
+                                        ));
+                                    });
+                                    if *time_of_day == setting {
+                                        entity.insert(SelectedOption);
+                                    }
+                                }
+                            });
+
+                        parent
+                            .spawn((
+                                Node {
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                BackgroundColor(CRIMSON.into()),
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn((Text::new("Weather"), button_text_style.clone()));
+                                for setting in [
+                                    WeatherChoice::Forecast,
+                                    WeatherChoice::Clear,
+                                    WeatherChoice::Rain,
+                                    WeatherChoice::Fog,
+                                    WeatherChoice::Wind,
+                                ] {
+                                    let mut entity = parent.spawn((
+                                        Button,
+                                        Node {
+                                            width: Val::Px(120.0),
+                                            ..button_node.clone()
+                                        },
+                                        BackgroundColor(NORMAL_BUTTON),
+                                        setting,
+                                    ));
+                                    entity.with_children(|parent| {
+                                        parent.spawn((
+                                            Text::new(format!("{setting:?}")),
+                                            button_text_style.clone(),
+                                        ));
+                                    });
+                                    if *weather_choice == setting {
+                                        entity.insert(SelectedOption);
+                                    }
+                                }
+                            });
+
+                        parent
+                            .spawn((
+                                Node {
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                BackgroundColor(CRIMSON.into()),
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn((Text::new("Subtitles"), button_text_style.clone()));
+                                for (label, shown) in [("Off", false), ("On", true)] {
+                                    let mut entity = parent.spawn((
+                                        Button,
+                                        Node {
+                                            width: Val::Px(100.0),
+                                            ..button_node.clone()
+                                        },
+                                        BackgroundColor(NORMAL_BUTTON),
+                                        Subtitles(shown),
+                                    ));
+                                    entity.with_children(|parent| {
+                                        parent.spawn((Text::new(label), button_text_style.clone()));
+                                    });
+                                    if *subtitles == Subtitles(shown) {
+                                        entity.insert(SelectedOption);
+                                    }
+                                }
+                            });
+
+                        // drawing the way to the next stop, off for players who want it harder
+                        parent
+                            .spawn((
+                                Node {
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                BackgroundColor(CRIMSON.into()),
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn((
+                                    Text::new("Route Guidance"),
+                                    button_text_style.clone(),
+                                ));
+                                for (label, shown) in [("Off", false), ("On", true)] {
+                                    let mut entity = parent.spawn((
+                                        Button,
+                                        Node {
+                                            width: Val::Px(100.0),
+                                            ..button_node.clone()
+                                        },
+                                        BackgroundColor(NORMAL_BUTTON),
+                                        RouteGuidance(shown),
+                                    ));
+                                    entity.with_children(|parent| {
+                                        parent.spawn((Text::new(label), button_text_style.clone()));
+                                    });
+                                    if *route_guidance == RouteGuidance(shown) {
+                                        entity.insert(SelectedOption);
+                                    }
+                                }
+                            });
+
+                        // writing gameplay events to a local log, only if the player asks for it
+                        parent
+                            .spawn((
+                                Node {
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                BackgroundColor(CRIMSON.into()),
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn((Text::new("Telemetry"), button_text_style.clone()));
+                                for (label, enabled) in [("Off", false), ("On", true)] {
+                                    let mut entity = parent.spawn((
+                                        Button,
+                                        Node {
+                                            width: Val::Px(100.0),
+                                            ..button_node.clone()
+                                        },
+                                        BackgroundColor(NORMAL_BUTTON),
+                                        Telemetry(enabled),
+                                    ));
+                                    entity.with_children(|parent| {
+                                        parent.spawn((Text::new(label), button_text_style.clone()));
+                                    });
+                                    if *telemetry == Telemetry(enabled) {
+                                        entity.insert(SelectedOption);
+                                    }
+                                }
+                            });
+
+                        parent
+                            .spawn(Node {
+                                align_items: AlignItems::Center,
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                for (label, action) in [
+                                    ("Camera", MenuButtonAction::CameraSettings),
+                                    ("Back", MenuButtonAction::BackToMainMenu),
+                                ] {
+                                    parent
+                                        .spawn((
+                                            Button,
+                                            button_node.clone(),
+                                            BackgroundColor(NORMAL_BUTTON),
+                                            action,
+                                        ))
+                                        .with_children(|parent| {
+                                            parent.spawn((
+                                                Text::new(label),
+                                                button_text_style.clone(),
+                                            ));
+                                        });
+                                }
+                            });
+                    });
+            });
+    }
+    /// how a camera option's value is shown on the camera screen
+    fn camera_option_label(settings: &CameraModeSettings, option: CameraOption) -> String {
+        match option {
+            CameraOption::Zoom => format!("{:.1}x", settings.zoom),
+            CameraOption::Fov => format!("{:.0}°", settings.fov_degrees()),
+        }
+    }
+    fn camera_menu_setup(mut commands: Commands, camera_settings: Res<CameraSettings>) {
+        let button_node = Node {
+            width: Val::Px(50.0),
+            height: Val::Px(50.0),
+            margin: UiRect::all(Val::Px(6.0)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        };
+        let button_text_style = (
+            TextFont {
+                font_size: 33.0,
+                ..default()
+            },
+            TextColor(TXT_CLR),
+        );
+
+        commands
+            .spawn((
+                Node {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                OnCameraSettingsMenuScreen,
+            ))
+            .with_children(|parent| {
+                parent
+                    .spawn((
+                        Node {
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        BackgroundColor(CRIMSON.into()),
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn((
+                            Text::new("Camera"),
+                            TextFont {
+                                font_size: 67.0,
+                                ..default()
+                            },
+                            TextColor(TXT_CLR),
+                            Node {
+                                margin: UiRect::all(Val::Px(20.0)),
+                                ..default()
+                            },
+                        ));
+
+                        // a zoom and a field of view for each mode, stepped with - and +
+                        for mode in CameraMode::ALL {
+                            parent
+                                .spawn(Node {
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                })
+                                .with_children(|parent| {
+                                    parent.spawn((
+                                        Text::new(mode.label()),
+                                        button_text_style.clone(),
+                                        Node {
+                                            width: Val::Px(200.0),
+                                            ..default()
+                                        },
+                                    ));
+                                    for (title, option, step) in [
+                                        ("Zoom", CameraOption::Zoom, 0.1),
+                                        ("FOV", CameraOption::Fov, 5.0),
+                                    ] {
+                                        parent.spawn((
+                                            Text::new(title),
+                                            button_text_style.clone(),
+                                            Node {
+                                                margin: UiRect::left(Val::Px(20.0)),
+                                                ..default()
+                                            },
+                                        ));
+                                        for (label, step) in [("-", -step), ("+", step)] {
+                                            let mut button = parent.spawn((
+                                                Button,
+                                                button_node.clone(),
+                                                BackgroundColor(NORMAL_BUTTON),
+                                                CameraStepButton(mode, option, step),
+                                            ));
+                                            button.with_children(|parent| {
+                                                parent.spawn((
+                                                    Text::new(label),
+                                                    button_text_style.clone(),
+                                                ));
+                                            });
+                                            // the value sits between its two buttons
+                                            if step < 0.0 {
+                                                parent.spawn((
+                                                    Text::new(camera_option_label(
+                                                        camera_settings.mode(mode),
+                                                        option,
+                                                    )),
+                                                    button_text_style.clone(),
+                                                    Node {
+                                                        width: Val::Px(80.0),
+                                                        justify_content: JustifyContent::Center,
+                                                        ..default()
+                                                    },
+                                                    TextLayout::new_with_justify(
+                                                        JustifyText::Center,
+                                                    ),
+                                                    CameraOptionText(mode, option),
+                                                ));
+                                            }
+                                        }
+                                    }
+                                });
+                        }
+
+                        parent
+                            .spawn((
+                                Button,
+                                Node {
+                                    width: Val::Px(200.0),
+                                    ..button_node.clone()
+                                },
+                                BackgroundColor(NORMAL_BUTTON),
+                                MenuButtonAction::BackToSettings,
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn((Text::new("Back"), button_text_style.clone()));
+                            });
+                    });
+            });
+    }
+    fn camera_step_button(
+        interaction_query: Query<(&Interaction, &CameraStepButton), Changed<Interaction>>,
+        mut text_query: Query<(&mut Text, &CameraOptionText)>,
+        mut camera_settings: ResMut<CameraSettings>,
+    ) {
+        for (interaction, CameraStepButton(mode, option, step)) in &interaction_query {
+            if *interaction != Interaction::Pressed {
+                continue;
+            }
+            let settings = camera_settings.mode_mut(*mode);
+            match option {
+                CameraOption::Zoom => settings.set_zoom(settings.zoom + step),
+                CameraOption::Fov => settings.set_fov_degrees(settings.fov_degrees() + step),
+            }
+            for (mut text, CameraOptionText(text_mode, text_option)) in &mut text_query {
+                if text_mode == mode && text_option == option {
+                    text.0 = camera_option_label(settings, *option);
+                }
+            }
+        }
+    }
+    fn pause_menu_setup(
+        mut commands: Commands,
+        asset_server: Res<AssetServer>,
+        query: Query<Entity, With<Camera>>,
+    ) {
+        for entity in query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        commands.spawn((
+            Camera2d,
+            Camera {
+                order: 0, // Ensure unique order
+                ..default()
+            },
+        ));
+        let button_node = Node {
+            width: Val::Px(300.0),
+            height: Val::Px(65.0),
+            margin: UiRect::all(Val::Px(20.0)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        };
+        let button_icon_node = Node {
+            width: Val::Px(30.0),
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.0),
+            ..default()
+        };
+        let button_text_font = TextFont {
+            font_size: 33.0,
+            ..default()
+        };
+
+        commands
+            .spawn((
+                Node {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                OnPauseMenuScreen,
+            ))
+            .with_children(|parent| {
+                parent
+                    .spawn((
+                        Node {
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        BackgroundColor(CRIMSON.into()),
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn((
+                            Text::new("Pause Menu"),
+                            TextFont {
+                                font_size: 67.0,
+                                ..default()
+                            },
+                            TextColor(TXT_CLR),
+                            Node {
+                                margin: UiRect::all(Val::Px(50.0)),
+                                ..default()
+                            },
+                        ));
+
+                        parent
+                            .spawn((
+                                Button,
+                                button_node.clone(),
+                                BackgroundColor(NORMAL_BUTTON),
+                                MenuButtonAction::Play,
+                            ))
+                            .with_children(|parent| {
+                                let icon = asset_server.load("Images/new_game.png");
+                                parent.spawn((ImageNode::new(icon), button_icon_node.clone()));
+                                parent.spawn((
+                                    Text::new("Resume"),
+                                    button_text_font.clone(),
+                                    TextColor(TXT_CLR),
+                                ));
+                            });
+
+                        parent
+                            .spawn((
+                                Button,
+                                button_node.clone(),
+                                BackgroundColor(NORMAL_BUTTON),
+                                MenuButtonAction::Help,
+                            ))
+                            .with_children(|parent| {
+                                let icon = asset_server.load("Images/Help.png");
+                                parent.spawn((ImageNode::new(icon), button_icon_node.clone()));
+                                parent.spawn((
+                                    Text::new("Help"),
+                                    button_text_font.clone(),
+                                    TextColor(TXT_CLR),
+                                ));
+                            });
+
+                        parent
+                            .spawn((
+                                Button,
+                                button_node.clone(),
+                                BackgroundColor(NORMAL_BUTTON),
+                                MenuButtonAction::Quit,
+                            ))
+                            .with_children(|parent| {
+                                let icon = asset_server.load("Images/exit.png");
+                                parent.spawn((ImageNode::new(icon), button_icon_node.clone()));
+                                parent.spawn((
+                                    Text::new("Quit"),
+                                    button_text_font.clone(),
+                                    TextColor(TXT_CLR),
+                                ));
+                            });
+                    });
+            });
+    }
+    fn lose_menu_setup(
+        mut commands: Commands,
+        asset_server: Res<AssetServer>,
+        last_run: Option<Res<LastRun>>,
+    ) {
+        let button_node = Node {
+            width: Val::Px(300.0),
+            height: Val::Px(65.0),
+            margin: UiRect::all(Val::Px(20.0)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        };
+        let button_text_style = (
+            TextFont {
+                font_size: 33.0,
+                ..default()
+            },
+            TextColor(TXT_CLR),
+        );
+
+        commands
+            .spawn((
+                Node {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                OnLoseMenuScreen,
+            ))
+            .with_children(|parent| {
+                parent
+                    .spawn((
+                        Node {
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        BackgroundColor(CRIMSON.into()),
+                    ))
+                    .with_children(|parent| {
+                        if last_run.as_ref().is_some_and(|run| run.out_of_time) {
+                            parent.spawn((
+                                Text::new("Time's up!"),
+                                TextFont {
+                                    font_size: 67.0,
+                                    ..default()
+                                },
+                                TextColor(TXT_CLR),
+                                Node {
+                                    margin: UiRect::all(Val::Px(30.0)),
+                                    ..default()
+                                },
+                            ));
+                        } else {
+                            let icon = asset_server.load("Images/Lose_Icon.png");
+                            parent.spawn((
+                                ImageNode::new(icon),
+                                Node {
+                                    width: Val::Px(200.0),
+                                    height: Val::Px(200.0),
+                                    ..default()
+                                },
+                            ));
+                        }
+                        if let Some(run) = &last_run {
+                            parent.spawn((
+                                Text::new(format!("{} on {:?}", run.mode, run.difficulty)),
+                                button_text_style.clone(),
+                            ));
+                            // every point is banked as a coin for the upgrade shop
+                            parent.spawn((
+                                Text::new(format!("Score: {} (+{} coins)", run.score, run.score)),
+                                button_text_style.clone(),
+                            ));
+                            let best = if run.new_best {
+                                "New best!".to_string()
+                            } else {
+                                format!("Best: {}", run.best)
+                            };
+                            parent.spawn((Text::new(best), button_text_style.clone()));
+                        }
+
+                        parent
+                            .spawn((
+                                Button,
+                                button_node.clone(),
+                                BackgroundColor(NORMAL_BUTTON),
+                                MenuButtonAction::BackToMainMenu,
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn((Text::new("Main Menu"), button_text_style.clone()));
+                            });
+
+                        parent
+                            .spawn((
+                                Button,
+                                button_node.clone(),
+                                BackgroundColor(NORMAL_BUTTON),
+                                MenuButtonAction::Quit,
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn((Text::new("Exit"), button_text_style.clone()));
+                            });
+                    });
+            });
+    }
+    fn menu_action(
+        interaction_query: Query<
+            (&Interaction, &MenuButtonAction),
+            (Changed<Interaction>, With<Button>),
+        >,
+        mut app_exit_events: EventWriter<AppExit>,
+        mut menu_state: ResMut<NextState<MenuState>>,
+        mut game_state: ResMut<NextState<GameState>>,
+        mut param_set: ParamSet<(
+            Query<&mut Transform, With<PlayerModel>>,
+            Query<&mut Transform, With<RotatableCamera>>,
+        )>,
+        player_state: Res<PlayerState>,
+        camera_state: Res<CameraState>,
+        mut is_resuming: Local<bool>,
+    ) {
+        for (interaction, menu_button_action) in &interaction_query {
+            if *interaction == Interaction::Pressed {
+                match menu_button_action {
+                    MenuButtonAction::Quit => {
+                        app_exit_events.send(AppExit::Success);
+                    }
+                    MenuButtonAction::Play => {
+                        if *is_resuming {
+                            // Restore saved positions
                             if let Ok(mut player_transform) = param_set.p0().get_single_mut() {
                                 player_transform.translation = player_state.position;
                                 player_transform.rotation = player_state.rotation;
                             }
 
-                            if let Ok(mut camera_transform) = param_set.p1().get_single_mut() {
-                                camera_transform.translation = camera_state.position;
-                                camera_transform.rotation = camera_state.rotation;
-                            }
-                        }
-                        game_state.set(GameState::Game);
-                        menu_state.set(MenuState::Disabled);
-                        *is_resuming = false;
+                            if let Ok(mut camera_transform) = param_set.p1().get_single_mut() {
+                                camera_transform.translation = camera_state.position;
+                                camera_transform.rotation = camera_state.rotation;
+                            }
+                        }
+                        game_state.set(GameState::Game);
+                        menu_state.set(MenuState::Disabled);
+                        *is_resuming = false;
+                    }
+                    MenuButtonAction::ChooseMode => {
+                        menu_state.set(MenuState::Modes);
+                    }
+                    MenuButtonAction::BackToMainMenu => {
+                        game_state.set(GameState::Menu);
+                        menu_state.set(MenuState::Main);
+                    }
+                    MenuButtonAction::Help => {
+                        game_state.set(GameState::Help);
+                        menu_state.set(MenuState::Help);
+                    }
+                    MenuButtonAction::Settings | MenuButtonAction::BackToSettings => {
+                        menu_state.set(MenuState::Settings);
+                    }
+                    MenuButtonAction::CameraSettings => {
+                        menu_state.set(MenuState::CameraSettings);
+                    }
+                    MenuButtonAction::Upgrades => {
+                        menu_state.set(MenuState::Upgrades);
+                    }
+                    MenuButtonAction::Garage => {
+                        menu_state.set(MenuState::Garage);
+                    }
+                    MenuButtonAction::Achievements => {
+                        menu_state.set(MenuState::Achievements);
+                    }
+                    MenuButtonAction::Statistics => {
+                        menu_state.set(MenuState::Statistics);
+                    }
+                    MenuButtonAction::Profiles => {
+                        game_state.set(GameState::Profiles);
+                        menu_state.set(MenuState::Disabled);
+                    }
+                }
+            }
+        }
+    }
+}
+
+mod atmosphere {
+    use super::{despawn_screen, GameState, TimeOfDay, WeatherChoice, TXT_CLR};
+    use crate::game::{AtmosphereCamera, BuildingModel, PlayerModel};
+    use bevy::{
+        math::Affine3A,
+        pbr::{DistanceFog, FogFalloff},
+        prelude::*,
+        render::{
+            mesh::{Indices, PrimitiveTopology},
+            primitives::Aabb,
+            render_asset::RenderAssetUsages,
+        },
+    };
+    use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
+    use std::f32::consts::PI;
+
+    // This plugin runs the clock and the weather over the course of a run: the sun, the ambient
+    // light and the sky colour follow the time of day, building windows and street lamps light up
+    // at night, and rain, fog and wind roll in on a forecast drawn from the run's seed
+    pub fn atmosphere_plugin(app: &mut App) {
+        app.add_systems(OnEnter(GameState::Game), atmosphere_setup)
+            .add_systems(
+                Update,
+                (
+                    advance_time_of_day,
+                    advance_weather,
+                    update_lighting,
+                    update_rain,
+                    add_building_windows,
+                    light_up_night,
+                    update_clock,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Game)),
+            )
+            .add_systems(OnExit(GameState::Game), despawn_screen::<OnAtmosphere>);
+    }
+
+    /// hour on the clock when a run starts, so a round goes from afternoon into the night
+    const START_HOUR: f32 = 16.0;
+    /// in-game hours that pass for every real second
+    const HOURS_PER_SECOND: f32 = 0.1;
+    /// how far out the street lamps stand, lighting the outer road where the senders wait
+    const LAMP_RING_RADIUS: f32 = 193.0;
+    const LAMP_COUNT: usize = 12;
+    /// chance that any given window has its lights on at night
+    const LIT_WINDOW_CHANCE: f64 = 0.45;
+
+    /// how many rain drops are kept falling around the robot
+    const RAIN_DROPS: usize = 300;
+    /// half the width of the box around the robot the rain falls in
+    const RAIN_AREA: f32 = 150.0;
+    const RAIN_HEIGHT: f32 = 160.0;
+    const RAIN_SPEED: f32 = 220.0;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Weather {
+        Clear,
+        Rain,
+        Fog,
+        Wind,
+    }
+    impl Weather {
+        /// distance at which everything has disappeared into the fog, and the route guidance with it
+        pub fn visibility(self) -> f32 {
+            match self {
+                Weather::Clear => 1500.0,
+                Weather::Rain => 500.0,
+                Weather::Fog => 160.0,
+                Weather::Wind => 1200.0,
+            }
+        }
+        /// how grey the sky gets, from 0 to 1
+        fn overcast(self) -> f32 {
+            match self {
+                Weather::Clear => 0.0,
+                Weather::Rain => 0.7,
+                Weather::Fog => 0.5,
+                Weather::Wind => 0.2,
+            }
+        }
+        /// how well the robot's wheels grip the road, 1 being a dry road
+        fn traction(self) -> f32 {
+            match self {
+                Weather::Rain => 0.25,
+                _ => 1.0,
+            }
+        }
+        /// how hard the wind pushes the robot, in units per second
+        fn wind_strength(self) -> f32 {
+            match self {
+                Weather::Wind => 14.0,
+                Weather::Rain => 4.0,
+                _ => 0.0,
+            }
+        }
+    }
+    /// the run's weather forecast, each spell of weather lasts a while before the next one is drawn
+    pub struct WeatherForecast {
+        rng: StdRng,
+        weather: Weather,
+        wind_heading: f32,
+        remaining: f32,
+    }
+    impl WeatherForecast {
+        fn new(seed: u64) -> Self {
+            WeatherForecast {
+                // keep the weather's draws apart from anything else seeded by the run
+                rng: StdRng::seed_from_u64(seed ^ 0x5745_4154_4845_5200),
+                weather: Weather::Clear,
+                wind_heading: 0.0,
+                // every run starts with a short clear spell
+                remaining: 15.0,
+            }
+        }
+        fn tick(&mut self, delta_secs: f32) {
+            self.remaining -= delta_secs;
+            if self.remaining > 0.0 {
+                return;
+            }
+            self.weather = match self.rng.gen_range(0..20) {
+                0..8 => Weather::Clear,
+                8..13 => Weather::Rain,
+                13..17 => Weather::Fog,
+                _ => Weather::Wind,
+            };
+            self.wind_heading = self.rng.gen_range(0.0..2.0 * PI);
+            self.remaining = self.rng.gen_range(15.0..35.0);
+        }
+    }
+    #[derive(Resource)]
+    pub struct AtmosphereModel {
+        /// hour of the day, from 0 up to 24
+        pub time_of_day: f32,
+        pub weather: Weather,
+        /// current fog distance, eased towards what the weather calls for
+        pub visibility: f32,
+        /// current grip of the road, eased towards what the weather calls for
+        pub traction: f32,
+        /// how grey the sky is right now
+        pub overcast: f32,
+        /// sideways push the wind puts on the robot, in units per second
+        pub wind: Vec3,
+        forecast: WeatherForecast,
+    }
+    impl AtmosphereModel {
+        pub fn new(seed: u64) -> Self {
+            AtmosphereModel {
+                time_of_day: START_HOUR,
+                weather: Weather::Clear,
+                visibility: Weather::Clear.visibility(),
+                traction: 1.0,
+                overcast: 0.0,
+                wind: Vec3::ZERO,
+                forecast: WeatherForecast::new(seed),
+            }
+        }
+        /// 1 with the sun straight up, 0 when it sits on the horizon at 6 and 18 o'clock
+        pub fn sun_height(&self) -> f32 {
+            ((self.time_of_day - 6.0) / 12.0 * PI).sin()
+        }
+        /// 1 in full daylight, fading down to 0 once the sun has set
+        pub fn daylight(&self) -> f32 {
+            let t = ((self.sun_height() + 0.1) / 0.35).clamp(0.0, 1.0);
+            t * t * (3.0 - 2.0 * t)
+        }
+        pub fn is_night(&self) -> bool {
+            self.daylight() < 0.2
+        }
+    }
+
+    #[derive(Component)]
+    struct OnAtmosphere;
+    #[derive(Component)]
+    struct Sun;
+    #[derive(Component)]
+    struct StreetLamp;
+    #[derive(Component)]
+    struct ClockText;
+    #[derive(Component)]
+    struct RainCloud;
+    #[derive(Component)]
+    struct RainDrop;
+    /// put on a building once its window panels have been added
+    #[derive(Component)]
+    struct LitWindows;
+    #[derive(Component)]
+    struct BuildingWindows;
+    /// materials that glow at night, shared by every window and lamp
+    #[derive(Resource)]
+    struct NightMaterials {
+        windows: Handle<StandardMaterial>,
+        lamps: Handle<StandardMaterial>,
+    }
+
+    fn atmosphere_setup(
+        mut commands: Commands,
+        mut meshes: ResMut<Assets<Mesh>>,
+        mut materials: ResMut<Assets<StandardMaterial>>,
+    ) {
+        commands.spawn((
+            DirectionalLight {
+                shadows_enabled: true,
+                ..default()
+            },
+            Transform::default(),
+            Sun,
+            OnAtmosphere,
+        ));
+
+        let night_materials = NightMaterials {
+            windows: materials.add(StandardMaterial {
+                base_color: Color::srgb(0.1, 0.1, 0.12),
+                ..default()
+            }),
+            lamps: materials.add(StandardMaterial {
+                base_color: Color::srgb(0.9, 0.85, 0.6),
+                ..default()
+            }),
+        };
+        let pole = meshes.add(Cylinder::new(0.6, 18.0));
+        let pole_material = materials.add(Color::srgb(0.2, 0.2, 0.22));
+        let bulb = meshes.add(Sphere::new(1.5));
+        for i in 0..LAMP_COUNT {
+            let angle = i as f32 / LAMP_COUNT as f32 * 2.0 * PI;
+            let position = Vec3::new(angle.cos(), 0.0, angle.sin()) * LAMP_RING_RADIUS;
+            commands
+                .spawn((
+                    Mesh3d(pole.clone()),
+                    MeshMaterial3d(pole_material.clone()),
+                    Transform::from_translation(position + Vec3::Y * 10.1),
+                    OnAtmosphere,
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        Mesh3d(bulb.clone()),
+                        MeshMaterial3d(night_materials.lamps.clone()),
+                        Transform::from_xyz(0.0, 9.5, 0.0),
+                    ));
+                    parent.spawn((
+                        PointLight {
+                            intensity: 0.0,
+                            range: 80.0,
+                            color: Color::srgb(1.0, 0.85, 0.6),
+                            ..default()
+                        },
+                        Transform::from_xyz(0.0, 8.0, 0.0),
+                        StreetLamp,
+                    ));
+                });
+        }
+        commands.insert_resource(night_materials);
+
+        // the drops are kept in world space, the cloud only exists to hide them all at once
+        let drop = meshes.add(Cuboid::new(0.15, 4.0, 0.15));
+        let drop_material = materials.add(StandardMaterial {
+            base_color: Color::srgba(0.7, 0.75, 0.9, 0.5),
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..default()
+        });
+        let mut rng = thread_rng();
+        commands
+            .spawn((
+                Transform::default(),
+                Visibility::Hidden,
+                RainCloud,
+                OnAtmosphere,
+            ))
+            .with_children(|parent| {
+                for _ in 0..RAIN_DROPS {
+                    parent.spawn((
+                        Mesh3d(drop.clone()),
+                        MeshMaterial3d(drop_material.clone()),
+                        Transform::from_xyz(
+                            rng.gen_range(-RAIN_AREA..RAIN_AREA),
+                            rng.gen_range(0.0..RAIN_HEIGHT),
+                            rng.gen_range(-RAIN_AREA..RAIN_AREA),
+                        ),
+                        RainDrop,
+                    ));
+                }
+            });
+
+        commands.spawn((
+            ClockText,
+            Text::new(""),
+            TextFont {
+                font_size: 40.0,
+                ..default()
+            },
+            TextColor(TXT_CLR),
+            Node {
+                position_type: PositionType::Absolute,
+                right: Val::Px(10.0),
+                ..default()
+            },
+            OnAtmosphere,
+        ));
+    }
+    fn advance_time_of_day(
+        time: Res<Time>,
+        time_of_day: Res<TimeOfDay>,
+        mut atmosphere: ResMut<AtmosphereModel>,
+    ) {
+        atmosphere.time_of_day = match time_of_day.locked_hour() {
+            Some(hour) => hour,
+            None => (atmosphere.time_of_day + HOURS_PER_SECOND * time.delta_secs()) % 24.0,
+        };
+    }
+    fn advance_weather(
+        time: Res<Time>,
+        weather_choice: Res<WeatherChoice>,
+        mut atmosphere: ResMut<AtmosphereModel>,
+    ) {
+        let delta_secs = time.delta_secs();
+        atmosphere.forecast.tick(delta_secs);
+        let weather = weather_choice
+            .fixed()
+            .unwrap_or(atmosphere.forecast.weather);
+        atmosphere.weather = weather;
+
+        // ease everything over a few seconds so a change of weather rolls in rather than snapping
+        let t = 1.0 - (-0.5 * delta_secs).exp();
+        let heading = atmosphere.forecast.wind_heading;
+        let wind = Vec3::new(heading.cos(), 0.0, heading.sin()) * weather.wind_strength();
+        atmosphere.wind = atmosphere.wind.lerp(wind, t);
+        atmosphere.visibility += (weather.visibility() - atmosphere.visibility) * t;
+        atmosphere.traction += (weather.traction() - atmosphere.traction) * t;
+        atmosphere.overcast += (weather.overcast() - atmosphere.overcast) * t;
+    }
+    fn update_lighting(
+        mut commands: Commands,
+        atmosphere: Res<AtmosphereModel>,
+        mut sun_query: Query<(&mut Transform, &mut DirectionalLight), With<Sun>>,
+        mut ambient_light: ResMut<AmbientLight>,
+        mut camera_query: Query<
+            (Entity, &mut Camera, Option<&mut DistanceFog>),
+            With<AtmosphereCamera>,
+        >,
+    ) {
+        let daylight = atmosphere.daylight();
+        // how close the sun is to the horizon, used to tint sunrise and sunset
+        let low_sun = (1.0 - atmosphere.sun_height().abs() * 3.0).clamp(0.0, 1.0);
+        let angle = (atmosphere.time_of_day - 6.0) / 12.0 * PI;
+        // the sun rises in the east (+x) and sets in the west, tilted so it's never straight overhead
+        let towards_sun = Vec3::new(angle.cos(), angle.sin(), 0.35).normalize();
+        for (mut transform, mut light) in sun_query.iter_mut() {
+            *transform =
+                Transform::from_translation(towards_sun * 300.0).looking_at(Vec3::ZERO, Vec3::Y);
+            light.illuminance =
+                light_consts::lux::AMBIENT_DAYLIGHT * daylight * (1.0 - 0.7 * atmosphere.overcast);
+            light.color = Color::WHITE.mix(&Color::srgb(1.0, 0.6, 0.35), low_sun);
+        }
+
+        ambient_light.color = Color::srgb(0.35, 0.4, 0.7).mix(&Color::WHITE, daylight);
+        ambient_light.brightness = 40.0 + 260.0 * daylight;
+
+        let sky = Color::srgb(0.02, 0.03, 0.08)
+            .mix(&Color::srgb(0.9, 0.5, 0.3), low_sun * daylight.max(0.3))
+            .mix(&Color::srgb(0.45, 0.7, 0.95), daylight * (1.0 - low_sun))
+            .mix(
+                &Color::srgb(0.5, 0.52, 0.55).mix(&Color::srgb(0.05, 0.05, 0.06), 1.0 - daylight),
+                atmosphere.overcast,
+            );
+        let falloff = FogFalloff::Linear {
+            start: atmosphere.visibility * 0.2,
+            end: atmosphere.visibility,
+        };
+        for (entity, mut camera, fog) in camera_query.iter_mut() {
+            camera.clear_color = ClearColorConfig::Custom(sky);
+            // the fog fades into the sky so far off buildings melt into the background
+            match fog {
+                Some(mut fog) => {
+                    fog.color = sky;
+                    fog.falloff = falloff.clone();
+                }
+                None => {
+                    commands.entity(entity).insert(DistanceFog {
+                        color: sky,
+                        falloff: falloff.clone(),
+                        ..default()
+                    });
+                }
+            }
+        }
+    }
+    fn update_rain(
+        time: Res<Time>,
+        atmosphere: Res<AtmosphereModel>,
+        mut cloud_query: Query<&mut Visibility, With<RainCloud>>,
+        mut drop_query: Query<&mut Transform, (With<RainDrop>, Without<PlayerModel>)>,
+        player_query: Query<&Transform, With<PlayerModel>>,
+    ) {
+        let raining = atmosphere.weather == Weather::Rain;
+        for mut visibility in cloud_query.iter_mut() {
+            visibility.set_if_neq(if raining {
+                Visibility::Inherited
+            } else {
+                Visibility::Hidden
+            });
+        }
+        if !raining {
+            return;
+        }
+        let center = player_query
+            .get_single()
+            .map(|transform| transform.translation)
+            .unwrap_or_default();
+        let fall = (Vec3::NEG_Y * RAIN_SPEED + atmosphere.wind * 3.0) * time.delta_secs();
+        let mut rng = thread_rng();
+        for mut transform in drop_query.iter_mut() {
+            transform.translation += fall;
+            let offset = transform.translation - center;
+            // drops that land or drift out of the box start again from the top near the robot
+            if transform.translation.y < 0.0
+                || offset.x.abs() > RAIN_AREA
+                || offset.z.abs() > RAIN_AREA
+            {
+                transform.translation = Vec3::new(
+                    center.x + rng.gen_range(-RAIN_AREA..RAIN_AREA),
+                    RAIN_HEIGHT,
+                    center.z + rng.gen_range(-RAIN_AREA..RAIN_AREA),
+                );
+            }
+        }
+    }
+    /// once a building's scene has loaded, wraps it in a single mesh of window panels that glow at night
+    fn add_building_windows(
+        mut commands: Commands,
+        buildings: Query<Entity, (With<BuildingModel>, Without<LitWindows>)>,
+        children: Query<&Children>,
+        bounds: Query<&Aabb>,
+        transforms: Query<(&Transform, &Parent)>,
+        night_materials: Res<NightMaterials>,
+        mut meshes: ResMut<Assets<Mesh>>,
+    ) {
+        for building in buildings.iter() {
+            let mut min = Vec3::MAX;
+            let mut max = Vec3::MIN;
+            for entity in children.iter_descendants(building) {
+                let Ok(aabb) = bounds.get(entity) else {
+                    continue;
+                };
+                let Some(to_building) = relative_transform(entity, building, &transforms) else {
+                    continue;
+                };
+                let (center, half_extents) =
+                    (Vec3::from(aabb.center), Vec3::from(aabb.half_extents));
+                for corner in [-1.0, 1.0].into_iter().flat_map(|x| {
+                    [-1.0, 1.0]
+                        .into_iter()
+                        .flat_map(move |y| [-1.0, 1.0].map(|z| Vec3::new(x, y, z)))
+                }) {
+                    let point = to_building.transform_point3(center + half_extents * corner);
+                    min = min.min(point);
+                    max = max.max(point);
+                }
+            }
+            if min.x > max.x {
+                // the scene hasn't spawned its meshes yet
+                continue;
+            }
+            commands.entity(building).insert(LitWindows).with_child((
+                Mesh3d(meshes.add(window_mesh(min, max))),
+                MeshMaterial3d(night_materials.windows.clone()),
+                BuildingWindows,
+            ));
+        }
+    }
+    /// combines the local transforms between a mesh and one of its ancestors
+    fn relative_transform(
+        mut entity: Entity,
+        ancestor: Entity,
+        transforms: &Query<(&Transform, &Parent)>,
+    ) -> Option<Affine3A> {
+        let mut affine = Affine3A::IDENTITY;
+        while entity != ancestor {
+            let (transform, parent) = transforms.get(entity).ok()?;
+            affine = transform.compute_affine() * affine;
+            entity = parent.get();
+        }
+        Some(affine)
+    }
+    /// builds one mesh with a window panel in a random selection of the slots on each side of a box
+    fn window_mesh(min: Vec3, max: Vec3) -> Mesh {
+        const WINDOW_SIZE: Vec2 = Vec2::new(4.0, 5.0);
+        const SPACING: Vec2 = Vec2::new(8.0, 12.0);
+        let mut rng = thread_rng();
+        let center = (min + max) / 2.0;
+        let half_extents = (max - min) / 2.0;
+        let mut positions = Vec::new();
+        let mut normals = Vec::new();
+        let mut uvs = Vec::new();
+        let mut indices = Vec::new();
+        for normal in [Vec3::X, Vec3::NEG_X, Vec3::Z, Vec3::NEG_Z] {
+            // runs along the wall so that the panels face outwards
+            let along = Vec3::Y.cross(normal);
+            let wall = center + normal * (normal.dot(half_extents).abs() + 0.3);
+            let half_width = along.dot(half_extents).abs();
+            let columns = ((half_width * 2.0 - 4.0) / SPACING.x).floor().max(0.0) as i32;
+            let mut height = min.y + 8.0;
+            while height + WINDOW_SIZE.y < max.y - 4.0 {
+                for column in 0..columns {
+                    if !rng.gen_bool(LIT_WINDOW_CHANCE) {
+                        continue;
+                    }
+                    let offset = (column as f32 - (columns - 1) as f32 / 2.0) * SPACING.x;
+                    let middle = Vec3::new(wall.x, height, wall.z) + along * offset;
+                    let across = along * WINDOW_SIZE.x / 2.0;
+                    let up = Vec3::Y * WINDOW_SIZE.y / 2.0;
+                    let first = positions.len() as u32;
+                    positions.extend([
+                        middle - across - up,
+                        middle + across - up,
+                        middle + across + up,
+                        middle - across + up,
+                    ]);
+                    normals.extend([normal; 4]);
+                    uvs.extend([[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]]);
+                    indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+                }
+                height += SPACING.y;
+            }
+        }
+        Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+        .with_inserted_indices(Indices::U32(indices))
+    }
+    fn light_up_night(
+        atmosphere: Res<AtmosphereModel>,
+        night_materials: Res<NightMaterials>,
+        mut materials: ResMut<Assets<StandardMaterial>>,
+        mut lamp_query: Query<&mut PointLight, With<StreetLamp>>,
+        mut window_query: Query<&mut Visibility, With<BuildingWindows>>,
+        mut last_glow: Local<Option<f32>>,
+    ) {
+        let glow = 1.0 - atmosphere.daylight();
+        let visibility = if glow > 0.05 {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        for mut window_visibility in window_query.iter_mut() {
+            window_visibility.set_if_neq(visibility);
+        }
+        // only touch the materials when the light level actually moved, they're shared by everything
+        if !night_materials.is_changed() && last_glow.is_some_and(|last| (last - glow).abs() < 0.01)
+        {
+            return;
+        }
+        *last_glow = Some(glow);
+        if let Some(material) = materials.get_mut(&night_materials.windows) {
+            material.emissive = LinearRgba::rgb(1.0, 0.8, 0.45) * (glow * 6.0);
+        }
+        if let Some(material) = materials.get_mut(&night_materials.lamps) {
+            material.emissive = LinearRgba::rgb(1.0, 0.9, 0.6) * (glow * 12.0);
+        }
+        for mut light in lamp_query.iter_mut() {
+            light.intensity = 2_000_000.0 * glow;
+        }
+    }
+    fn update_clock(
+        atmosphere: Res<AtmosphereModel>,
+        mut clock_query: Query<&mut Text, With<ClockText>>,
+    ) {
+        let hours = atmosphere.time_of_day.floor();
+        let minutes = ((atmosphere.time_of_day - hours) * 60.0).floor();
+        let bonus = if atmosphere.is_night() {
+            " (night bonus)"
+        } else {
+            ""
+        };
+        for mut text in clock_query.iter_mut() {
+            text.0 = format!(
+                "{:02}:{:02} {:?}{bonus}",
+                hours as u32, minutes as u32, atmosphere.weather
+            );
+        }
+    }
+}
+
+mod audio {
+    use super::{asset_file, Subtitles, Volume, TXT_CLR};
+    use bevy::{
+        audio::{SpatialScale, Volume as SinkVolume},
+        prelude::*,
+    };
+    use rand::{thread_rng, Rng};
+    use std::{collections::HashMap, fs, time::Duration};
+
+    // This plugin plays every one-shot sound in the game. Gameplay sends a SoundEvent, and the
+    // mixer decides whether there's a free voice for it, cutting off the least important sound
+    // when too many are going at once, and pans it relative to the camera when it has a position.
+    // Spoken lines are picked from the voice line manifest and shown as subtitles
+    pub fn audio_plugin(app: &mut App) {
+        app.add_event::<SoundEvent>()
+            .init_resource::<Subtitle>()
+            .add_systems(Startup, (load_voice_lines, spawn_subtitles))
+            .add_systems(Update, (play_sounds, show_subtitles).chain());
+    }
+
+    /// most one-shot sounds that can be heard at once
+    const MAX_VOICES: usize = 8;
+    /// shrinks world distances before panning and fall-off, a building is about 36 units across
+    const SPATIAL_SCALE: f32 = 1.0 / 60.0;
+    const VOICE_LINES_FILE: &str = "Audio/voice_lines.cfg";
+
+    /// something a person in the city says
+    #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+    pub enum Sound {
+        Pickup,
+        FragilePickup,
+        Delivery,
+        LateDelivery,
+        CrashNearby,
+        Idle,
+        ParcelBroken,
+    }
+    impl Sound {
+        /// the name the sound goes by in the voice line manifest
+        fn from_key(key: &str) -> Option<Self> {
+            match key {
+                "pickup" => Some(Sound::Pickup),
+                "fragile_pickup" => Some(Sound::FragilePickup),
+                "delivery" => Some(Sound::Delivery),
+                "late_delivery" => Some(Sound::LateDelivery),
+                "crash_nearby" => Some(Sound::CrashNearby),
+                "idle" => Some(Sound::Idle),
+                "parcel_broken" => Some(Sound::ParcelBroken),
+                _ => None,
+            }
+        }
+        /// when every voice is taken, a sound only plays if it beats the least important one
+        fn priority(self) -> u8 {
+            match self {
+                Sound::Idle => 0,
+                Sound::Pickup | Sound::FragilePickup => 1,
+                Sound::Delivery | Sound::LateDelivery | Sound::ParcelBroken => 2,
+                Sound::CrashNearby => 3,
+            }
+        }
+        /// how many copies of the sound can overlap before new ones are skipped
+        fn max_instances(self) -> usize {
+            1
+        }
+    }
+    #[derive(Event)]
+    pub struct SoundEvent {
+        pub sound: Sound,
+        /// where the sound comes from, or None to play it straight to both speakers
+        pub position: Option<Vec3>,
+    }
+    impl SoundEvent {
+        pub fn at(sound: Sound, position: Vec3) -> Self {
+            SoundEvent {
+                sound,
+                position: Some(position),
+            }
+        }
+    }
+    /// a one-shot sound that's playing, despawned by bevy once it finishes
+    #[derive(Component)]
+    struct SoundVoice {
+        sound: Sound,
+        started: Duration,
+    }
+
+    struct VoiceLine {
+        /// recording to play, lines without one are only shown as subtitles
+        clip: Option<String>,
+        subtitle: String,
+    }
+    /// every line each sound can be voiced with, read from the manifest
+    #[derive(Resource, Default)]
+    struct VoiceLineBank {
+        lines: HashMap<Sound, Vec<VoiceLine>>,
+        /// the line picked last time for each sound, so it doesn't come straight back
+        last: HashMap<Sound, usize>,
+    }
+    impl VoiceLineBank {
+        fn parse(manifest: &str) -> Self {
+            let mut bank = VoiceLineBank::default();
+            for line in manifest.lines().map(str::trim) {
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let mut fields = line.splitn(3, '|').map(str::trim);
+                let (Some(cue), Some(clip), Some(subtitle)) =
+                    (fields.next(), fields.next(), fields.next())
+                else {
+                    warn!("skipping malformed voice line: {line}");
+                    continue;
+                };
+                let Some(sound) = Sound::from_key(cue) else {
+                    warn!("skipping voice line for unknown cue {cue}");
+                    continue;
+                };
+                bank.lines.entry(sound).or_default().push(VoiceLine {
+                    clip: (!clip.is_empty()).then(|| clip.to_string()),
+                    subtitle: subtitle.to_string(),
+                });
+            }
+            bank
+        }
+        /// picks one of the sound's lines at random, never the same one twice in a row
+        fn pick(&mut self, sound: Sound) -> Option<&VoiceLine> {
+            let lines = self.lines.get(&sound)?;
+            let mut index = thread_rng().gen_range(0..lines.len());
+            if lines.len() > 1 && self.last.get(&sound) == Some(&index) {
+                index = (index + thread_rng().gen_range(1..lines.len())) % lines.len();
+            }
+            self.last.insert(sound, index);
+            lines.get(index)
+        }
+    }
+    /// the line that was last spoken, shown along the bottom of the screen for a little while
+    #[derive(Resource, Default)]
+    struct Subtitle {
+        text: String,
+        remaining: f32,
+    }
+    #[derive(Component)]
+    struct SubtitleText;
+
+    /// the ears to put on the camera so positioned sounds pan with the view
+    pub fn listener() -> SpatialListener {
+        SpatialListener::new(0.5 / SPATIAL_SCALE)
+    }
+
+    fn load_voice_lines(mut commands: Commands) {
+        let manifest = fs::read_to_string(asset_file(VOICE_LINES_FILE)).unwrap_or_else(|error| {
+            warn!("could not read {VOICE_LINES_FILE}: {error}");
+            String::new()
+        });
+        commands.insert_resource(VoiceLineBank::parse(&manifest));
+    }
+    fn spawn_subtitles(mut commands: Commands) {
+        commands
+            .spawn(Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                bottom: Val::Px(40.0),
+                justify_content: JustifyContent::Center,
+                ..default()
+            })
+            .with_children(|parent| {
+                parent.spawn((
+                    Text::new(""),
+                    TextFont {
+                        font_size: 30.0,
+                        ..default()
+                    },
+                    TextColor(TXT_CLR),
+                    BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+                    Node {
+                        padding: UiRect::axes(Val::Px(12.0), Val::Px(4.0)),
+                        ..default()
+                    },
+                    Visibility::Hidden,
+                    SubtitleText,
+                ));
+            });
+    }
+    fn play_sounds(
+        mut commands: Commands,
+        mut events: EventReader<SoundEvent>,
+        asset_server: Res<AssetServer>,
+        time: Res<Time>,
+        volume: Res<Volume>,
+        voice_query: Query<(Entity, &SoundVoice)>,
+        (mut bank, mut subtitle): (ResMut<VoiceLineBank>, ResMut<Subtitle>),
+    ) {
+        let mut voices: Vec<(Entity, Sound, Duration)> = voice_query
+            .iter()
+            .map(|(entity, voice)| (entity, voice.sound, voice.started))
+            .collect();
+        for event in events.read() {
+            let sound = event.sound;
+            let playing = voices.iter().filter(|voice| voice.1 == sound).count();
+            if playing >= sound.max_instances() {
+                continue;
+            }
+            let Some(line) = bank.pick(sound) else {
+                continue;
+            };
+            // only a line with a recording needs one of the voices
+            if line.clip.is_some() && voices.len() >= MAX_VOICES {
+                // steal the oldest of the least important voices, as long as it matters less
+                let Some(index) = voices
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, voice)| (voice.1.priority(), voice.2))
+                    .map(|(index, _)| index)
+                else {
+                    continue;
+                };
+                if voices[index].1.priority() >= sound.priority() {
+                    continue;
+                }
+                commands.entity(voices[index].0).despawn();
+                voices.swap_remove(index);
+            }
+            subtitle.text.clone_from(&line.subtitle);
+            // long enough to read at a relaxed pace
+            subtitle.remaining = 2.0 + 0.06 * line.subtitle.len() as f32;
+            let Some(clip) = &line.clip else {
+                continue;
+            };
+
+            let settings =
+                PlaybackSettings::DESPAWN.with_volume(SinkVolume::new(volume.0 as f32 / 9.0));
+            let mut entity = commands.spawn((
+                AudioPlayer::new(asset_server.load(clip)),
+                SoundVoice {
+                    sound,
+                    started: time.elapsed(),
+                },
+            ));
+            match event.position {
+                Some(position) => entity.insert((
+                    settings
+                        .with_spatial(true)
+                        .with_spatial_scale(SpatialScale::new(SPATIAL_SCALE)),
+                    Transform::from_translation(position),
+                )),
+                None => entity.insert(settings),
+            };
+            voices.push((entity.id(), sound, time.elapsed()));
+        }
+    }
+    fn show_subtitles(
+        time: Res<Time>,
+        subtitles: Res<Subtitles>,
+        mut subtitle: ResMut<Subtitle>,
+        mut text_query: Query<(&mut Text, &mut Visibility), With<SubtitleText>>,
+    ) {
+        subtitle.remaining -= time.delta_secs();
+        let showing = subtitles.0 && subtitle.remaining > 0.0;
+        for (mut text, mut visibility) in text_query.iter_mut() {
+            if showing && text.0 != subtitle.text {
+                text.0.clone_from(&subtitle.text);
+            }
+            visibility.set_if_neq(if showing {
+                Visibility::Inherited
+            } else {
+                Visibility::Hidden
+            });
+        }
+    }
+}
+
+mod music {
+    use super::{asset_file, GameState, Volume};
+    use crate::game::{CheckPointCube, GameTimer};
+    use bevy::prelude::*;
+
+    // This plugin keeps a looping track going for whichever screen is up, crossfading between
+    // them when the state changes, and brings extra layers in over the gameplay track as a run
+    // gets tense
+    pub fn music_plugin(app: &mut App) {
+        app.init_resource::<MusicIntensity>()
+            .add_systems(Startup, spawn_music)
+            .add_systems(
+                Update,
+                (
+                    update_intensity.run_if(in_state(GameState::Game)),
+                    mix_music,
+                )
+                    .chain(),
+            );
+    }
+
+    /// how long it takes one track to fade out and the next to fade in
+    const CROSSFADE_SECS: f32 = 1.5;
+    /// the timer starts pushing the music up for the last this many seconds of a run
+    const HURRY_SECS: f32 = 20.0;
+    /// how far up the music goes while the robot has a parcel on board
+    const CARRYING_INTENSITY: f32 = 0.4;
+
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+    enum MusicTrack {
+        Menu,
+        Gameplay,
+        Pause,
+        Results,
+    }
+    impl MusicTrack {
+        fn for_state(state: &GameState) -> Option<Self> {
+            match state {
+                GameState::Splash => None,
+                GameState::Profiles | GameState::Menu | GameState::Help => Some(MusicTrack::Menu),
+                GameState::Game => Some(MusicTrack::Gameplay),
+                GameState::Pause => Some(MusicTrack::Pause),
+                GameState::Lose => Some(MusicTrack::Results),
+            }
+        }
+    }
+    /// every stem that gets played, with the intensity it starts coming in at
+    const LAYERS: [(MusicTrack, &str, f32); 6] = [
+        (MusicTrack::Menu, "Audio/Music/menu.ogg", 0.0),
+        (MusicTrack::Gameplay, "Audio/Music/gameplay_base.ogg", 0.0),
+        (MusicTrack::Gameplay, "Audio/Music/gameplay_drums.ogg", 0.35),
+        (MusicTrack::Gameplay, "Audio/Music/gameplay_lead.ogg", 0.7),
+        (MusicTrack::Pause, "Audio/Music/pause.ogg", 0.0),
+        (MusicTrack::Results, "Audio/Music/results.ogg", 0.0),
+    ];
+
+    /// how tense the gameplay music is, from 0 up to 1
+    #[derive(Resource, Default)]
+    pub struct MusicIntensity(pub f32);
+    /// one looping stem, kept playing the whole time so the layers stay in step with each other
+    #[derive(Component)]
+    struct MusicLayer {
+        track: MusicTrack,
+        threshold: f32,
+        gain: f32,
+    }
+
+    fn spawn_music(mut commands: Commands, asset_server: Res<AssetServer>) {
+        for (track, path, threshold) in LAYERS {
+            // the tracks aren't shipped with the game, so any that are missing just stay quiet
+            if !asset_file(path).exists() {
+                warn!("no music at {path}, leaving it out");
+                continue;
+            }
+            commands.spawn((
+                AudioPlayer::new(asset_server.load(path)),
+                PlaybackSettings::LOOP.with_volume(bevy::audio::Volume::new(0.0)),
+                MusicLayer {
+                    track,
+                    threshold,
+                    gain: 0.0,
+                },
+            ));
+        }
+    }
+    fn update_intensity(
+        time: Res<Time>,
+        timer: Option<Res<GameTimer>>,
+        parcel_query: Query<(), With<CheckPointCube>>,
+        mut intensity: ResMut<MusicIntensity>,
+    ) {
+        let hurry = timer
+            .map(|timer| 1.0 - (timer.remaining_secs() / HURRY_SECS).min(1.0))
+            .unwrap_or_default();
+        let carrying = if parcel_query.is_empty() {
+            0.0
+        } else {
+            CARRYING_INTENSITY
+        };
+        let target = hurry.max(carrying);
+        // ease it so a layer doesn't stutter in and out on the edge of its threshold
+        intensity.0 += (target - intensity.0) * (1.0 - (-2.0 * time.delta_secs()).exp());
+    }
+    fn mix_music(
+        time: Res<Time>,
+        state: Res<State<GameState>>,
+        volume: Res<Volume>,
+        intensity: Res<MusicIntensity>,
+        mut layer_query: Query<(&mut MusicLayer, Option<&AudioSink>)>,
+    ) {
+        let current = MusicTrack::for_state(state.get());
+        let step = time.delta_secs() / CROSSFADE_SECS;
+        for (mut layer, sink) in layer_query.iter_mut() {
+            let target = if Some(layer.track) != current {
+                0.0
+            } else if layer.threshold == 0.0 {
+                1.0
+            } else {
+                ((intensity.0 - layer.threshold) / 0.15).clamp(0.0, 1.0)
+            };
+            layer.gain += (target - layer.gain).clamp(-step, step);
+            if let Some(sink) = sink {
+                sink.set_volume(layer.gain * volume.0 as f32 / 9.0);
+            }
+        }
+    }
+}
+
+mod people {
+    use super::GameState;
+    use bevy::prelude::*;
+    use rand::Rng;
+
+    // This plugin gives every person a look of their own: once their scene has loaded their
+    // texture is tinted one of a handful of colours, and some of them get a hat or a bag
+    pub fn people_plugin(app: &mut App) {
+        app.init_resource::<PersonVariants>()
+            .add_systems(Update, dress_people.run_if(in_state(GameState::Game)));
+    }
+
+    /// the person model is a 2.5 radius ball of a head sat on a body, this is the top of the head
+    const HEAD_TOP: f32 = 7.5;
+
+    #[derive(Clone, Copy, Debug)]
+    pub enum Hat {
+        Cap,
+        Stovepipe,
+        Cone,
+    }
+    /// the pool of looks people are drawn from
+    #[derive(Resource)]
+    pub struct PersonVariants {
+        /// colours that replace the base colour of a person's material, the texture still shows
+        /// through underneath
+        pub colors: Vec<Color>,
+        pub hats: Vec<Hat>,
+        /// colours hats and bags come in
+        pub accessory_colors: Vec<Color>,
+        /// odds of a person wearing a hat, from 0 to 1
+        pub hat_chance: f64,
+        /// odds of a person carrying a bag, from 0 to 1
+        pub bag_chance: f64,
+    }
+    impl Default for PersonVariants {
+        fn default() -> Self {
+            PersonVariants {
+                colors: vec![
+                    Color::WHITE,
+                    Color::srgb(1.0, 0.75, 0.7),
+                    Color::srgb(0.7, 0.85, 1.0),
+                    Color::srgb(0.75, 1.0, 0.7),
+                    Color::srgb(1.0, 0.95, 0.6),
+                    Color::srgb(0.85, 0.7, 1.0),
+                ],
+                hats: vec![Hat::Cap, Hat::Stovepipe, Hat::Cone],
+                accessory_colors: vec![
+                    Color::srgb(0.15, 0.15, 0.18),
+                    Color::srgb(0.7, 0.1, 0.1),
+                    Color::srgb(0.1, 0.3, 0.7),
+                    Color::srgb(0.9, 0.6, 0.1),
+                    Color::srgb(0.45, 0.3, 0.2),
+                ],
+                hat_chance: 0.5,
+                bag_chance: 0.35,
+            }
+        }
+    }
+    impl PersonVariants {
+        pub fn pick(&self, rng: &mut impl Rng) -> PersonAppearance {
+            let mut any_color = |colors: &[Color]| {
+                if colors.is_empty() {
+                    Color::WHITE
+                } else {
+                    colors[rng.gen_range(0..colors.len())]
+                }
+            };
+            let tint = any_color(&self.colors);
+            let hat_color = any_color(&self.accessory_colors);
+            let bag_color = any_color(&self.accessory_colors);
+            let hat = (!self.hats.is_empty() && rng.gen_bool(self.hat_chance))
+                .then(|| (self.hats[rng.gen_range(0..self.hats.len())], hat_color));
+            let bag = rng.gen_bool(self.bag_chance).then_some(bag_color);
+            PersonAppearance { tint, hat, bag }
+        }
+    }
+    /// how a person is dressed, applied once their scene has loaded
+    #[derive(Component, Clone, Debug)]
+    pub struct PersonAppearance {
+        tint: Color,
+        hat: Option<(Hat, Color)>,
+        bag: Option<Color>,
+    }
+    /// marks a person whose scene has already been patched
+    #[derive(Component)]
+    struct Dressed;
+
+    fn dress_people(
+        mut commands: Commands,
+        people_query: Query<(Entity, &PersonAppearance), Without<Dressed>>,
+        children_query: Query<&Children>,
+        material_query: Query<&MeshMaterial3d<StandardMaterial>>,
+        mut materials: ResMut<Assets<StandardMaterial>>,
+        mut meshes: ResMut<Assets<Mesh>>,
+    ) {
+        for (entity, appearance) in people_query.iter() {
+            // the scene's meshes only turn up a few frames after the person is spawned
+            let mut patched = false;
+            for descendant in children_query.iter_descendants(entity) {
+                let Ok(material) = material_query.get(descendant) else {
+                    continue;
+                };
+                let Some(mut tinted) = materials.get(&material.0).cloned() else {
+                    continue;
+                };
+                tinted.base_color = appearance.tint;
+                commands
+                    .entity(descendant)
+                    .insert(MeshMaterial3d(materials.add(tinted)));
+                patched = true;
+            }
+            if !patched {
+                continue;
+            }
+
+            commands.entity(entity).insert(Dressed);
+            if let Some((hat, color)) = appearance.hat {
+                let material = materials.add(color);
+                let pieces: Vec<(Mesh, f32)> = match hat {
+                    Hat::Cap => vec![(Cylinder::new(2.3, 0.8).into(), HEAD_TOP - 0.4)],
+                    Hat::Stovepipe => vec![
+                        (Cylinder::new(2.8, 0.2).into(), HEAD_TOP - 0.5),
+                        (Cylinder::new(1.7, 2.6).into(), HEAD_TOP + 0.7),
+                    ],
+                    Hat::Cone => vec![(Cone::new(2.2, 3.0).into(), HEAD_TOP + 1.0)],
+                };
+                commands.entity(entity).with_children(|parent| {
+                    for (mesh, height) in pieces {
+                        parent.spawn((
+                            Mesh3d(meshes.add(mesh)),
+                            MeshMaterial3d(material.clone()),
+                            Transform::from_xyz(0.0, height, 0.0),
+                        ));
+                    }
+                });
+            }
+            if let Some(color) = appearance.bag {
+                commands.entity(entity).with_children(|parent| {
+                    parent.spawn((
+                        Mesh3d(meshes.add(Cuboid::new(1.0, 2.0, 2.2))),
+                        MeshMaterial3d(materials.add(color)),
+                        Transform::from_xyz(2.7, 2.5, 0.0),
+                    ));
+                });
+            }
+        }
+    }
+}
+
+mod difficulty {
+    use super::{despawn_screen, Difficulty, GameMode, GameState};
+    use crate::audio::{Sound, SoundEvent};
+    use crate::game::{
+        CrashCause, Deliveries, DevCheats, PlayerModel, RobotBumped, RobotCrashed, RunRng,
+    };
+    use crate::people::PersonVariants;
+    use bevy::prelude::*;
+    use rand::Rng;
+    use std::f32::consts::TAU;
+
+    // This plugin makes a run harder the more parcels get delivered: deadlines shrink, drop-offs
+    // move further away, and pedestrians and delivery carts start getting in the robot's way.
+    // How quickly that happens depends on the Easy/Normal/Hard preset picked before the run
+    pub fn difficulty_plugin(app: &mut App) {
+        app.insert_resource(DifficultyCurve::new(Difficulty::Normal, 0))
+            .add_systems(
+                Update,
+                (
+                    ramp_difficulty,
+                    spawn_obstacles,
+                    move_obstacles,
+                    obstacle_collisions,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Game)),
+            )
+            // obstacles stay where they are through a pause, like the drop-off
+            .add_systems(OnEnter(GameState::Menu), despawn_screen::<Obstacle>)
+            .add_systems(OnEnter(GameState::Lose), despawn_screen::<Obstacle>);
+    }
+
+    /// how far out the pedestrians walk, just inside the ring of pickup spots
+    const SIDEWALK_RADIUS: f32 = 178.0;
+    /// how close the robot can get to an obstacle before it counts as running into it
+    const PEDESTRIAN_RADIUS: f32 = 8.0;
+    const CART_RADIUS: f32 = 10.0;
+    const PEDESTRIAN_SPEED: f32 = 8.0;
+    const CART_SPEED: f32 = 30.0;
+    /// the avenues through the middle of the city the carts run along, kept clear of the start
+    const CART_ROUTES: [(Vec2, Vec2); 4] = [
+        (Vec2::new(0.0, 40.0), Vec2::new(0.0, 150.0)),
+        (Vec2::new(0.0, -40.0), Vec2::new(0.0, -150.0)),
+        (Vec2::new(40.0, 0.0), Vec2::new(150.0, 0.0)),
+        (Vec2::new(-40.0, 0.0), Vec2::new(-150.0, 0.0)),
+    ];
+
+    /// the numbers a preset starts a run with and works up to
+    struct DifficultyPreset {
+        /// seconds the first parcel has to be delivered in, and the least it comes down to
+        start_deadline: f32,
+        end_deadline: f32,
+        /// shortest drive between a pickup and its drop-off, early and late in the run
+        start_distance: f32,
+        end_distance: f32,
+        start_pedestrians: usize,
+        end_pedestrians: usize,
+        start_carts: usize,
+        end_carts: usize,
+        /// deliveries it takes to go from the start of the curve to the end
+        ramp_deliveries: u32,
+    }
+    impl Difficulty {
+        fn preset(self) -> DifficultyPreset {
+            match self {
+                Difficulty::Easy => DifficultyPreset {
+                    start_deadline: 40.0,
+                    end_deadline: 25.0,
+                    start_distance: 60.0,
+                    end_distance: 200.0,
+                    start_pedestrians: 0,
+                    end_pedestrians: 3,
+                    start_carts: 0,
+                    end_carts: 1,
+                    ramp_deliveries: 20,
+                },
+                Difficulty::Normal => DifficultyPreset {
+                    start_deadline: 30.0,
+                    end_deadline: 15.0,
+                    start_distance: 80.0,
+                    end_distance: 260.0,
+                    start_pedestrians: 1,
+                    end_pedestrians: 5,
+                    start_carts: 0,
+                    end_carts: 2,
+                    ramp_deliveries: 15,
+                },
+                Difficulty::Hard => DifficultyPreset {
+                    start_deadline: 25.0,
+                    end_deadline: 10.0,
+                    start_distance: 120.0,
+                    end_distance: 300.0,
+                    start_pedestrians: 2,
+                    end_pedestrians: 8,
+                    start_carts: 1,
+                    end_carts: 4,
+                    ramp_deliveries: 10,
+                },
+            }
+        }
+    }
+    /// where the run is on its preset's difficulty curve
+    #[derive(Resource)]
+    pub struct DifficultyCurve {
+        preset: DifficultyPreset,
+        /// 0 at the start of a run, 1 once the preset is as hard as it gets
+        progress: f32,
+    }
+    impl DifficultyCurve {
+        fn new(difficulty: Difficulty, deliveries: u32) -> Self {
+            let preset = difficulty.preset();
+            let progress = (deliveries as f32 / preset.ramp_deliveries as f32).min(1.0);
+            DifficultyCurve { preset, progress }
+        }
+        fn ramp(&self, start: f32, end: f32) -> f32 {
+            start + (end - start) * self.progress
+        }
+        /// seconds the sender gives for the next parcel to arrive
+        pub fn deadline_secs(&self) -> f32 {
+            self.ramp(self.preset.start_deadline, self.preset.end_deadline)
+        }
+        /// the shortest drive there can be from where a parcel is picked up to its drop-off
+        pub fn min_distance(&self) -> f32 {
+            self.ramp(self.preset.start_distance, self.preset.end_distance)
+        }
+        fn pedestrians(&self) -> usize {
+            let preset = &self.preset;
+            self.ramp(
+                preset.start_pedestrians as f32,
+                preset.end_pedestrians as f32,
+            )
+            .round() as usize
+        }
+        fn carts(&self) -> usize {
+            let preset = &self.preset;
+            self.ramp(preset.start_carts as f32, preset.end_carts as f32)
+                .round() as usize
+        }
+    }
+
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    enum ObstacleKind {
+        Pedestrian,
+        Cart,
+    }
+    /// something that goes back and forth along a path and ends the run if the robot hits it
+    #[derive(Component)]
+    pub struct Obstacle {
+        kind: ObstacleKind,
+        from: Vec3,
+        to: Vec3,
+        /// how far along the path it is, from 0 up to 2 and back round, past 1 it's heading back
+        travel: f32,
+    }
+    impl Obstacle {
+        fn speed(&self) -> f32 {
+            match self.kind {
+                ObstacleKind::Pedestrian => PEDESTRIAN_SPEED,
+                ObstacleKind::Cart => CART_SPEED,
+            }
+        }
+        pub fn radius(&self) -> f32 {
+            match self.kind {
+                ObstacleKind::Pedestrian => PEDESTRIAN_RADIUS,
+                ObstacleKind::Cart => CART_RADIUS,
+            }
+        }
+        pub fn label(&self) -> &'static str {
+            match self.kind {
+                ObstacleKind::Pedestrian => "pedestrian",
+                ObstacleKind::Cart => "cart",
+            }
+        }
+    }
+
+    fn ramp_difficulty(
+        difficulty: Res<Difficulty>,
+        deliveries: Res<Deliveries>,
+        mut curve: ResMut<DifficultyCurve>,
+    ) {
+        if difficulty.is_changed() || deliveries.is_changed() {
+            *curve = DifficultyCurve::new(*difficulty, deliveries.0);
+        }
+    }
+    /// tops up the pedestrians and carts to however many the curve calls for
+    fn spawn_obstacles(
+        mut commands: Commands,
+        asset_server: Res<AssetServer>,
+        curve: Res<DifficultyCurve>,
+        obstacle_query: Query<&Obstacle>,
+        (mut run_rng, person_variants): (ResMut<RunRng>, Res<PersonVariants>),
+        mut meshes: ResMut<Assets<Mesh>>,
+        mut materials: ResMut<Assets<StandardMaterial>>,
+    ) {
+        let count = |kind| {
+            obstacle_query
+                .iter()
+                .filter(|obstacle| obstacle.kind == kind)
+                .count()
+        };
+        for _ in count(ObstacleKind::Pedestrian)..curve.pedestrians() {
+            let start = run_rng.gen_range(0.0..TAU);
+            let end = start + run_rng.gen_range(0.2..0.45);
+            let from = Vec3::new(start.cos(), 0.0, start.sin()) * SIDEWALK_RADIUS;
+            let to = Vec3::new(end.cos(), 0.0, end.sin()) * SIDEWALK_RADIUS;
+            commands.spawn((
+                SceneRoot(asset_server.load("Models/person.glb#Scene0")),
+                Transform::from_translation(from.with_y(1.1)),
+                person_variants.pick(&mut **run_rng),
+                Obstacle {
+                    kind: ObstacleKind::Pedestrian,
+                    from,
+                    to,
+                    travel: 0.0,
+                },
+            ));
+        }
+        let carts = count(ObstacleKind::Cart);
+        if carts < curve.carts() {
+            let mesh = meshes.add(Cuboid::new(8.0, 6.0, 12.0));
+            let material = materials.add(Color::srgb(0.95, 0.55, 0.1));
+            for _ in carts..curve.carts() {
+                let (from, to) = CART_ROUTES[run_rng.gen_range(0..CART_ROUTES.len())];
+                let from = Vec3::new(from.x, 0.0, from.y);
+                let to = Vec3::new(to.x, 0.0, to.y);
+                commands.spawn((
+                    Mesh3d(mesh.clone()),
+                    MeshMaterial3d(material.clone()),
+                    Transform::from_translation(from.with_y(4.0)),
+                    Obstacle {
+                        kind: ObstacleKind::Cart,
+                        from,
+                        to,
+                        // start somewhere along the avenue so carts don't all bunch up
+                        travel: run_rng.gen_range(0.0..2.0),
+                    },
+                ));
+            }
+        }
+    }
+    fn move_obstacles(time: Res<Time>, mut obstacle_query: Query<(&mut Transform, &mut Obstacle)>) {
+        for (mut transform, mut obstacle) in obstacle_query.iter_mut() {
+            let length = obstacle.from.distance(obstacle.to).max(1.0);
+            obstacle.travel =
+                (obstacle.travel + obstacle.speed() * time.delta_secs() / length) % 2.0;
+            let (along, heading) = if obstacle.travel < 1.0 {
+                (obstacle.travel, obstacle.to - obstacle.from)
+            } else {
+                (2.0 - obstacle.travel, obstacle.from - obstacle.to)
+            };
+            let position = obstacle.from.lerp(obstacle.to, along);
+            transform.translation.x = position.x;
+            transform.translation.z = position.z;
+            transform.rotation = Quat::from_rotation_arc(Vec3::Z, heading.normalize_or(Vec3::Z));
+        }
+    }
+    fn obstacle_collisions(
+        mut game_state: ResMut<NextState<GameState>>,
+        (game_mode, cheats): (Res<GameMode>, Res<DevCheats>),
+        mut player_query: Query<&mut Transform, With<PlayerModel>>,
+        obstacle_query: Query<(&Transform, &Obstacle), Without<PlayerModel>>,
+        mut sound_events: EventWriter<SoundEvent>,
+        (mut bump_events, mut crash_events): (EventWriter<RobotBumped>, EventWriter<RobotCrashed>),
+    ) {
+        let Ok(mut player_transform) = player_query.get_single_mut() else {
+            return;
+        };
+        if cheats.noclip {
+            return;
+        }
+        for (transform, obstacle) in obstacle_query.iter() {
+            let offset = (player_transform.translation - transform.translation).with_y(0.0);
+            if offset.length() >= obstacle.radius() {
+                continue;
+            }
+            if !game_mode.crashes() || cheats.god {
+                // Zen just nudges the robot out of the way
+                let pushed =
+                    transform.translation + offset.normalize_or(Vec3::X) * obstacle.radius();
+                player_transform.translation.x = pushed.x;
+                player_transform.translation.z = pushed.z;
+                bump_events.send(RobotBumped);
+                continue;
+            }
+            let cause = match obstacle.kind {
+                ObstacleKind::Pedestrian => {
+                    sound_events.send(SoundEvent::at(Sound::CrashNearby, transform.translation));
+                    CrashCause::Pedestrian
+                }
+                ObstacleKind::Cart => CrashCause::Cart,
+            };
+            crash_events.send(RobotCrashed {
+                at: player_transform.translation,
+                cause,
+                building: None,
+            });
+            game_state.set(GameState::Lose);
+            return;
+        }
+    }
+}
+
+mod dispatch {
+    use super::GameState;
+    use crate::audio::{Sound, SoundEvent};
+    use crate::contracts::{Contract, ContractBoard};
+    use crate::game::{
+        spawn_light_blue_cube, spawn_person_model, BuildingModel, CheckPointCube, ParcelPickedUp,
+        PeopleModel, RunRng, BUILDING_CRASH_RADIUS, WALL_RADIUS,
+    };
+    use crate::parcels::ParcelKind;
+    use crate::people::PersonVariants;
+    use bevy::prelude::*;
+    use rand::Rng;
+    use std::{cmp::Reverse, collections::BinaryHeap, collections::VecDeque};
+
+    // This plugin decides where senders wait and where their parcels go, and puts them out for
+    // the contracts the player has taken on. It keeps a grid of the streets between the
+    // buildings so every spot it picks can actually be driven to, and it picks drop-offs by how
+    // long the drive is rather than how far apart they look on the map
+    pub fn dispatch_plugin(app: &mut App) {
+        app.init_resource::<DeliveryDispatcher>().add_systems(
+            Update,
+            (build_nav_grid, dispatch_jobs)
+                .chain()
+                .run_if(in_state(GameState::Game)),
+        );
+    }
+
+    /// every spot a sender can wait at or a parcel can be dropped off at
+    pub const DELIVERY_SPOTS: [Vec3; 16] = [
+        Vec3::new(165.0, 1.1, 3.0),
+        Vec3::new(145.0, 1.1, 110.0),
+        Vec3::new(145.0, 1.1, -100.0),
+        Vec3::new(95.0, 1.1, 155.0),
+        Vec3::new(95.0, 1.1, -150.0),
+        Vec3::new(45.0, 1.1, 180.0),
+        Vec3::new(50.0, 1.1, -180.0),
+        Vec3::new(0.0, 1.1, -190.0),
+        Vec3::new(0.0, 1.1, 190.0),
+        Vec3::new(-50.0, 1.1, 180.0),
+        Vec3::new(-45.0, 1.1, -180.0),
+        Vec3::new(-95.0, 1.1, 150.0),
+        Vec3::new(-95.0, 1.1, -155.0),
+        Vec3::new(-145.0, 1.1, 100.0),
+        Vec3::new(-145.0, 1.1, -110.0),
+        Vec3::new(-165.0, 1.1, 0.0),
+    ];
+    /// width of a grid cell, the robot itself is about two cells across
+    pub const CELL: f32 = 5.0;
+    /// the grid covers the island out to the wall in every direction
+    const GRID_HALF_EXTENT: f32 = 200.0;
+    const GRID_SIZE: usize = 80;
+    /// how close the robot has to get to pick up or drop off, so a spot this near is no trip
+    const HANDOVER_DISTANCE: f32 = 20.0;
+    /// how many of the last spots used are kept out of the draw
+    const RECENT_SPOTS: usize = 4;
+    /// the least drive between the robot and a new sender
+    const SENDER_MIN_DISTANCE: f32 = 50.0;
+    /// drop-offs are drawn from drives this much longer than the curve's minimum at most
+    const BAND_WIDTH: f32 = 150.0;
+    /// step costs for the route search, straight and diagonal, scaled up to stay whole numbers
+    const STRAIGHT_COST: u32 = 10;
+    const DIAGONAL_COST: u32 = 14;
+
+    /// which cells of the map the robot can drive through
+    #[derive(Resource)]
+    pub struct NavGrid {
+        blocked: Vec<bool>,
+    }
+    /// the streets the robot can still get down with an oversized parcel on board
+    #[derive(Resource, Deref)]
+    pub struct WideNavGrid(NavGrid);
+    impl NavGrid {
+        fn new(buildings: &[Vec2], clearance: f32) -> Self {
+            let blocked = (0..GRID_SIZE * GRID_SIZE)
+                .map(|index| {
+                    let center = Self::center(index).xz();
+                    center.length() > WALL_RADIUS - CELL
+                        || buildings
+                            .iter()
+                            .any(|building| building.distance(center) < clearance)
+                })
+                .collect();
+            NavGrid { blocked }
+        }
+        fn cell(position: Vec3) -> Option<usize> {
+            let column = ((position.x + GRID_HALF_EXTENT) / CELL).floor();
+            let row = ((position.z + GRID_HALF_EXTENT) / CELL).floor();
+            let size = GRID_SIZE as f32;
+            ((0.0..size).contains(&column) && (0.0..size).contains(&row))
+                .then(|| row as usize * GRID_SIZE + column as usize)
+        }
+        pub fn center(index: usize) -> Vec3 {
+            let column = (index % GRID_SIZE) as f32;
+            let row = (index / GRID_SIZE) as f32;
+            Vec3::new(
+                (column + 0.5) * CELL - GRID_HALF_EXTENT,
+                0.0,
+                (row + 0.5) * CELL - GRID_HALF_EXTENT,
+            )
+        }
+        /// whether the cell a position falls in can't be driven through
+        pub fn blocks(&self, position: Vec3) -> bool {
+            match Self::cell(position) {
+                Some(index) => self.blocked[index],
+                None => true,
+            }
+        }
+        /// the middle of every cell the robot can't drive through, inside the wall
+        pub fn blocked_cells(&self) -> impl Iterator<Item = Vec3> + '_ {
+            (0..GRID_SIZE * GRID_SIZE)
+                .filter(|&index| self.blocked[index])
+                .map(Self::center)
+                .filter(|center| center.length() <= WALL_RADIUS - CELL)
+        }
+        /// the nearest open cell to a position, for spots that sit right up against something
+        pub fn open_cell_near(&self, position: Vec3) -> Option<usize> {
+            let start = Self::cell(position)?;
+            if !self.blocked[start] {
+                return Some(start);
+            }
+            (0..GRID_SIZE * GRID_SIZE)
+                .filter(|&index| !self.blocked[index])
+                .map(|index| (index, Self::center(index).xz().distance(position.xz())))
+                .filter(|(_, distance)| *distance < HANDOVER_DISTANCE)
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|(index, _)| index)
+        }
+        fn neighbours(&self, index: usize) -> impl Iterator<Item = (usize, u32)> + '_ {
+            let column = (index % GRID_SIZE) as isize;
+            let row = (index / GRID_SIZE) as isize;
+            let open = move |column: isize, row: isize| {
+                let size = GRID_SIZE as isize;
+                (0..size).contains(&column)
+                    && (0..size).contains(&row)
+                    && !self.blocked[(row * size + column) as usize]
+            };
+            [
+                (-1, 0),
+                (1, 0),
+                (0, -1),
+                (0, 1),
+                (-1, -1),
+                (1, -1),
+                (-1, 1),
+                (1, 1),
+            ]
+            .into_iter()
+            .filter(move |&(dc, dr)| {
+                // diagonals can't cut the corner of a building
+                open(column + dc, row + dr) && open(column + dc, row) && open(column, row + dr)
+            })
+            .map(move |(dc, dr)| {
+                let cost = if dc != 0 && dr != 0 {
+                    DIAGONAL_COST
+                } else {
+                    STRAIGHT_COST
+                };
+                (
+                    (row + dr) as usize * GRID_SIZE + (column + dc) as usize,
+                    cost,
+                )
+            })
+        }
+        /// how far the robot has to drive from a position to each cell, None where it can't get
+        pub fn distances_from(&self, position: Vec3) -> Vec<Option<f32>> {
+            let mut costs = vec![u32::MAX; GRID_SIZE * GRID_SIZE];
+            let mut queue = BinaryHeap::new();
+            if let Some(start) = self.open_cell_near(position) {
+                costs[start] = 0;
+                queue.push(Reverse((0, start)));
+            }
+            while let Some(Reverse((cost, index))) = queue.pop() {
+                if cost > costs[index] {
+                    continue;
+                }
+                for (next, step) in self.neighbours(index) {
+                    if cost + step < costs[next] {
+                        costs[next] = cost + step;
+                        queue.push(Reverse((cost + step, next)));
+                    }
+                }
+            }
+            costs
+                .into_iter()
+                .map(|cost| (cost != u32::MAX).then(|| cost as f32 / STRAIGHT_COST as f32 * CELL))
+                .collect()
+        }
+    }
+
+    impl NavGrid {
+        /// the way from a position down to wherever `distances` was measured from, one point per
+        /// cell, empty when there's no way through
+        pub fn route(&self, distances: &[Option<f32>], from: Vec3) -> Vec<Vec3> {
+            let Some(mut cell) = self.open_cell_near(from) else {
+                return Vec::new();
+            };
+            let Some(mut distance) = distances[cell] else {
+                return Vec::new();
+            };
+            let mut route = vec![Self::center(cell)];
+            // every step goes to whichever neighbour is closest to the end, until there's none
+            while let Some((next, next_distance)) = self
+                .neighbours(cell)
+                .filter_map(|(next, _)| Some((next, distances[next]?)))
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .filter(|(_, next_distance)| *next_distance < distance)
+            {
+                cell = next;
+                distance = next_distance;
+                route.push(Self::center(cell));
+            }
+            route
+        }
+    }
+
+    /// keeps track of the spots used lately so the same few don't keep coming up, only jobs the
+    /// robot takes on count, not offers that come and go
+    #[derive(Resource, Default)]
+    pub struct DeliveryDispatcher {
+        recent: VecDeque<usize>,
+    }
+    impl DeliveryDispatcher {
+        /// plans a job starting from wherever the robot is: a spot for the sender to wait at and
+        /// one for the parcel to go to, with the drive between them
+        pub fn plan(
+            &self,
+            (grid, wide_grid): (&NavGrid, &WideNavGrid),
+            from: Vec3,
+            parcel: ParcelKind,
+            min_distance: f32,
+            rng: &mut impl Rng,
+        ) -> Option<(usize, usize, f32)> {
+            let (pickup, _) = self.choose(grid, from, SENDER_MIN_DISTANCE, f32::INFINITY, rng)?;
+            // an oversized parcel can only go somewhere the wide streets lead
+            let parcel_grid = if parcel == ParcelKind::Oversized {
+                wide_grid
+            } else {
+                grid
+            };
+            let (dropoff, distance) = self.choose(
+                parcel_grid,
+                DELIVERY_SPOTS[pickup],
+                min_distance,
+                min_distance + BAND_WIDTH,
+                rng,
+            )?;
+            Some((pickup, dropoff, distance))
+        }
+        /// picks a spot the robot can drive to from `from`, ideally with a drive between `min`
+        /// and `max` long, and never the spot it's standing at or one used lately
+        fn choose(
+            &self,
+            grid: &NavGrid,
+            from: Vec3,
+            min: f32,
+            max: f32,
+            rng: &mut impl Rng,
+        ) -> Option<(usize, f32)> {
+            let distances = grid.distances_from(from);
+            let reachable: Vec<(usize, f32)> = DELIVERY_SPOTS
+                .iter()
+                .enumerate()
+                .filter_map(|(spot, position)| {
+                    let distance = distances[grid.open_cell_near(*position)?]?;
+                    (distance > HANDOVER_DISTANCE).then_some((spot, distance))
+                })
+                .collect();
+            // if every reachable spot has been used lately, allow them again
+            let fresh: Vec<(usize, f32)> = reachable
+                .iter()
+                .filter(|(spot, _)| !self.recent.contains(spot))
+                .copied()
+                .collect();
+            let candidates = if fresh.is_empty() { reachable } else { fresh };
+
+            let in_band: Vec<(usize, f32)> = candidates
+                .iter()
+                .filter(|(_, distance)| (min..=max).contains(distance))
+                .copied()
+                .collect();
+            if in_band.is_empty() {
+                // nothing in the band, so take whichever drive comes closest to it
+                let band_gap = |distance: f32| (min - distance).max(distance - max);
+                candidates
+                    .iter()
+                    .min_by(|a, b| band_gap(a.1).total_cmp(&band_gap(b.1)))
+                    .copied()
+            } else {
+                Some(in_band[rng.gen_range(0..in_band.len())])
+            }
+        }
+        /// marks the spots of a job the robot has taken on as used
+        pub fn remember(&mut self, pickup: usize, dropoff: usize) {
+            self.recent.extend([pickup, dropoff]);
+            while self.recent.len() > RECENT_SPOTS {
+                self.recent.pop_front();
+            }
+        }
+    }
+
+    fn build_nav_grid(
+        mut commands: Commands,
+        added_query: Query<(), Added<BuildingModel>>,
+        building_query: Query<&Transform, With<BuildingModel>>,
+    ) {
+        if !added_query.is_empty() {
+            let buildings: Vec<Vec2> = building_query
+                .iter()
+                .map(|transform| transform.translation.xz())
+                .collect();
+            commands.insert_resource(NavGrid::new(&buildings, BUILDING_CRASH_RADIUS));
+            let wide_clearance = BUILDING_CRASH_RADIUS + ParcelKind::Oversized.extra_clearance();
+            commands.insert_resource(WideNavGrid(NavGrid::new(&buildings, wide_clearance)));
+        }
+    }
+    /// anything that shows a job is already out, a sender waiting or a parcel on board
+    type JobQuery<'w, 's> = Query<'w, 's, (), Or<(With<PeopleModel>, With<CheckPointCube>)>>;
+
+    /// puts out the sender for the next contract in the queue, and its drop-off once the robot
+    /// collects
+    fn dispatch_jobs(
+        mut commands: Commands,
+        asset_server: Res<AssetServer>,
+        mut board: ResMut<ContractBoard>,
+        (mut pickup_events, mut sound_events): (
+            EventReader<ParcelPickedUp>,
+            EventWriter<SoundEvent>,
+        ),
+        job_query: JobQuery,
+        (mut run_rng, person_variants): (ResMut<RunRng>, Res<PersonVariants>),
+    ) {
+        if let Some(pickup) = pickup_events.read().last() {
+            // the contract whose sender the robot just reached
+            let nearest = board
+                .queue
+                .iter()
+                .enumerate()
+                .min_by(|(_, a), (_, b)| {
+                    let distance =
+                        |contract: &Contract| DELIVERY_SPOTS[contract.pickup].distance(pickup.at);
+                    distance(a).total_cmp(&distance(b))
+                })
+                .map(|(index, _)| index);
+            let contract = nearest.and_then(|index| board.queue.remove(index));
+            // the sender has a word of warning for the fragile ones
+            let sound = match contract.as_ref().map(|contract| contract.parcel) {
+                Some(ParcelKind::Fragile) => Sound::FragilePickup,
+                _ => Sound::Pickup,
+            };
+            sound_events.send(SoundEvent::at(sound, pickup.at));
+            if let Some(contract) = contract {
+                spawn_light_blue_cube(
+                    &mut commands,
+                    &asset_server,
+                    DELIVERY_SPOTS[contract.dropoff],
+                    contract.parcel,
+                    contract.deadline_secs,
+                );
+            }
+            return;
+        }
+        if !job_query.is_empty() {
+            return;
+        }
+        if let Some(contract) = board.queue.front() {
+            let appearance = person_variants.pick(&mut **run_rng);
+            spawn_person_model(
+                &mut commands,
+                &asset_server,
+                DELIVERY_SPOTS[contract.pickup],
+                appearance,
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use rand::{rngs::StdRng, SeedableRng};
+
+        fn open_grid() -> NavGrid {
+            NavGrid::new(&[], BUILDING_CRASH_RADIUS)
+        }
+        fn both_grids(buildings: &[Vec2]) -> (NavGrid, WideNavGrid) {
+            let wide_clearance = BUILDING_CRASH_RADIUS + ParcelKind::Oversized.extra_clearance();
+            (
+                NavGrid::new(buildings, BUILDING_CRASH_RADIUS),
+                WideNavGrid(NavGrid::new(buildings, wide_clearance)),
+            )
+        }
+        fn drive(grid: &NavGrid, from: Vec3, to: Vec3) -> Option<f32> {
+            grid.distances_from(from)[grid.open_cell_near(to)?]
+        }
+
+        #[test]
+        fn buildings_and_the_wall_are_blocked() {
+            let grid = NavGrid::new(&[Vec2::ZERO], BUILDING_CRASH_RADIUS);
+            assert!(grid.blocks(Vec3::ZERO));
+            assert!(grid.blocks(Vec3::new(BUILDING_CRASH_RADIUS - CELL, 0.0, 0.0)));
+            assert!(!grid.blocks(Vec3::new(BUILDING_CRASH_RADIUS + CELL, 0.0, 0.0)));
+            assert!(grid.blocks(Vec3::new(WALL_RADIUS, 0.0, 0.0)));
+            assert!(grid.blocks(Vec3::new(0.0, 0.0, -2.0 * WALL_RADIUS)));
+            assert!(grid
+                .blocked_cells()
+                .all(|cell| cell.length() < BUILDING_CRASH_RADIUS));
+        }
+        #[test]
+        fn blocked_cells_cant_be_reached() {
+            let grid = NavGrid::new(&[Vec2::ZERO], BUILDING_CRASH_RADIUS);
+            let distances = grid.distances_from(Vec3::new(-60.0, 0.0, 0.0));
+            let middle = NavGrid::cell(Vec3::ZERO).unwrap();
+            assert_eq!(distances[middle], None);
+        }
+        #[test]
+        fn route_goes_around_a_building() {
+            let grid = NavGrid::new(&[Vec2::ZERO], BUILDING_CRASH_RADIUS);
+            let from = Vec3::new(-40.0, 0.0, 0.0);
+            let to = Vec3::new(40.0, 0.0, 0.0);
+            let distances = grid.distances_from(to);
+            let route = grid.route(&distances, from);
+
+            assert!(route.len() > 2);
+            assert!(route.first().unwrap().distance(from) < CELL);
+            assert!(route.last().unwrap().distance(to) < CELL);
+            assert!(route.iter().all(|point| !grid.blocks(*point)));
+            // every step is to a neighbouring cell
+            assert!(route
+                .windows(2)
+                .all(|pair| pair[0].distance(pair[1]) < 1.5 * CELL));
+            // the drive is longer than the straight line through the building
+            let driven = drive(&grid, from, to).unwrap();
+            assert!(driven > from.distance(to) + CELL);
+        }
+        #[test]
+        fn no_route_out_of_a_building() {
+            let grid = NavGrid::new(&[Vec2::ZERO], 3.0 * BUILDING_CRASH_RADIUS);
+            let distances = grid.distances_from(Vec3::new(120.0, 0.0, 0.0));
+            assert!(grid.route(&distances, Vec3::ZERO).is_empty());
+        }
+        #[test]
+        fn an_open_drive_is_about_the_straight_line() {
+            let grid = open_grid();
+            let from = Vec3::new(-50.0, 0.0, 0.0);
+            let to = Vec3::new(50.0, 0.0, 0.0);
+            let driven = drive(&grid, from, to).unwrap();
+            assert!((driven - 100.0).abs() <= CELL);
+        }
+
+        #[test]
+        fn plan_measures_the_drop_off_from_the_sender() {
+            let (grid, wide_grid) = both_grids(&[Vec2::ZERO]);
+            let dispatcher = DeliveryDispatcher::default();
+            let from = Vec3::new(0.0, 0.0, 60.0);
+            for seed in 0..20 {
+                let mut rng = StdRng::seed_from_u64(seed);
+                let (pickup, dropoff, distance) = dispatcher
+                    .plan(
+                        (&grid, &wide_grid),
+                        from,
+                        ParcelKind::Standard,
+                        100.0,
+                        &mut rng,
+                    )
+                    .unwrap();
+                assert_ne!(pickup, dropoff);
+                assert!(drive(&grid, from, DELIVERY_SPOTS[pickup]).unwrap() >= SENDER_MIN_DISTANCE);
+                let expected = drive(&grid, DELIVERY_SPOTS[pickup], DELIVERY_SPOTS[dropoff]);
+                assert_eq!(Some(distance), expected);
+                assert!((100.0..=100.0 + BAND_WIDTH).contains(&distance));
+            }
+        }
+        #[test]
+        fn plan_is_the_same_for_the_same_seed() {
+            let (grid, wide_grid) = both_grids(&[]);
+            let dispatcher = DeliveryDispatcher::default();
+            let plan = |seed| {
+                dispatcher.plan(
+                    (&grid, &wide_grid),
+                    Vec3::ZERO,
+                    ParcelKind::Fragile,
+                    120.0,
+                    &mut StdRng::seed_from_u64(seed),
+                )
+            };
+            assert_eq!(plan(7), plan(7));
+        }
+        #[test]
+        fn recent_spots_are_left_out() {
+            let grid = open_grid();
+            let mut dispatcher = DeliveryDispatcher::default();
+            dispatcher.remember(0, 1);
+            dispatcher.remember(2, 3);
+            for seed in 0..50 {
+                let mut rng = StdRng::seed_from_u64(seed);
+                let (spot, _) = dispatcher
+                    .choose(&grid, Vec3::ZERO, 0.0, f32::INFINITY, &mut rng)
+                    .unwrap();
+                assert!(spot > 3, "spot {spot} was used lately");
+            }
+        }
+        #[test]
+        fn the_oldest_spots_are_forgotten_first() {
+            let mut dispatcher = DeliveryDispatcher::default();
+            dispatcher.remember(0, 1);
+            dispatcher.remember(2, 3);
+            dispatcher.remember(4, 5);
+            assert_eq!(dispatcher.recent, [2, 3, 4, 5]);
+        }
+        #[test]
+        fn spots_come_back_once_all_were_used() {
+            let grid = open_grid();
+            let dispatcher = DeliveryDispatcher {
+                recent: (0..DELIVERY_SPOTS.len()).collect(),
+            };
+            let mut rng = StdRng::seed_from_u64(1);
+            assert!(dispatcher
+                .choose(&grid, Vec3::ZERO, 0.0, f32::INFINITY, &mut rng)
+                .is_some());
+        }
+        #[test]
+        fn out_of_band_takes_the_closest_drive() {
+            let grid = open_grid();
+            let dispatcher = DeliveryDispatcher::default();
+            let from = DELIVERY_SPOTS[0];
+            let mut rng = StdRng::seed_from_u64(3);
+            let (spot, distance) = dispatcher
+                .choose(&grid, from, 10_000.0, f32::INFINITY, &mut rng)
+                .unwrap();
+            let longest = (0..DELIVERY_SPOTS.len())
+                .filter_map(|spot| drive(&grid, from, DELIVERY_SPOTS[spot]))
+                .fold(0.0, f32::max);
+            assert_eq!(distance, longest);
+            assert_ne!(spot, 0);
+        }
+    }
+}
+
+mod contracts {
+    use super::{despawn_screen, GameState, TXT_CLR};
+    use crate::console::{AppConsoleExt, ConsoleCommand};
+    use crate::difficulty::DifficultyCurve;
+    use crate::dispatch::{DeliveryDispatcher, NavGrid, WideNavGrid, DELIVERY_SPOTS};
+    use crate::game::{PlayerModel, RunRng};
+    use crate::parcels::ParcelKind;
+    use crate::upgrades::Progress;
+    use bevy::{input::ButtonInput, prelude::*};
+    use std::collections::VecDeque;
+
+    // This plugin runs the contract board. Rather than being handed one sender after another,
+    // the player picks jobs off the board with J and the number keys. Accepted contracts wait
+    // in a queue and are worked through in order, while offers that get declined or sit too
+    // long are swapped for fresh ones
+    pub fn contracts_plugin(app: &mut App) {
+        app.init_resource::<ContractBoard>()
+            .add_console_command(SpawnSenderCommand)
+            .add_systems(OnEnter(GameState::Game), board_setup)
+            .add_systems(
+                Update,
+                (refill_board, use_board, update_board)
+                    .chain()
+                    .run_if(in_state(GameState::Game)),
+            )
+            .add_systems(OnExit(GameState::Game), despawn_screen::<OnContractBoard>)
+            .add_systems(OnEnter(GameState::Menu), clear_board)
+            .add_systems(OnEnter(GameState::Lose), clear_board);
+    }
+
+    /// how many offers are up on the board at once
+    const BOARD_SIZE: usize = 4;
+    /// how long an offer stays up before it's replaced
+    const OFFER_SECS: f32 = 45.0;
+    /// the keys that pick an offer, one for each line of the board
+    const OFFER_KEYS: [KeyCode; BOARD_SIZE] = [
+        KeyCode::Digit1,
+        KeyCode::Digit2,
+        KeyCode::Digit3,
+        KeyCode::Digit4,
+    ];
+
+    /// a job on the board: pick a parcel up from one spot and get it to another in time
+    #[derive(Clone, Debug)]
+    pub struct Contract {
+        pub pickup: usize,
+        pub dropoff: usize,
+        pub parcel: ParcelKind,
+        pub deadline_secs: f32,
+        /// the drive from the pickup to the drop-off
+        pub distance: f32,
+        /// points it pays if it's delivered on time in daylight
+        pub payout: usize,
+        /// counts down to the offer coming off the board if nobody takes it, only while a run is
+        /// being played
+        expires: Timer,
+    }
+    /// the offers up for grabs and the contracts taken on, front of the queue first
+    #[derive(Resource, Default)]
+    pub struct ContractBoard {
+        pub offers: Vec<Contract>,
+        pub queue: VecDeque<Contract>,
+        pub open: bool,
+    }
+
+    #[derive(Component)]
+    struct OnContractBoard;
+    #[derive(Component)]
+    struct BoardPanel;
+    #[derive(Component)]
+    struct QueueText;
+
+    /// which way a spot lies from the middle of the island, to name it on the board
+    fn spot_name(spot: usize) -> &'static str {
+        const NAMES: [&str; 8] = [
+            "East",
+            "South-east",
+            "South",
+            "South-west",
+            "West",
+            "North-west",
+            "North",
+            "North-east",
+        ];
+        let position = DELIVERY_SPOTS[spot];
+        let eighths = (position.z.atan2(position.x) / std::f32::consts::FRAC_PI_4).round();
+        NAMES[eighths.rem_euclid(8.0) as usize]
+    }
+    fn describe(contract: &Contract) -> String {
+        format!(
+            "{} from {} to {}, {}m, {}s, pays {}",
+            contract.parcel.label(),
+            spot_name(contract.pickup),
+            spot_name(contract.dropoff),
+            contract.distance.round(),
+            contract.deadline_secs.round(),
+            contract.payout,
+        )
+    }
+
+    fn board_setup(mut commands: Commands) {
+        commands.spawn((
+            QueueText,
+            OnContractBoard,
+            Text::new(""),
+            TextFont {
+                font_size: 24.0,
+                ..default()
+            },
+            TextColor(TXT_CLR),
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(90.0),
+                ..default()
+            },
+        ));
+        commands.spawn((
+            BoardPanel,
+            OnContractBoard,
+            Text::new(""),
+            TextFont {
+                font_size: 24.0,
+                ..default()
+            },
+            TextColor(TXT_CLR),
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(130.0),
+                left: Val::Px(10.0),
+                padding: UiRect::all(Val::Px(12.0)),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.75)),
+            Visibility::Hidden,
+        ));
+    }
+    /// takes expired offers down and fills the board back up
+    fn refill_board(
+        mut board: ResMut<ContractBoard>,
+        dispatcher: Res<DeliveryDispatcher>,
+        (grid, wide_grid): (Option<Res<NavGrid>>, Option<Res<WideNavGrid>>),
+        player_query: Query<&Transform, With<PlayerModel>>,
+        (curve, time, mut run_rng): (Res<DifficultyCurve>, Res<Time>, ResMut<RunRng>),
+    ) {
+        for offer in board.offers.iter_mut() {
+            offer.expires.tick(time.delta());
+        }
+        board.offers.retain(|offer| !offer.expires.finished());
+        let (Some(grid), Some(wide_grid)) = (grid, wide_grid) else {
+            return;
+        };
+        let from = player_query
+            .get_single()
+            .map(|transform| transform.translation)
+            .unwrap_or_default();
+        while board.offers.len() < BOARD_SIZE {
+            let parcel = ParcelKind::draw(&mut **run_rng);
+            let Some((pickup, dropoff, distance)) = dispatcher.plan(
+                (&grid, &wide_grid),
+                from,
+                parcel,
+                curve.min_distance(),
+                &mut **run_rng,
+            ) else {
+                return;
+            };
+            board.offers.push(Contract {
+                pickup,
+                dropoff,
+                parcel,
+                deadline_secs: curve.deadline_secs() * parcel.deadline_scale(),
+                distance,
+                // a delivery made on time in daylight is worth two points before the parcel's cut
+                payout: parcel.pay(2),
+                expires: Timer::from_seconds(OFFER_SECS, TimerMode::Once),
+            });
+        }
+    }
+    /// `spawn sender` puts a new contract at the front of the queue, even past the cargo limit,
+    /// so its sender turns up as soon as the robot is free
+    struct SpawnSenderCommand;
+    impl ConsoleCommand for SpawnSenderCommand {
+        fn name(&self) -> &'static str {
+            "spawn"
+        }
+        fn usage(&self) -> &'static str {
+            "sender"
+        }
+        fn run(&self, args: &[&str], world: &mut World) -> Result<String, String> {
+            if args != ["sender"] {
+                return Err("only senders can be spawned".to_string());
+            }
+            world
+                .run_system_cached(spawn_sender)
+                .map_err(|error| error.to_string())?
+        }
+    }
+    fn spawn_sender(
+        mut board: ResMut<ContractBoard>,
+        mut dispatcher: ResMut<DeliveryDispatcher>,
+        (grid, wide_grid): (Option<Res<NavGrid>>, Option<Res<WideNavGrid>>),
+        player_query: Query<&Transform, With<PlayerModel>>,
+        (curve, mut run_rng): (Res<DifficultyCurve>, ResMut<RunRng>),
+    ) -> Result<String, String> {
+        let (Some(grid), Some(wide_grid)) = (grid, wide_grid) else {
+            return Err("the streets haven't been mapped yet".to_string());
+        };
+        let from = player_query
+            .get_single()
+            .map(|transform| transform.translation)
+            .unwrap_or_default();
+        let parcel = ParcelKind::draw(&mut **run_rng);
+        let (pickup, dropoff, distance) = dispatcher
+            .plan(
+                (&grid, &wide_grid),
+                from,
+                parcel,
+                curve.min_distance(),
+                &mut **run_rng,
+            )
+            .ok_or("nowhere to put a sender".to_string())?;
+        dispatcher.remember(pickup, dropoff);
+        board.queue.push_front(Contract {
+            pickup,
+            dropoff,
+            parcel,
+            deadline_secs: curve.deadline_secs() * parcel.deadline_scale(),
+            distance,
+            payout: parcel.pay(2),
+            // it goes straight into the queue, so it never comes off the board
+            expires: Timer::from_seconds(OFFER_SECS, TimerMode::Once),
+        });
+        Ok(format!(
+            "{} sender waiting to the {}",
+            parcel.label(),
+            spot_name(pickup)
+        ))
+    }
+    /// J opens and closes the board, a number key takes that offer on and shift with it turns
+    /// the offer down
+    fn use_board(
+        keyboard_input: Res<ButtonInput<KeyCode>>,
+        mut board: ResMut<ContractBoard>,
+        mut dispatcher: ResMut<DeliveryDispatcher>,
+        progress: Res<Progress>,
+    ) {
+        if keyboard_input.just_pressed(KeyCode::KeyJ) {
+            board.open = !board.open;
+        }
+        if !board.open {
+            return;
+        }
+        let Some(index) = OFFER_KEYS
+            .iter()
+            .position(|key| keyboard_input.just_pressed(*key))
+            .filter(|index| *index < board.offers.len())
+        else {
+            return;
+        };
+        let declining = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+        if declining {
+            board.offers.remove(index);
+        } else if board.queue.len() < progress.cargo_slots() {
+            let contract = board.offers.remove(index);
+            dispatcher.remember(contract.pickup, contract.dropoff);
+            board.queue.push_back(contract);
+        }
+    }
+    fn update_board(
+        board: Res<ContractBoard>,
+        progress: Res<Progress>,
+        mut panel_query: Query<(&mut Text, &mut Visibility), With<BoardPanel>>,
+        mut queue_query: Query<&mut Text, (With<QueueText>, Without<BoardPanel>)>,
+    ) {
+        let queue_label = match board.queue.front() {
+            Some(next) => format!(
+                "Next: {} ({} queued), J for the board",
+                describe(next),
+                board.queue.len()
+            ),
+            None => "No contracts, press J for the board".to_string(),
+        };
+        for mut text in queue_query.iter_mut() {
+            if text.0 != queue_label {
+                text.0 = queue_label.clone();
+            }
+        }
+
+        let Ok((mut text, mut visibility)) = panel_query.get_single_mut() else {
+            return;
+        };
+        *visibility = if board.open {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        if !board.open {
+            return;
+        }
+        let mut lines = vec!["Contract board".to_string()];
+        for (index, offer) in board.offers.iter().enumerate() {
+            let left = offer.expires.remaining_secs().ceil();
+            lines.push(format!(
+                "{}. {} (offer ends in {left}s)",
+                index + 1,
+                describe(offer)
+            ));
+        }
+        lines.push(format!(
+            "1-{BOARD_SIZE} accept, shift+1-{BOARD_SIZE} decline, queue {}/{}",
+            board.queue.len(),
+            progress.cargo_slots()
+        ));
+        let label = lines.join("\n");
+        if text.0 != label {
+            text.0 = label;
+        }
+    }
+    /// a finished run takes its contracts with it
+    fn clear_board(mut board: ResMut<ContractBoard>) {
+        *board = ContractBoard::default();
+    }
+}
+
+mod guidance {
+    use super::{GameState, RouteGuidance};
+    use crate::atmosphere::AtmosphereModel;
+    use crate::dispatch::{NavGrid, WideNavGrid};
+    use crate::game::{CheckPointCube, PeopleModel, PlayerModel};
+    use crate::parcels::ParcelKind;
+    use bevy::prelude::*;
+
+    // This plugin draws the way from the robot to its next stop on the ground: the waiting
+    // sender, or the drop-off once a parcel is on board. The route follows the streets on the
+    // dispatcher's grid and is worked out again as the robot moves
+    pub fn guidance_plugin(app: &mut App) {
+        app.init_gizmo_group::<RouteGizmos>()
+            .add_systems(Startup, configure_route_gizmos)
+            .add_systems(
+                Update,
+                draw_route
+                    .run_if(in_state(GameState::Game).and(resource_equals(RouteGuidance(true)))),
+            );
+    }
+
+    /// how high over the road the route floats, so it doesn't flicker into the ground
+    const ROUTE_HEIGHT: f32 = 1.5;
+    /// an arrow is drawn every this many cells along the route
+    const ARROW_SPACING: usize = 6;
+
+    #[derive(Default, Reflect, GizmoConfigGroup)]
+    struct RouteGizmos;
+
+    /// drive distances to the current stop, kept until the stop moves
+    struct RouteField {
+        objective: Vec3,
+        wide: bool,
+        distances: Vec<Option<f32>>,
+    }
+
+    fn configure_route_gizmos(mut config_store: ResMut<GizmoConfigStore>) {
+        let (config, _) = config_store.config_mut::<RouteGizmos>();
+        config.line_width = 6.0;
+    }
+    fn draw_route(
+        mut gizmos: Gizmos<RouteGizmos>,
+        (time, atmosphere): (Res<Time>, Option<Res<AtmosphereModel>>),
+        (grid, wide_grid): (Option<Res<NavGrid>>, Option<Res<WideNavGrid>>),
+        player_query: Query<&Transform, With<PlayerModel>>,
+        cube_query: Query<(&Transform, &CheckPointCube)>,
+        sender_query: Query<&Transform, (With<PeopleModel>, Without<PlayerModel>)>,
+        mut field: Local<Option<RouteField>>,
+    ) {
+        let (Some(grid), Some(wide_grid), Ok(player)) =
+            (grid, wide_grid, player_query.get_single())
+        else {
+            return;
+        };
+        // a parcel on board always comes first, otherwise head for the sender
+        let Some((objective, wide)) = cube_query
+            .iter()
+            .map(|(transform, cube)| {
+                (
+                    transform.translation,
+                    cube.parcel() == ParcelKind::Oversized,
+                )
+            })
+            .chain(
+                sender_query
+                    .iter()
+                    .map(|transform| (transform.translation, false)),
+            )
+            .next()
+        else {
+            return;
+        };
+        // oversized parcels only fit down the wide streets
+        let grid: &NavGrid = if wide { &wide_grid } else { &grid };
+        if wide_grid.is_changed()
+            || field
+                .as_ref()
+                .is_none_or(|field| field.objective != objective || field.wide != wide)
+        {
+            *field = Some(RouteField {
+                objective,
+                wide,
+                distances: grid.distances_from(objective),
+            });
+        }
+        let Some(field) = field.as_ref() else {
+            return;
+        };
+
+        let route: Vec<Vec3> = grid
+            .route(&field.distances, player.translation)
+            .into_iter()
+            .map(|point| point.with_y(ROUTE_HEIGHT))
+            // the route is lost in the fog just like everything else
+            .take_while(|point| {
+                atmosphere.as_ref().is_none_or(|atmosphere| {
+                    point.distance(player.translation) < atmosphere.visibility
+                })
+            })
+            .collect();
+        // a slow pulse so the route reads as a glow rather than paint on the road
+        let glow = 0.75 + 0.25 * (time.elapsed_secs() * 3.0).sin();
+        let color = Color::srgba(0.3, 1.0, 0.9, glow);
+        gizmos.linestrip(route.iter().copied(), color);
+        for pair in route.windows(ARROW_SPACING / 2).step_by(ARROW_SPACING) {
+            if let (Some(start), Some(end)) = (pair.first(), pair.last()) {
+                gizmos.arrow(*start, *end, color).with_tip_length(3.0);
+            }
+        }
+    }
+}
+
+mod parcels {
+    use super::{despawn_screen, GameState};
+    use crate::atmosphere::AtmosphereModel;
+    use crate::audio::{Sound, SoundEvent};
+    use crate::game::{
+        CheckPointCube, PlayerModel, PlayerMotion, RobotBumped, RobotCrashed, TopSpeed, ROBOT_TOP,
+    };
+    use bevy::prelude::*;
+    use rand::Rng;
+
+    // This plugin handles the different kinds of parcel a sender can hand over. The kind is
+    // drawn when the parcel is picked up, shows as a box on top of the robot, and changes how
+    // long the robot has, how it drives and how much the delivery pays
+    pub fn parcels_plugin(app: &mut App) {
+        app.add_systems(
+            Update,
+            (carry_parcel, break_fragile_parcels).run_if(in_state(GameState::Game)),
+        )
+        .add_systems(
+            OnEnter(GameState::Lose),
+            break_in_crash.before(despawn_screen::<CheckPointCube>),
+        );
+    }
+
+    /// a fragile parcel breaks when the robot goes faster than this share of its top speed, so
+    /// it has to be driven with a light foot, and a tailwind makes that harder
+    const FRAGILE_SPEED_MARGIN: f32 = 0.85;
+    /// or when the robot bumps into something going faster than this
+    const FRAGILE_HIT_SPEED: f32 = 15.0;
+
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+    pub enum ParcelKind {
+        Standard,
+        /// has to be there quickly, and pays double
+        Express,
+        /// breaks if the robot hits something or drives too fast
+        Fragile,
+        /// slows the robot down
+        Heavy,
+        /// too wide for the narrow gaps between buildings
+        Oversized,
+    }
+    impl ParcelKind {
+        /// how often each kind comes up, out of the total of the weights
+        const WEIGHTS: [(ParcelKind, u32); 5] = [
+            (ParcelKind::Standard, 40),
+            (ParcelKind::Express, 15),
+            (ParcelKind::Fragile, 15),
+            (ParcelKind::Heavy, 15),
+            (ParcelKind::Oversized, 15),
+        ];
+        pub fn draw(rng: &mut impl Rng) -> Self {
+            let total: u32 = Self::WEIGHTS.iter().map(|(_, weight)| weight).sum();
+            let mut roll = rng.gen_range(0..total);
+            for (kind, weight) in Self::WEIGHTS {
+                if roll < weight {
+                    return kind;
+                }
+                roll -= weight;
+            }
+            ParcelKind::Standard
+        }
+        pub fn label(self) -> &'static str {
+            match self {
+                ParcelKind::Standard => "Parcel",
+                ParcelKind::Express => "Express",
+                ParcelKind::Fragile => "Fragile",
+                ParcelKind::Heavy => "Heavy",
+                ParcelKind::Oversized => "Oversized",
+            }
+        }
+        /// multiplies the time the difficulty curve gives for a delivery
+        pub fn deadline_scale(self) -> f32 {
+            match self {
+                ParcelKind::Standard => 1.0,
+                ParcelKind::Express => 0.6,
+                ParcelKind::Fragile | ParcelKind::Oversized => 1.2,
+                ParcelKind::Heavy => 1.4,
+            }
+        }
+        /// the points a delivery of this kind is worth, given what a standard one would pay
+        pub fn pay(self, points: usize) -> usize {
+            let multiplier = match self {
+                ParcelKind::Standard => 1.0,
+                ParcelKind::Express => 2.0,
+                ParcelKind::Fragile | ParcelKind::Heavy | ParcelKind::Oversized => 1.5,
+            };
+            (points as f32 * multiplier).round() as usize
+        }
+        /// multiplies the robot's top speed while the parcel is on board
+        pub fn speed_scale(self) -> f32 {
+            match self {
+                ParcelKind::Heavy => 0.65,
+                _ => 1.0,
+            }
+        }
+        /// how much further the robot has to keep from buildings with the parcel on board
+        pub fn extra_clearance(self) -> f32 {
+            match self {
+                ParcelKind::Oversized => 6.0,
+                _ => 0.0,
+            }
+        }
+        /// the box that sits on the robot, and its colour
+        fn model(self) -> (Cuboid, Color) {
+            match self {
+                ParcelKind::Standard => (Cuboid::new(6.0, 4.0, 6.0), Color::srgb(0.65, 0.45, 0.25)),
+                ParcelKind::Express => (Cuboid::new(5.0, 3.0, 5.0), Color::srgb(0.9, 0.15, 0.1)),
+                ParcelKind::Fragile => (Cuboid::new(5.0, 5.0, 5.0), Color::srgb(0.95, 0.85, 0.9)),
+                ParcelKind::Heavy => (Cuboid::new(7.0, 6.0, 7.0), Color::srgb(0.3, 0.3, 0.35)),
+                ParcelKind::Oversized => {
+                    (Cuboid::new(18.0, 4.0, 8.0), Color::srgb(0.75, 0.6, 0.35))
+                }
+            }
+        }
+    }
+
+    /// the box on top of the robot for the parcel it's carrying
+    #[derive(Component)]
+    struct CarriedParcel(ParcelKind);
+
+    /// keeps the box on the robot in step with the parcel it's carrying, if any
+    fn carry_parcel(
+        mut commands: Commands,
+        mut meshes: ResMut<Assets<Mesh>>,
+        mut materials: ResMut<Assets<StandardMaterial>>,
+        cube_query: Query<&CheckPointCube>,
+        player_query: Query<Entity, With<PlayerModel>>,
+        carried_query: Query<(Entity, &CarriedParcel)>,
+    ) {
+        let parcel = cube_query.iter().next().map(CheckPointCube::parcel);
+        let mut carrying = false;
+        for (entity, carried) in carried_query.iter() {
+            if Some(carried.0) == parcel {
+                carrying = true;
+            } else {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        let (Some(parcel), Ok(player), false) = (parcel, player_query.get_single(), carrying)
+        else {
+            return;
+        };
+        let (shape, color) = parcel.model();
+        commands.entity(player).with_child((
+            Mesh3d(meshes.add(shape)),
+            MeshMaterial3d(materials.add(color)),
+            Transform::from_xyz(0.0, ROBOT_TOP + shape.half_size.y, 0.0),
+            CarriedParcel(parcel),
+        ));
+    }
+    /// a fragile parcel is lost if the robot hits something or goes too fast with it
+    fn break_fragile_parcels(
+        mut commands: Commands,
+        mut bump_events: EventReader<RobotBumped>,
+        cube_query: Query<(Entity, &CheckPointCube)>,
+        player_query: Query<(&Transform, &PlayerMotion), With<PlayerModel>>,
+        atmosphere: Res<AtmosphereModel>,
+        top_speed: TopSpeed,
+        mut sound_events: EventWriter<SoundEvent>,
+    ) {
+        let bumped = bump_events.read().count() > 0;
+        let Ok((transform, motion)) = player_query.get_single() else {
+            return;
+        };
+        let speed = (motion.velocity + atmosphere.wind).length();
+        let limit = FRAGILE_SPEED_MARGIN * top_speed.get();
+        let broken = speed > limit || (bumped && speed > FRAGILE_HIT_SPEED);
+        if !broken {
+            return;
+        }
+        for (entity, cube) in cube_query.iter() {
+            if cube.parcel() == ParcelKind::Fragile {
+                // with its drop-off gone the dispatcher sends a new sender
+                commands.entity(entity).despawn_recursive();
+                sound_events.send(SoundEvent::at(Sound::ParcelBroken, transform.translation));
+            }
+        }
+    }
+    /// a crash that ends the run breaks a fragile parcel along with it
+    fn break_in_crash(
+        mut commands: Commands,
+        mut crash_events: EventReader<RobotCrashed>,
+        cube_query: Query<(Entity, &CheckPointCube)>,
+        mut sound_events: EventWriter<SoundEvent>,
+    ) {
+        let Some(crash) = crash_events.read().last() else {
+            return;
+        };
+        for (entity, cube) in cube_query.iter() {
+            if cube.parcel() == ParcelKind::Fragile {
+                commands.entity(entity).despawn_recursive();
+                sound_events.send(SoundEvent::at(Sound::ParcelBroken, crash.at));
+            }
+        }
+    }
+}
+
+mod upgrades {
+    use super::{despawn_screen, GameState, TXT_CLR};
+    use crate::game::{keeps_run, DeliveryMade, LastRun, PlayerModel, PlayerMotion};
+    use crate::profiles::ActiveProfile;
+    use crate::settings::{read_key_values, write_key_values};
+    use bevy::prelude::*;
+
+    // This plugin keeps the player's coins and robot upgrades between runs. Every point scored
+    // in a run is banked as a coin when it ends, and coins buy levels of the upgrades on the
+    // Upgrades screen. It also runs the robot's battery, which drains while driving and gets
+    // topped up by each delivery
+    pub fn upgrades_plugin(app: &mut App) {
+        app.init_resource::<Progress>()
+            .init_resource::<Battery>()
+            .add_systems(OnEnter(GameState::Game), battery_setup)
+            .add_systems(
+                Update,
+                (
+                    (
+                        load_progress.run_if(resource_changed::<ActiveProfile>),
+                        bank_earnings.run_if(resource_exists_and_changed::<LastRun>.and(keeps_run)),
+                        save_progress.run_if(resource_changed::<Progress>),
+                    )
+                        .chain(),
+                    (drain_battery, update_battery_text).run_if(in_state(GameState::Game)),
+                ),
+            )
+            .add_systems(OnExit(GameState::Game), despawn_screen::<BatteryText>);
+    }
+
+    pub const PROGRESS_FILE: &str = "progress.cfg";
+    /// seconds of driving a battery without upgrades lasts, and how many more each level adds
+    const BATTERY_SECS: f32 = 90.0;
+    const BATTERY_SECS_PER_LEVEL: f32 = 30.0;
+    /// seconds of driving every delivery puts back in the battery
+    const DELIVERY_CHARGE_SECS: f32 = 30.0;
+    /// how fast the robot can still crawl along on a flat battery
+    const FLAT_BATTERY_SPEED: f32 = 0.4;
+
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+    pub enum Upgrade {
+        Speed,
+        Handling,
+        Battery,
+        Cargo,
+        Magnet,
+    }
+    /// every upgrade, in the order the shop lists them
+    pub const UPGRADES: [Upgrade; 5] = [
+        Upgrade::Speed,
+        Upgrade::Handling,
+        Upgrade::Battery,
+        Upgrade::Cargo,
+        Upgrade::Magnet,
+    ];
+    impl Upgrade {
+        pub const MAX_LEVEL: u32 = 5;
+        pub fn label(self) -> &'static str {
+            match self {
+                Upgrade::Speed => "Speed",
+                Upgrade::Handling => "Handling",
+                Upgrade::Battery => "Battery",
+                Upgrade::Cargo => "Cargo Slots",
+                Upgrade::Magnet => "Magnet",
+            }
+        }
+        /// the name the upgrade's level is saved under
+        pub fn key(self) -> &'static str {
+            match self {
+                Upgrade::Speed => "speed",
+                Upgrade::Handling => "handling",
+                Upgrade::Battery => "battery",
+                Upgrade::Cargo => "cargo",
+                Upgrade::Magnet => "magnet",
+            }
+        }
+    }
+
+    /// the coins banked so far and the level bought of each upgrade, in `UPGRADES` order
+    #[derive(Resource, Default)]
+    pub struct Progress {
+        pub coins: u32,
+        levels: [u32; UPGRADES.len()],
+    }
+    impl Progress {
+        pub fn level(&self, upgrade: Upgrade) -> u32 {
+            self.levels[upgrade as usize]
+        }
+        /// what the next level of an upgrade costs, None once it's maxed out
+        pub fn cost(&self, upgrade: Upgrade) -> Option<u32> {
+            let level = self.level(upgrade);
+            (level < Upgrade::MAX_LEVEL).then_some(25 * (level + 1))
+        }
+        /// buys the next level of an upgrade if there are the coins for it
+        pub fn buy(&mut self, upgrade: Upgrade) -> bool {
+            match self.cost(upgrade) {
+                Some(cost) if cost <= self.coins => {
+                    self.coins -= cost;
+                    self.levels[upgrade as usize] += 1;
+                    true
+                }
+                _ => false,
+            }
+        }
+        /// multiplies the robot's top speed
+        pub fn speed_scale(&self) -> f32 {
+            1.0 + 0.08 * self.level(Upgrade::Speed) as f32
+        }
+        /// multiplies how quickly the robot turns and gets up to speed
+        pub fn handling_scale(&self) -> f32 {
+            1.0 + 0.2 * self.level(Upgrade::Handling) as f32
+        }
+        /// seconds of driving a full battery lasts
+        pub fn battery_secs(&self) -> f32 {
+            BATTERY_SECS + BATTERY_SECS_PER_LEVEL * self.level(Upgrade::Battery) as f32
+        }
+        /// how many contracts can wait in the queue
+        pub fn cargo_slots(&self) -> usize {
+            3 + self.level(Upgrade::Cargo) as usize
+        }
+        /// how close the robot has to get to pick up or drop off a parcel
+        pub fn magnet_range(&self) -> f32 {
+            20.0 + 3.0 * self.level(Upgrade::Magnet) as f32
+        }
+    }
+
+    /// seconds of driving left in the robot's battery
+    #[derive(Resource)]
+    pub struct Battery {
+        charge: f32,
+        capacity: f32,
+    }
+    impl Default for Battery {
+        fn default() -> Self {
+            Battery::full(&Progress::default())
+        }
+    }
+    impl Battery {
+        pub fn full(progress: &Progress) -> Self {
+            Battery {
+                charge: progress.battery_secs(),
+                capacity: progress.battery_secs(),
+            }
+        }
+        /// multiplies the robot's top speed, which drops right off once the battery is flat
+        pub fn speed_scale(&self) -> f32 {
+            if self.charge > 0.0 {
+                1.0
+            } else {
+                FLAT_BATTERY_SPEED
+            }
+        }
+    }
+
+    #[derive(Component)]
+    struct BatteryText;
+
+    fn load_progress(mut progress: ResMut<Progress>, profile: Res<ActiveProfile>) {
+        let values = read_key_values(&profile.slot_path(PROGRESS_FILE));
+        let get = |key: &str| values.get(key).and_then(|value| value.parse::<u32>().ok());
+        progress.coins = get("coins").unwrap_or_default();
+        for upgrade in UPGRADES {
+            let level = get(&format!("upgrade.{}", upgrade.key())).unwrap_or_default();
+            progress.levels[upgrade as usize] = level.min(Upgrade::MAX_LEVEL);
+        }
+    }
+    fn save_progress(progress: Res<Progress>, profile: Res<ActiveProfile>) {
+        let keys: Vec<String> = UPGRADES
+            .iter()
+            .map(|upgrade| format!("upgrade.{}", upgrade.key()))
+            .collect();
+        let mut entries = vec![("coins", progress.coins.to_string())];
+        for (key, upgrade) in keys.iter().zip(UPGRADES) {
+            entries.push((key.as_str(), progress.level(upgrade).to_string()));
+        }
+        write_key_values(&profile.slot_path(PROGRESS_FILE), &entries);
+    }
+    /// a finished run's points go into the bank as coins
+    fn bank_earnings(last_run: Res<LastRun>, mut progress: ResMut<Progress>) {
+        progress.coins += last_run.score as u32;
+    }
+    fn battery_setup(mut commands: Commands) {
+        commands.spawn((
+            BatteryText,
+            Text::new(""),
+            TextFont {
+                font_size: 30.0,
+                ..default()
+            },
+            TextColor(TXT_CLR),
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(50.0),
+                right: Val::Px(10.0),
+                ..default()
+            },
+        ));
+    }
+    fn drain_battery(
+        time: Res<Time>,
+        mut battery: ResMut<Battery>,
+        mut delivery_events: EventReader<DeliveryMade>,
+        player_query: Query<&PlayerMotion, With<PlayerModel>>,
+    ) {
+        let delivered = delivery_events.read().count() as f32;
+        let moving = player_query
+            .iter()
+            .any(|motion| motion.velocity.length() > 1.0);
+        let mut charge = battery.charge + DELIVERY_CHARGE_SECS * delivered;
+        if moving {
+            charge -= time.delta_secs();
+        }
+        battery.charge = charge.clamp(0.0, battery.capacity);
+    }
+    fn update_battery_text(
+        battery: Res<Battery>,
+        mut text_query: Query<&mut Text, With<BatteryText>>,
+    ) {
+        let percent = (100.0 * battery.charge / battery.capacity).ceil();
+        let label = if battery.charge > 0.0 {
+            format!("Battery {percent}%")
+        } else {
+            "Battery flat!".to_string()
+        };
+        for mut text in text_query.iter_mut() {
+            if text.0 != label {
+                text.0 = label.clone();
+            }
+        }
+    }
+}
+
+mod garage {
+    use crate::game::{PlayerModel, ROBOT_TOP};
+    use crate::profiles::ActiveProfile;
+    use crate::settings::{read_key_values, write_key_values};
+    use bevy::{
+        gltf::GltfMaterialName,
+        prelude::*,
+        render::{
+            camera::RenderTarget,
+            render_asset::RenderAssetUsages,
+            render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages},
+        },
+    };
+
+    // This plugin lets the player dress up the robot in the garage: a paint colour, a decal, an
+    // antenna and a cargo box. The choices are applied to the robot's scene once it loads, both
+    // on the robot in the city and on the one turning in the garage's preview
+    pub fn garage_plugin(app: &mut App) {
+        app.insert_resource(RobotPaint::Factory)
+            .insert_resource(RobotDecal::None)
+            .insert_resource(RobotAntenna::None)
+            .insert_resource(RobotCargoBox::None)
+            .add_systems(
+                Update,
+                (
+                    (
+                        load_garage.run_if(resource_changed::<ActiveProfile>),
+                        save_garage.run_if(
+                            resource_changed::<RobotPaint>
+                                .or(resource_changed::<RobotDecal>)
+                                .or(resource_changed::<RobotAntenna>)
+                                .or(resource_changed::<RobotCargoBox>),
+                        ),
+                    )
+                        .chain(),
+                    (dress_robots, paint_robots).chain(),
+                    turn_preview,
+                ),
+            );
+    }
+
+    const GARAGE_FILE: &str = "garage.cfg";
+    /// somewhere well below the island where the preview robot is out of everyone else's sight
+    const PREVIEW_SPOT: Vec3 = Vec3::new(0.0, -2000.0, 0.0);
+    const PREVIEW_SIZE: u32 = 512;
+    /// how far the preview camera sits from the robot, and how fast it circles it
+    const PREVIEW_DISTANCE: f32 = 40.0;
+    const PREVIEW_TURN_SPEED: f32 = 0.6;
+    /// the material on the robot's body, the one the paint and decals go on
+    const BODY_MATERIAL: &str = "bot_main_tex";
+
+    #[derive(Resource, Debug, Component, PartialEq, Eq, Clone, Copy)]
+    pub enum RobotPaint {
+        Factory,
+        Red,
+        Blue,
+        Yellow,
+        Green,
+        Black,
+    }
+    impl RobotPaint {
+        pub const ALL: [Self; 6] = [
+            RobotPaint::Factory,
+            RobotPaint::Red,
+            RobotPaint::Blue,
+            RobotPaint::Yellow,
+            RobotPaint::Green,
+            RobotPaint::Black,
+        ];
+        pub fn label(self) -> &'static str {
+            match self {
+                RobotPaint::Factory => "Factory",
+                RobotPaint::Red => "Red",
+                RobotPaint::Blue => "Blue",
+                RobotPaint::Yellow => "Yellow",
+                RobotPaint::Green => "Green",
+                RobotPaint::Black => "Black",
+            }
+        }
+        /// the colour painted over the robot, None keeps the colours it came with
+        fn color(self) -> Option<Color> {
+            match self {
+                RobotPaint::Factory => None,
+                RobotPaint::Red => Some(Color::srgb(0.8, 0.12, 0.1)),
+                RobotPaint::Blue => Some(Color::srgb(0.12, 0.3, 0.85)),
+                RobotPaint::Yellow => Some(Color::srgb(0.95, 0.8, 0.15)),
+                RobotPaint::Green => Some(Color::srgb(0.15, 0.65, 0.25)),
+                RobotPaint::Black => Some(Color::srgb(0.08, 0.08, 0.1)),
+            }
+        }
+    }
+    #[derive(Resource, Debug, Component, PartialEq, Eq, Clone, Copy)]
+    pub enum RobotDecal {
+        None,
+        Stripe,
+        TwinStripes,
+        Badge,
+    }
+    impl RobotDecal {
+        pub const ALL: [Self; 4] = [
+            RobotDecal::None,
+            RobotDecal::Stripe,
+            RobotDecal::TwinStripes,
+            RobotDecal::Badge,
+        ];
+        pub fn label(self) -> &'static str {
+            match self {
+                RobotDecal::None => "None",
+                RobotDecal::Stripe => "Stripe",
+                RobotDecal::TwinStripes => "Twin",
+                RobotDecal::Badge => "Badge",
+            }
+        }
+    }
+    #[derive(Resource, Debug, Component, PartialEq, Eq, Clone, Copy)]
+    pub enum RobotAntenna {
+        None,
+        Stub,
+        Whip,
+    }
+    impl RobotAntenna {
+        pub const ALL: [Self; 3] = [RobotAntenna::None, RobotAntenna::Stub, RobotAntenna::Whip];
+        pub fn label(self) -> &'static str {
+            match self {
+                RobotAntenna::None => "None",
+                RobotAntenna::Stub => "Stub",
+                RobotAntenna::Whip => "Whip",
+            }
+        }
+    }
+    #[derive(Resource, Debug, Component, PartialEq, Eq, Clone, Copy)]
+    pub enum RobotCargoBox {
+        None,
+        Crate,
+        Mailbag,
+        Locker,
+    }
+    impl RobotCargoBox {
+        pub const ALL: [Self; 4] = [
+            RobotCargoBox::None,
+            RobotCargoBox::Crate,
+            RobotCargoBox::Mailbag,
+            RobotCargoBox::Locker,
+        ];
+        pub fn label(self) -> &'static str {
+            match self {
+                RobotCargoBox::None => "None",
+                RobotCargoBox::Crate => "Crate",
+                RobotCargoBox::Mailbag => "Mailbag",
+                RobotCargoBox::Locker => "Locker",
+            }
+        }
+    }
+
+    /// the camera, light and robot making up the garage preview
+    #[derive(Component)]
+    pub struct GaragePreview;
+    #[derive(Component)]
+    struct PreviewRobot;
+    #[derive(Component)]
+    struct PreviewCamera;
+    /// a robot whose scene has been patched, with its own copy of the body material and the
+    /// texture the body came with
+    #[derive(Component)]
+    struct Dressed {
+        body: Handle<StandardMaterial>,
+        texture: Option<Handle<Image>>,
+    }
+    /// the accessory meshes added to a robot
+    #[derive(Component)]
+    struct RobotAccessory;
+    /// the robots in the city and in the garage preview
+    type RobotQuery<'w, 's> = Query<
+        'w,
+        's,
+        (Entity, Option<&'static Dressed>),
+        Or<(With<PlayerModel>, With<PreviewRobot>)>,
+    >;
+    /// the meshes of a loaded scene, with the name each one's material has in the model
+    type NamedMaterialQuery<'w, 's> = Query<
+        'w,
+        's,
+        (
+            &'static MeshMaterial3d<StandardMaterial>,
+            &'static GltfMaterialName,
+        ),
+    >;
+
+    impl RobotDecal {
+        /// whether the decal covers a spot on the robot's top, measured across the body from -1
+        /// to 1 with +Z to the front
+        fn covers(self, spot: Vec2) -> bool {
+            match self {
+                RobotDecal::None => false,
+                RobotDecal::Stripe => spot.x.abs() < 0.2,
+                RobotDecal::TwinStripes => (spot.x.abs() - 0.4).abs() < 0.12,
+                RobotDecal::Badge => spot.distance(Vec2::new(0.0, 0.6)) < 0.44,
+            }
+        }
+    }
+    /// where the robot's top sits in its texture: the middle of it, and how far the texture
+    /// moves for each step across the body, laid in diagonally
+    const TOP_UV_CENTER: Vec2 = Vec2::new(0.2678, 0.138);
+    const TOP_UV_STEP: f32 = 0.0446;
+    const DECAL_COLOR: Color = Color::srgb(0.95, 0.95, 0.95);
+
+    /// the spot on the robot's top a point of its texture is painted on, None off the top
+    fn top_spot(uv: Vec2) -> Option<Vec2> {
+        let across = (TOP_UV_CENTER.x - uv.x) / TOP_UV_STEP;
+        let along = (uv.y - TOP_UV_CENTER.y) / TOP_UV_STEP;
+        let spot = Vec2::new(across + along, across - along) / 2.0;
+        // the top's corners are cut off
+        (spot.x.abs() <= 1.0 && spot.y.abs() <= 1.0 && spot.x.abs() + spot.y.abs() <= 1.5)
+            .then_some(spot)
+    }
+    /// the robot's texture with the paint and the decal worked into it
+    fn dress_texture(original: &Image, paint: RobotPaint, decal: RobotDecal) -> Image {
+        let mut image = original.clone();
+        let (width, height) = (image.width(), image.height());
+        for y in 0..height {
+            for x in 0..width {
+                let uv = Vec2::new(
+                    (x as f32 + 0.5) / width as f32,
+                    (y as f32 + 0.5) / height as f32,
+                );
+                let color = if top_spot(uv).is_some_and(|spot| decal.covers(spot)) {
+                    DECAL_COLOR
+                } else if let (Some(paint), Ok(texel)) =
+                    (paint.color(), original.get_color_at(x, y))
+                {
+                    // paint over the texture's shading rather than flatten it
+                    let (paint, texel) = (paint.to_linear(), texel.to_linear());
+                    LinearRgba::new(
+                        texel.red * paint.red,
+                        texel.green * paint.green,
+                        texel.blue * paint.blue,
+                        texel.alpha,
+                    )
+                    .into()
+                } else {
+                    continue;
+                };
+                // a texture in a format that can't be written keeps its own colours
+                let _ = image.set_color_at(x, y, color);
+            }
+        }
+        image
+    }
+
+    /// sets up a robot turning in front of a camera that draws into an image, for the garage
+    /// screen to show, and returns that image
+    pub fn spawn_preview(
+        commands: &mut Commands,
+        asset_server: &Res<AssetServer>,
+        images: &mut Assets<Image>,
+    ) -> Handle<Image> {
+        let size = Extent3d {
+            width: PREVIEW_SIZE,
+            height: PREVIEW_SIZE,
+            ..default()
+        };
+        let mut image = Image::new_fill(
+            size,
+            TextureDimension::D2,
+            &[0, 0, 0, 0],
+            TextureFormat::Bgra8UnormSrgb,
+            RenderAssetUsages::default(),
+        );
+        image.texture_descriptor.usage = TextureUsages::TEXTURE_BINDING
+            | TextureUsages::COPY_DST
+            | TextureUsages::RENDER_ATTACHMENT;
+        let image = images.add(image);
+
+        commands.spawn((
+            Camera3d::default(),
+            Camera {
+                target: RenderTarget::Image(image.clone()),
+                order: -1,
+                clear_color: ClearColorConfig::Custom(Color::srgb(0.12, 0.12, 0.14)),
+                ..default()
+            },
+            Transform::from_translation(PREVIEW_SPOT + Vec3::new(0.0, 15.0, PREVIEW_DISTANCE))
+                .looking_at(PREVIEW_SPOT + Vec3::Y * 6.0, Vec3::Y),
+            PreviewCamera,
+            GaragePreview,
+        ));
+        commands.spawn((
+            PointLight {
+                intensity: 20_000_000.0,
+                range: 200.0,
+                ..default()
+            },
+            Transform::from_translation(PREVIEW_SPOT + Vec3::new(20.0, 40.0, 30.0)),
+            GaragePreview,
+        ));
+        commands.spawn((
+            SceneRoot(asset_server.load("Models/bot_main.glb#Scene0")),
+            Transform::from_translation(PREVIEW_SPOT),
+            PreviewRobot,
+            GaragePreview,
+        ));
+        image
+    }
+    /// circles the preview camera round the robot like a turntable
+    fn turn_preview(time: Res<Time>, mut camera_query: Query<&mut Transform, With<PreviewCamera>>) {
+        for mut transform in camera_query.iter_mut() {
+            let angle = time.elapsed_secs() * PREVIEW_TURN_SPEED;
+            let offset = Vec3::new(
+                angle.sin() * PREVIEW_DISTANCE,
+                15.0,
+                angle.cos() * PREVIEW_DISTANCE,
+            );
+            *transform = Transform::from_translation(PREVIEW_SPOT + offset)
+                .looking_at(PREVIEW_SPOT + Vec3::Y * 6.0, Vec3::Y);
+        }
+    }
+    /// gives the robots their own body material and swaps their accessories, whenever one has
+    /// just loaded or the choices change
+    fn dress_robots(
+        mut commands: Commands,
+        robot_query: RobotQuery,
+        children_query: Query<&Children>,
+        (material_query, accessory_query): (NamedMaterialQuery, Query<(), With<RobotAccessory>>),
+        mut materials: ResMut<Assets<StandardMaterial>>,
+        mut meshes: ResMut<Assets<Mesh>>,
+        (paint, decal, antenna, cargo_box): (
+            Res<RobotPaint>,
+            Res<RobotDecal>,
+            Res<RobotAntenna>,
+            Res<RobotCargoBox>,
+        ),
+    ) {
+        let changed = paint.is_changed()
+            || decal.is_changed()
+            || antenna.is_changed()
+            || cargo_box.is_changed();
+        for (entity, dressed) in robot_query.iter() {
+            match dressed {
+                Some(_) if changed => {}
+                Some(_) => continue,
+                None => {
+                    // the scene's meshes only turn up a few frames after the robot is spawned,
+                    // and the body gets a copy of its material so the paint stays on this robot
+                    let Some((descendant, copy)) = children_query
+                        .iter_descendants(entity)
+                        .filter_map(|descendant| {
+                            let (material, name) = material_query.get(descendant).ok()?;
+                            (name.0 == BODY_MATERIAL).then_some((descendant, material))
+                        })
+                        .find_map(|(descendant, material)| {
+                            Some((descendant, materials.get(&material.0)?.clone()))
+                        })
+                    else {
+                        continue;
+                    };
+                    let texture = copy.base_color_texture.clone();
+                    let body = materials.add(copy);
+                    commands
+                        .entity(descendant)
+                        .insert(MeshMaterial3d(body.clone()));
+                    commands.entity(entity).insert(Dressed { body, texture });
+                }
+            }
+
+            for child in children_query.iter_descendants(entity) {
+                if accessory_query.contains(child) {
+                    commands.entity(child).despawn_recursive();
+                }
+            }
+            let dark = materials.add(Color::srgb(0.15, 0.15, 0.17));
+            let brown = materials.add(Color::srgb(0.5, 0.35, 0.2));
+            let mut pieces: Vec<(Mesh, Handle<StandardMaterial>, Vec3)> = Vec::new();
+            match *antenna {
+                RobotAntenna::None => {}
+                RobotAntenna::Stub => pieces.push((
+                    Cylinder::new(0.4, 3.0).into(),
+                    dark.clone(),
+                    Vec3::new(3.0, ROBOT_TOP + 1.5, -3.0),
+                )),
+                RobotAntenna::Whip => {
+                    pieces.push((
+                        Cylinder::new(0.15, 9.0).into(),
+                        dark.clone(),
+                        Vec3::new(3.0, ROBOT_TOP + 4.5, -3.0),
+                    ));
+                    pieces.push((
+                        Sphere::new(0.6).into(),
+                        materials.add(Color::srgb(0.9, 0.1, 0.1)),
+                        Vec3::new(3.0, ROBOT_TOP + 9.0, -3.0),
+                    ));
+                }
+            }
+            // cargo boxes hang off the back of the robot
+            match *cargo_box {
+                RobotCargoBox::None => {}
+                RobotCargoBox::Crate => pieces.push((
+                    Cuboid::new(6.0, 5.0, 4.0).into(),
+                    brown,
+                    Vec3::new(0.0, 7.0, -9.0),
+                )),
+                RobotCargoBox::Mailbag => pieces.push((
+                    Sphere::new(3.0).into(),
+                    materials.add(Color::srgb(0.25, 0.3, 0.6)),
+                    Vec3::new(0.0, 7.0, -9.0),
+                )),
+                RobotCargoBox::Locker => pieces.push((
+                    Cuboid::new(7.0, 8.0, 3.0).into(),
+                    dark,
+                    Vec3::new(0.0, 8.0, -8.5),
+                )),
+            }
+            commands.entity(entity).with_children(|parent| {
+                for (mesh, material, position) in pieces {
+                    parent.spawn((
+                        Mesh3d(meshes.add(mesh)),
+                        MeshMaterial3d(material),
+                        Transform::from_translation(position),
+                        RobotAccessory,
+                    ));
+                }
+            });
+        }
+    }
+    /// bakes the paint and the decal into the body's texture, on a robot that's just been dressed
+    /// or whenever they change, the factory look is just the texture the body came with
+    fn paint_robots(
+        dressed_query: Query<Ref<Dressed>>,
+        mut materials: ResMut<Assets<StandardMaterial>>,
+        mut images: ResMut<Assets<Image>>,
+        paint: Res<RobotPaint>,
+        decal: Res<RobotDecal>,
+    ) {
+        let changed = paint.is_changed() || decal.is_changed();
+        for dressed in dressed_query.iter() {
+            if !changed && !dressed.is_added() {
+                continue;
+            }
+            let texture = match (*paint, *decal) {
+                (RobotPaint::Factory, RobotDecal::None) => dressed.texture.clone(),
+                _ => dressed
+                    .texture
+                    .as_ref()
+                    .and_then(|texture| images.get(texture))
+                    .map(|original| dress_texture(original, *paint, *decal))
+                    .map(|image| images.add(image))
+                    .or(dressed.texture.clone()),
+            };
+            if let Some(body) = materials.get_mut(&dressed.body) {
+                body.base_color_texture = texture;
+            }
+        }
+    }
+    fn load_garage(
+        mut paint: ResMut<RobotPaint>,
+        mut decal: ResMut<RobotDecal>,
+        mut antenna: ResMut<RobotAntenna>,
+        mut cargo_box: ResMut<RobotCargoBox>,
+        profile: Res<ActiveProfile>,
+    ) {
+        let values = read_key_values(&profile.path(GARAGE_FILE));
+        let get = |key: &str| values.get(key).map(String::as_str);
+        // a profile that hasn't been in the garage yet gets the plain robot
+        *paint = RobotPaint::ALL
+            .into_iter()
+            .find(|option| get("paint") == Some(option.label()))
+            .unwrap_or(RobotPaint::Factory);
+        *decal = RobotDecal::ALL
+            .into_iter()
+            .find(|option| get("decal") == Some(option.label()))
+            .unwrap_or(RobotDecal::None);
+        *antenna = RobotAntenna::ALL
+            .into_iter()
+            .find(|option| get("antenna") == Some(option.label()))
+            .unwrap_or(RobotAntenna::None);
+        *cargo_box = RobotCargoBox::ALL
+            .into_iter()
+            .find(|option| get("cargo_box") == Some(option.label()))
+            .unwrap_or(RobotCargoBox::None);
+    }
+    fn save_garage(
+        paint: Res<RobotPaint>,
+        decal: Res<RobotDecal>,
+        antenna: Res<RobotAntenna>,
+        cargo_box: Res<RobotCargoBox>,
+        profile: Res<ActiveProfile>,
+    ) {
+        write_key_values(
+            &profile.path(GARAGE_FILE),
+            &[
+                ("paint", paint.label().to_string()),
+                ("decal", decal.label().to_string()),
+                ("antenna", antenna.label().to_string()),
+                ("cargo_box", cargo_box.label().to_string()),
+            ],
+        );
+    }
+}
+
+mod profiles {
+    use super::{despawn_screen, GameState, TXT_CLR};
+    use crate::menu::{SelectedOption, NORMAL_BUTTON};
+    use crate::settings::{read_key_values, write_key_values};
+    use crate::upgrades::PROGRESS_FILE;
+    use bevy::{
+        color::palettes::css::CRIMSON,
+        input::{
+            keyboard::{Key, KeyboardInput},
+            ButtonState,
+        },
+        prelude::*,
+    };
+    use std::{fs, path::Path};
+
+    // This plugin lets several people share the game, each with a profile of their own. Every
+    // profile keeps its settings, high scores, coins, upgrades and garage in its own folder, and
+    // the profile screen after the splash picks, creates, renames and deletes them
+    pub fn profiles_plugin(app: &mut App) {
+        app.add_systems(Startup, load_profiles)
+            .add_systems(OnEnter(GameState::Profiles), profiles_setup)
+            .add_systems(
+                Update,
+                (
+                    profile_action,
+                    type_profile_name,
+                    build_profiles_screen.run_if(resource_changed::<ProfileScreen>),
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Profiles)),
+            )
+            .add_systems(
+                OnExit(GameState::Profiles),
+                despawn_screen::<OnProfilesScreen>,
+            );
+    }
+
+    const PROFILES_DIR: &str = "saves/profiles";
+    /// remembers which profile was played last
+    const PROFILES_FILE: &str = "saves/profiles.cfg";
+    const DEFAULT_PROFILE: &str = "Player";
+    const MAX_NAME_LENGTH: usize = 16;
+    /// how many careers of coins and upgrades each profile can keep going side by side
+    const SAVE_SLOTS: usize = 3;
+    /// remembers which save slot the profile played last
+    const SLOT_FILE: &str = "slot.cfg";
+    /// the save files from before there were profiles, which go to the first one made
+    const OLD_SAVES: [&str; 4] = [
+        "settings.cfg",
+        "highscores.cfg",
+        "progress.cfg",
+        "garage.cfg",
+    ];
+
+    /// the profile being played, whose save files everything reads and writes
+    #[derive(Resource)]
+    pub struct ActiveProfile {
+        name: String,
+        slot: usize,
+    }
+    impl ActiveProfile {
+        pub fn name(&self) -> &str {
+            &self.name
+        }
+        /// which of the profile's save slots is being played, counting from 0
+        pub fn slot(&self) -> usize {
+            self.slot
+        }
+        /// where one of the profile's save files lives
+        pub fn path(&self, file: &str) -> String {
+            format!("{PROFILES_DIR}/{}/{file}", self.name)
+        }
+        /// where one of the save files kept for each save slot lives
+        pub fn slot_path(&self, file: &str) -> String {
+            slot_path(&self.name, self.slot, file)
+        }
+    }
+    /// the first slot is the profile's own folder, where saves from before there were slots are
+    fn slot_path(name: &str, slot: usize, file: &str) -> String {
+        match slot {
+            0 => format!("{PROFILES_DIR}/{name}/{file}"),
+            _ => format!("{PROFILES_DIR}/{name}/slot{}/{file}", slot + 1),
+        }
+    }
+    /// the save slot a profile played last
+    fn last_slot(name: &str) -> usize {
+        read_key_values(&format!("{PROFILES_DIR}/{name}/{SLOT_FILE}"))
+            .get("slot")
+            .and_then(|slot| slot.parse().ok())
+            .filter(|slot| *slot < SAVE_SLOTS)
+            .unwrap_or_default()
+    }
+
+    /// what the profile screen is showing, it's rebuilt whenever this changes
+    #[derive(Resource)]
+    struct ProfileScreen {
+        profiles: Vec<String>,
+        selected: usize,
+        /// the save slot picked for the selected profile
+        slot: usize,
+        editing: Option<NameEdit>,
+        /// Delete has been pressed once and needs pressing again to go through with it
+        confirm_delete: bool,
+    }
+    /// a name being typed in, for a new profile or to rename the selected one
+    enum NameEdit {
+        New(String),
+        Rename(String),
+    }
+    impl NameEdit {
+        fn text(&mut self) -> &mut String {
+            match self {
+                NameEdit::New(text) | NameEdit::Rename(text) => text,
+            }
+        }
+    }
+
+    #[derive(Component)]
+    struct OnProfilesScreen;
+    #[derive(Component, Clone, Copy)]
+    enum ProfileButton {
+        Select(usize),
+        Slot(usize),
+        New,
+        Rename,
+        Delete,
+        Play,
+    }
+
+    /// the profiles there are, by folder name in alphabetical order
+    fn list_profiles() -> Vec<String> {
+        let mut profiles: Vec<String> = fs::read_dir(PROFILES_DIR)
+            .map(|entries| {
+                entries
+                    .filter_map(Result::ok)
+                    .filter(|entry| entry.path().is_dir())
+                    .filter_map(|entry| entry.file_name().into_string().ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        profiles.sort();
+        profiles
+    }
+    /// names become folder names, so only letters, digits, spaces, dashes and underscores go
+    fn valid_name_char(character: char) -> bool {
+        character.is_alphanumeric() || matches!(character, ' ' | '-' | '_')
+    }
+    fn remember_last(name: &str) {
+        write_key_values(PROFILES_FILE, &[("last", name.to_string())]);
+    }
+    fn remember_slot(name: &str, slot: usize) {
+        write_key_values(
+            &format!("{PROFILES_DIR}/{name}/{SLOT_FILE}"),
+            &[("slot", slot.to_string())],
+        );
+    }
+
+    /// picks the profile played last, making the first one out of any old saves if there's none
+    fn load_profiles(mut commands: Commands) {
+        let mut profiles = list_profiles();
+        if profiles.is_empty() {
+            let folder = format!("{PROFILES_DIR}/{DEFAULT_PROFILE}");
+            if let Err(error) = fs::create_dir_all(&folder) {
+                warn!("could not create {folder}: {error}");
+            }
+            for file in OLD_SAVES {
+                let old = format!("saves/{file}");
+                if Path::new(&old).exists() {
+                    let _ = fs::rename(&old, format!("{folder}/{file}"));
+                }
+            }
+            profiles.push(DEFAULT_PROFILE.to_string());
+        }
+        let last = read_key_values(PROFILES_FILE).remove("last");
+        let name = last
+            .filter(|name| profiles.contains(name))
+            .unwrap_or_else(|| profiles[0].clone());
+        let slot = last_slot(&name);
+        commands.insert_resource(ActiveProfile { name, slot });
+    }
+    fn profiles_setup(mut commands: Commands, profile: Res<ActiveProfile>) {
+        let profiles = list_profiles();
+        let selected = profiles
+            .iter()
+            .position(|name| *name == profile.name)
+            .unwrap_or_default();
+        commands.insert_resource(ProfileScreen {
+            profiles,
+            selected,
+            slot: profile.slot,
+            editing: None,
+            confirm_delete: false,
+        });
+    }
+    fn build_profiles_screen(
+        mut commands: Commands,
+        screen: Res<ProfileScreen>,
+        old_query: Query<Entity, With<OnProfilesScreen>>,
+    ) {
+        for entity in old_query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        let button_node = Node {
+            width: Val::Px(300.0),
+            height: Val::Px(50.0),
+            margin: UiRect::all(Val::Px(6.0)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        };
+        let button_text_style = (
+            TextFont {
+                font_size: 30.0,
+                ..default()
+            },
+            TextColor(TXT_CLR),
+        );
+        let prompt = match &screen.editing {
+            Some(NameEdit::New(text)) => format!("New profile: {text}_"),
+            Some(NameEdit::Rename(text)) => format!("Rename to: {text}_"),
+            None if screen.confirm_delete => format!(
+                "Press Delete again to delete {} for good",
+                screen.profiles[screen.selected]
+            ),
+            None => String::new(),
+        };
+
+        commands
+            .spawn((
+                Node {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                OnProfilesScreen,
+            ))
+            .with_children(|parent| {
+                parent
+                    .spawn((
+                        Node {
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        BackgroundColor(CRIMSON.into()),
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn((
+                            Text::new("Who's Playing?"),
+                            TextFont {
+                                font_size: 67.0,
+                                ..default()
+                            },
+                            TextColor(TXT_CLR),
+                            Node {
+                                margin: UiRect::all(Val::Px(20.0)),
+                                ..default()
+                            },
+                        ));
+                        for (index, name) in screen.profiles.iter().enumerate() {
+                            let mut entity = parent.spawn((
+                                Button,
+                                button_node.clone(),
+                                BackgroundColor(NORMAL_BUTTON),
+                                ProfileButton::Select(index),
+                            ));
+                            entity.with_children(|parent| {
+                                parent.spawn((Text::new(name.clone()), button_text_style.clone()));
+                            });
+                            if index == screen.selected {
+                                entity.insert(SelectedOption);
+                            }
+                        }
+                        // each slot shows the coins saved in it, so they can be told apart
+                        parent.spawn(Node::default()).with_children(|parent| {
+                            let name = &screen.profiles[screen.selected];
+                            for slot in 0..SAVE_SLOTS {
+                                let coins = read_key_values(&slot_path(name, slot, PROGRESS_FILE))
+                                    .remove("coins")
+                                    .unwrap_or_else(|| "0".to_string());
+                                let mut entity = parent.spawn((
+                                    Button,
+                                    Node {
+                                        width: Val::Px(200.0),
+                                        ..button_node.clone()
+                                    },
+                                    BackgroundColor(NORMAL_BUTTON),
+                                    ProfileButton::Slot(slot),
+                                ));
+                                entity.with_children(|parent| {
+                                    parent.spawn((
+                                        Text::new(format!("Slot {}: {coins}c", slot + 1)),
+                                        button_text_style.clone(),
+                                    ));
+                                });
+                                if slot == screen.slot {
+                                    entity.insert(SelectedOption);
+                                }
+                            }
+                        });
+                        parent.spawn((Text::new(prompt), button_text_style.clone()));
+
+                        parent.spawn(Node::default()).with_children(|parent| {
+                            let mut actions = vec![
+                                (ProfileButton::New, "New"),
+                                (ProfileButton::Rename, "Rename"),
+                            ];
+                            // there always has to be one profile left to play
+                            if screen.profiles.len() > 1 {
+                                actions.push((ProfileButton::Delete, "Delete"));
+                            }
+                            actions.push((ProfileButton::Play, "Play"));
+                            for (action, label) in actions {
+                                parent
+                                    .spawn((
+                                        Button,
+                                        Node {
+                                            width: Val::Px(150.0),
+                                            ..button_node.clone()
+                                        },
+                                        BackgroundColor(NORMAL_BUTTON),
+                                        action,
+                                    ))
+                                    .with_children(|parent| {
+                                        parent.spawn((Text::new(label), button_text_style.clone()));
+                                    });
+                            }
+                        });
+                    });
+            });
+    }
+    fn profile_action(
+        mut commands: Commands,
+        interaction_query: Query<(&Interaction, &ProfileButton), Changed<Interaction>>,
+        mut screen: ResMut<ProfileScreen>,
+        profile: Res<ActiveProfile>,
+        mut game_state: ResMut<NextState<GameState>>,
+    ) {
+        for (interaction, button) in &interaction_query {
+            if *interaction != Interaction::Pressed {
+                continue;
+            }
+            let confirming = screen.confirm_delete;
+            screen.confirm_delete = false;
+            match *button {
+                ProfileButton::Select(index) => {
+                    screen.selected = index;
+                    screen.slot = last_slot(&screen.profiles[index]);
+                    screen.editing = None;
+                }
+                ProfileButton::Slot(slot) => screen.slot = slot,
+                ProfileButton::New => screen.editing = Some(NameEdit::New(String::new())),
+                ProfileButton::Rename => {
+                    let name = screen.profiles[screen.selected].clone();
+                    screen.editing = Some(NameEdit::Rename(name));
+                }
+                ProfileButton::Delete if !confirming => {
+                    screen.editing = None;
+                    screen.confirm_delete = true;
+                }
+                ProfileButton::Delete => {
+                    let selected = screen.selected;
+                    let name = screen.profiles.remove(selected);
+                    let folder = format!("{PROFILES_DIR}/{name}");
+                    if let Err(error) = fs::remove_dir_all(&folder) {
+                        warn!("could not delete {folder}: {error}");
+                    }
+                    screen.selected = 0;
+                    screen.slot = last_slot(&screen.profiles[0]);
+                    // move off a deleted profile straight away, or its next save would bring the
+                    // folder back
+                    if name == profile.name {
+                        let name = screen.profiles[0].clone();
+                        remember_last(&name);
+                        let slot = screen.slot;
+                        commands.insert_resource(ActiveProfile { name, slot });
+                    }
+                }
+                ProfileButton::Play => {
+                    let name = screen.profiles[screen.selected].clone();
+                    let slot = screen.slot;
+                    remember_last(&name);
+                    remember_slot(&name, slot);
+                    // only a different profile or slot gets its saves loaded again
+                    if name != profile.name || slot != profile.slot {
+                        commands.insert_resource(ActiveProfile { name, slot });
+                    }
+                    game_state.set(GameState::Menu);
+                }
+            }
+        }
+    }
+    /// typing in a profile's name, Enter keeps it and Escape gives up on it
+    fn type_profile_name(
+        mut commands: Commands,
+        mut keyboard_events: EventReader<KeyboardInput>,
+        mut screen: ResMut<ProfileScreen>,
+        profile: Res<ActiveProfile>,
+    ) {
+        for event in keyboard_events.read() {
+            if event.state != ButtonState::Pressed || screen.editing.is_none() {
+                continue;
+            }
+            let Some(editing) = screen.editing.as_mut() else {
+                continue;
+            };
+            match &event.logical_key {
+                Key::Character(characters) => {
+                    let text = editing.text();
+                    for character in characters.chars().filter(|c| valid_name_char(*c)) {
+                        if text.chars().count() < MAX_NAME_LENGTH {
+                            text.push(character);
+                        }
+                    }
+                }
+                Key::Space => {
+                    let text = editing.text();
+                    if !text.is_empty() && text.chars().count() < MAX_NAME_LENGTH {
+                        text.push(' ');
+                    }
+                }
+                Key::Backspace => {
+                    editing.text().pop();
+                }
+                Key::Escape => screen.editing = None,
+                Key::Enter => {
+                    let Some(editing) = screen.editing.take() else {
+                        continue;
+                    };
+                    let (new, name) = match editing {
+                        NameEdit::New(name) => (true, name.trim().to_string()),
+                        NameEdit::Rename(name) => (false, name.trim().to_string()),
+                    };
+                    // names that only differ in case would share a folder on some systems
+                    let selected = screen.selected;
+                    let taken = screen.profiles.iter().enumerate().any(|(index, profile)| {
+                        profile.to_lowercase() == name.to_lowercase() && (new || index != selected)
+                    });
+                    if name.is_empty() || taken {
+                        continue;
+                    }
+                    let folder = format!("{PROFILES_DIR}/{name}");
+                    if new {
+                        if let Err(error) = fs::create_dir_all(&folder) {
+                            warn!("could not create {folder}: {error}");
+                            continue;
+                        }
+                        screen.profiles.push(name.clone());
+                        screen.slot = 0;
+                    } else {
+                        let old = screen.profiles[screen.selected].clone();
+                        if let Err(error) = fs::rename(format!("{PROFILES_DIR}/{old}"), &folder) {
+                            warn!("could not rename {old}: {error}");
+                            continue;
+                        }
+                        if old == profile.name {
+                            remember_last(&name);
+                            commands.insert_resource(ActiveProfile {
+                                name: name.clone(),
+                                slot: profile.slot,
+                            });
+                        }
+                        screen.profiles[selected] = name.clone();
+                    }
+                    screen.profiles.sort();
+                    screen.selected = screen
+                        .profiles
+                        .iter()
+                        .position(|profile| *profile == name)
+                        .unwrap_or_default();
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+mod achievements {
+    use super::{GameState, TXT_CLR};
+    use crate::game::{keeps_run, Deliveries, DeliveryMade, LastRun, RobotDrove};
+    use crate::profiles::ActiveProfile;
+    use crate::settings::{read_key_values, write_key_values};
+    use bevy::prelude::*;
+
+    // This plugin hands out achievements for milestones, worked out from what happens during
+    // play: deliveries, runs ending and the distance the robot covers. Progress is kept
+    // in the profile, and a toast pops up whenever one is unlocked
+    pub fn achievements_plugin(app: &mut App) {
+        app.init_resource::<AchievementProgress>()
+            .add_event::<AchievementUnlocked>()
+            .add_systems(
+                Update,
+                (
+                    load_achievements.run_if(resource_changed::<ActiveProfile>),
+                    (
+                        (track_deliveries, track_distance).run_if(in_state(GameState::Game)),
+                        track_run_end.run_if(resource_exists_and_changed::<LastRun>),
+                        // the distance creeps up every frame, so it's only written out along
+                        // with an unlock or at the end of a run
+                        save_achievements.run_if(
+                            on_event::<AchievementUnlocked>
+                                .or(resource_exists_and_changed::<LastRun>),
+                        ),
+                    )
+                        .chain()
+                        .run_if(keeps_run),
+                    fade_toasts,
+                )
+                    .chain(),
+            );
+    }
+
+    const ACHIEVEMENTS_FILE: &str = "achievements.cfg";
+    /// how long a toast stays up
+    const TOAST_SECS: f32 = 4.0;
+    /// deliveries in one run for Busy Day
+    const RUN_DELIVERIES: u32 = 10;
+    /// seconds from pickup to drop-off for Express Lane
+    const QUICK_DELIVERY_SECS: f32 = 5.0;
+    /// metres driven over every run for Long Haul, the map is laid out a unit to a metre
+    const TOTAL_DISTANCE: f32 = 10_000.0;
+
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+    pub enum Achievement {
+        FirstDelivery,
+        BusyDay,
+        Spotless,
+        ExpressLane,
+        LongHaul,
+    }
+    impl Achievement {
+        pub const ALL: [Self; 5] = [
+            Achievement::FirstDelivery,
+            Achievement::BusyDay,
+            Achievement::Spotless,
+            Achievement::ExpressLane,
+            Achievement::LongHaul,
+        ];
+        pub fn title(self) -> &'static str {
+            match self {
+                Achievement::FirstDelivery => "Signed For",
+                Achievement::BusyDay => "Busy Day",
+                Achievement::Spotless => "Spotless",
+                Achievement::ExpressLane => "Express Lane",
+                Achievement::LongHaul => "Long Haul",
+            }
+        }
+        pub fn description(self) -> &'static str {
+            match self {
+                Achievement::FirstDelivery => "make your first delivery",
+                Achievement::BusyDay => "make 10 deliveries in one run",
+                Achievement::Spotless => "finish a timed run without crashing",
+                Achievement::ExpressLane => "deliver a parcel within 5s of picking it up",
+                Achievement::LongHaul => "drive 10 km in total",
+            }
+        }
+        /// the name the achievement is saved under
+        fn key(self) -> &'static str {
+            match self {
+                Achievement::FirstDelivery => "first_delivery",
+                Achievement::BusyDay => "busy_day",
+                Achievement::Spotless => "spotless",
+                Achievement::ExpressLane => "express_lane",
+                Achievement::LongHaul => "long_haul",
+            }
+        }
+    }
+
+    /// which achievements are unlocked, and the running totals the others count towards
+    #[derive(Resource, Default)]
+    pub struct AchievementProgress {
+        unlocked: [bool; Achievement::ALL.len()],
+        distance: f32,
+    }
+    impl AchievementProgress {
+        pub fn unlocked(&self, achievement: Achievement) -> bool {
+            self.unlocked[achievement as usize]
+        }
+        /// how far along a counting achievement is, for the achievements screen
+        pub fn progress_text(&self, achievement: Achievement) -> Option<String> {
+            match achievement {
+                Achievement::LongHaul if !self.unlocked(achievement) => Some(format!(
+                    "{:.1} / {:.0} km",
+                    self.distance / 1000.0,
+                    TOTAL_DISTANCE / 1000.0
+                )),
+                _ => None,
+            }
+        }
+        /// marks an achievement unlocked, returning whether it wasn't already
+        fn unlock(&mut self, achievement: Achievement) -> bool {
+            !std::mem::replace(&mut self.unlocked[achievement as usize], true)
+        }
+    }
+
+    /// a message about a new achievement, which disappears after a few seconds
+    #[derive(Component)]
+    struct Toast(Timer);
+    /// sent when an achievement is unlocked
+    #[derive(Event)]
+    struct AchievementUnlocked;
+
+    /// unlocks an achievement and puts up a toast about it, if it wasn't unlocked already
+    fn award(
+        commands: &mut Commands,
+        progress: &mut AchievementProgress,
+        achievement: Achievement,
+        toasts: usize,
+    ) {
+        if !progress.unlock(achievement) {
+            return;
+        }
+        commands.send_event(AchievementUnlocked);
+        commands.spawn((
+            Toast(Timer::from_seconds(TOAST_SECS, TimerMode::Once)),
+            Text::new(format!("Achievement unlocked: {}", achievement.title())),
+            TextFont {
+                font_size: 30.0,
+                ..default()
+            },
+            TextColor(TXT_CLR),
+            Node {
+                position_type: PositionType::Absolute,
+                // toasts that come at once stack up rather than cover each other
+                bottom: Val::Px(80.0 + 50.0 * toasts as f32),
+                left: Val::Px(10.0),
+                padding: UiRect::all(Val::Px(10.0)),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.1, 0.35, 0.15, 0.85)),
+            GlobalZIndex(10),
+        ));
+    }
+
+    fn track_deliveries(
+        mut commands: Commands,
+        mut progress: ResMut<AchievementProgress>,
+        mut delivery_events: EventReader<DeliveryMade>,
+        deliveries: Res<Deliveries>,
+        toast_query: Query<(), With<Toast>>,
+    ) {
+        let mut toasts = toast_query.iter().count();
+        let mut earned = Vec::new();
+        for delivery in delivery_events.read() {
+            earned.push(Achievement::FirstDelivery);
+            if delivery.secs <= QUICK_DELIVERY_SECS {
+                earned.push(Achievement::ExpressLane);
+            }
+        }
+        if deliveries.0 >= RUN_DELIVERIES {
+            earned.push(Achievement::BusyDay);
+        }
+        for achievement in earned {
+            if !progress.unlocked(achievement) {
+                award(&mut commands, &mut progress, achievement, toasts);
+                toasts += 1;
+            }
+        }
+    }
+    fn track_distance(
+        mut commands: Commands,
+        mut drove_events: EventReader<RobotDrove>,
+        mut progress: ResMut<AchievementProgress>,
+        toast_query: Query<(), With<Toast>>,
+    ) {
+        let step: f32 = drove_events.read().map(|drove| drove.distance).sum();
+        if step == 0.0 {
+            return;
+        }
+        progress.distance += step;
+        if progress.distance >= TOTAL_DISTANCE && !progress.unlocked(Achievement::LongHaul) {
+            let toasts = toast_query.iter().count();
+            award(&mut commands, &mut progress, Achievement::LongHaul, toasts);
+        }
+    }
+    fn track_run_end(
+        mut commands: Commands,
+        last_run: Res<LastRun>,
+        mut progress: ResMut<AchievementProgress>,
+        toast_query: Query<(), With<Toast>>,
+    ) {
+        // a run that lasted until the clock ran out is one without a crash
+        if last_run.out_of_time && last_run.score > 0 {
+            let toasts = toast_query.iter().count();
+            award(&mut commands, &mut progress, Achievement::Spotless, toasts);
+        }
+    }
+    fn fade_toasts(
+        mut commands: Commands,
+        time: Res<Time>,
+        mut toast_query: Query<(Entity, &mut Toast)>,
+    ) {
+        for (entity, mut toast) in toast_query.iter_mut() {
+            if toast.0.tick(time.delta()).finished() {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+    fn load_achievements(mut progress: ResMut<AchievementProgress>, profile: Res<ActiveProfile>) {
+        let values = read_key_values(&profile.path(ACHIEVEMENTS_FILE));
+        for achievement in Achievement::ALL {
+            let key = format!("unlocked.{}", achievement.key());
+            progress.unlocked[achievement as usize] =
+                values.get(&key).is_some_and(|value| value == "true");
+        }
+        progress.distance = values
+            .get("distance")
+            .and_then(|value| value.parse().ok())
+            .unwrap_or_default();
+    }
+    fn save_achievements(progress: Res<AchievementProgress>, profile: Res<ActiveProfile>) {
+        let keys: Vec<String> = Achievement::ALL
+            .iter()
+            .map(|achievement| format!("unlocked.{}", achievement.key()))
+            .collect();
+        let mut entries = vec![("distance", progress.distance.round().to_string())];
+        for (key, achievement) in keys.iter().zip(Achievement::ALL) {
+            entries.push((key.as_str(), progress.unlocked(achievement).to_string()));
+        }
+        write_key_values(&profile.path(ACHIEVEMENTS_FILE), &entries);
+    }
+}
+
+mod stats {
+    use super::{GameMode, GameState};
+    use crate::game::{
+        keeps_run, CrashCause, DeliveryMade, LastRun, RobotCrashed, RobotDrove, RunInProgress,
+    };
+    use crate::profiles::ActiveProfile;
+    use crate::settings::{read_key_values, write_key_values};
+    use bevy::prelude::*;
+
+    // This plugin keeps count of how every run goes, both for the run under way and added up
+    // over the life of the profile: deliveries, crashes and what caused them, distance driven,
+    // streaks, delivery times and how long each mode has been played. The totals are saved in
+    // the profile whenever a run ends
+    pub fn stats_plugin(app: &mut App) {
+        app.init_resource::<LifetimeStats>()
+            .init_resource::<RunStats>()
+            .add_systems(
+                Update,
+                (
+                    load_stats.run_if(resource_changed::<ActiveProfile>),
+                    start_run_stats.run_if(resource_added::<RunInProgress>),
+                    (count_deliveries, count_distance, count_play_time)
+                        .run_if(in_state(GameState::Game)),
+                    // the crash that ends a run is read once the game has already left the run
+                    count_crashes,
+                    (finish_run_stats, save_stats)
+                        .run_if(resource_exists_and_changed::<LastRun>.and(keeps_run)),
+                    // a cheated run was counted as it went, so the saved totals are put back
+                    load_stats.run_if(resource_exists_and_changed::<LastRun>.and(not(keeps_run))),
+                )
+                    .chain(),
+            );
+    }
+
+    const STATS_FILE: &str = "stats.cfg";
+    const MODES: [GameMode; 4] = [
+        GameMode::Timed,
+        GameMode::Endless,
+        GameMode::Zen,
+        GameMode::Survival,
+    ];
+
+    /// totals over every run the profile has played
+    #[derive(Resource, Default)]
+    pub struct LifetimeStats {
+        pub runs: u32,
+        pub deliveries: u32,
+        pub late_deliveries: u32,
+        /// seconds from pickup to drop-off, over every delivery
+        delivery_secs: f32,
+        pub best_run_deliveries: u32,
+        /// the most deliveries made on time one after another in a run
+        pub best_streak: u32,
+        pub distance: f32,
+        crashes: [u32; CrashCause::ALL.len()],
+        play_secs: [f32; MODES.len()],
+    }
+    impl LifetimeStats {
+        pub fn crashes(&self, cause: CrashCause) -> u32 {
+            self.crashes[cause as usize]
+        }
+        pub fn play_secs(&self, mode: GameMode) -> f32 {
+            self.play_secs[mode as usize]
+        }
+        pub fn average_delivery_secs(&self) -> f32 {
+            if self.deliveries == 0 {
+                0.0
+            } else {
+                self.delivery_secs / self.deliveries as f32
+            }
+        }
+    }
+    /// how the run under way is going, or how the last one went once it's over
+    #[derive(Resource, Default)]
+    pub struct RunStats {
+        pub deliveries: u32,
+        pub distance: f32,
+        /// on-time deliveries in a row so far, and the longest run of them
+        streak: u32,
+        pub best_streak: u32,
+    }
+
+    fn start_run_stats(mut run_stats: ResMut<RunStats>) {
+        *run_stats = RunStats::default();
+    }
+    fn count_deliveries(
+        mut delivery_events: EventReader<DeliveryMade>,
+        mut stats: ResMut<LifetimeStats>,
+        mut run_stats: ResMut<RunStats>,
+    ) {
+        for delivery in delivery_events.read() {
+            stats.deliveries += 1;
+            stats.delivery_secs += delivery.secs;
+            run_stats.deliveries += 1;
+            if delivery.late {
+                stats.late_deliveries += 1;
+                run_stats.streak = 0;
+            } else {
+                run_stats.streak += 1;
+                run_stats.best_streak = run_stats.best_streak.max(run_stats.streak);
+            }
+        }
+    }
+    fn count_crashes(
+        mut crash_events: EventReader<RobotCrashed>,
+        mut stats: ResMut<LifetimeStats>,
+    ) {
+        for crash in crash_events.read() {
+            stats.crashes[crash.cause as usize] += 1;
+        }
+    }
+    fn count_distance(
+        mut drove_events: EventReader<RobotDrove>,
+        mut stats: ResMut<LifetimeStats>,
+        mut run_stats: ResMut<RunStats>,
+    ) {
+        for drove in drove_events.read() {
+            stats.distance += drove.distance;
+            run_stats.distance += drove.distance;
+        }
+    }
+    fn count_play_time(
+        time: Res<Time>,
+        game_mode: Res<GameMode>,
+        mut stats: ResMut<LifetimeStats>,
+    ) {
+        stats.play_secs[*game_mode as usize] += time.delta_secs();
+    }
+    fn finish_run_stats(mut stats: ResMut<LifetimeStats>, run_stats: Res<RunStats>) {
+        stats.runs += 1;
+        stats.best_run_deliveries = stats.best_run_deliveries.max(run_stats.deliveries);
+        stats.best_streak = stats.best_streak.max(run_stats.best_streak);
+    }
+    fn crash_key(cause: CrashCause) -> String {
+        format!("crashes.{}", cause.key())
+    }
+    fn play_time_key(mode: GameMode) -> String {
+        format!("play_secs.{mode:?}").to_lowercase()
+    }
+    fn load_stats(mut stats: ResMut<LifetimeStats>, profile: Res<ActiveProfile>) {
+        let values = read_key_values(&profile.path(STATS_FILE));
+        let number = |key: &str| values.get(key).and_then(|value| value.parse::<f32>().ok());
+        *stats = LifetimeStats {
+            runs: number("runs").unwrap_or_default() as u32,
+            deliveries: number("deliveries").unwrap_or_default() as u32,
+            late_deliveries: number("late_deliveries").unwrap_or_default() as u32,
+            delivery_secs: number("delivery_secs").unwrap_or_default(),
+            best_run_deliveries: number("best_run_deliveries").unwrap_or_default() as u32,
+            best_streak: number("best_streak").unwrap_or_default() as u32,
+            distance: number("distance").unwrap_or_default(),
+            crashes: CrashCause::ALL
+                .map(|cause| number(&crash_key(cause)).unwrap_or_default() as u32),
+            play_secs: MODES.map(|mode| number(&play_time_key(mode)).unwrap_or_default()),
+        };
+    }
+    fn save_stats(stats: Res<LifetimeStats>, profile: Res<ActiveProfile>) {
+        let mut entries = vec![
+            ("runs".to_string(), stats.runs.to_string()),
+            ("deliveries".to_string(), stats.deliveries.to_string()),
+            (
+                "late_deliveries".to_string(),
+                stats.late_deliveries.to_string(),
+            ),
+            (
+                "delivery_secs".to_string(),
+                format!("{:.1}", stats.delivery_secs),
+            ),
+            (
+                "best_run_deliveries".to_string(),
+                stats.best_run_deliveries.to_string(),
+            ),
+            ("best_streak".to_string(), stats.best_streak.to_string()),
+            ("distance".to_string(), format!("{:.0}", stats.distance)),
+        ];
+        for cause in CrashCause::ALL {
+            entries.push((crash_key(cause), stats.crashes(cause).to_string()));
+        }
+        for mode in MODES {
+            entries.push((play_time_key(mode), format!("{:.0}", stats.play_secs(mode))));
+        }
+        let entries: Vec<(&str, String)> = entries
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.clone()))
+            .collect();
+        write_key_values(&profile.path(STATS_FILE), &entries);
+    }
+}
+
+mod crash_log {
+    use super::{Difficulty, GameMode, RoundLength};
+    use crate::game::{
+        keeps_run, BuildingSlot, CrashCause, RobotCrashed, SelectedMap, BUILDING_COORDS,
+        BUILDING_CRASH_RADIUS, BUILDING_MODELS, WALL_RADIUS,
+    };
+    use bevy::{
+        image::Image,
+        prelude::*,
+        render::{
+            render_asset::RenderAssetUsages,
+            render_resource::{Extent3d, TextureDimension, TextureFormat},
+        },
+    };
+    use std::{
+        fs::{self, OpenOptions},
+        io::Write,
+        path::Path,
+    };
+
+    // This plugin writes down every crash that loses a run, where it happened and what was hit,
+    // so it's easy to see which parts of the island catch players out. The log builds up over
+    // every run on this machine, and can be exported as a table of the worst spots and a
+    // top-down heatmap of the island, drawn without needing a window
+    pub fn crash_log_plugin(app: &mut App) {
+        app.init_resource::<CrashLog>()
+            .add_event::<ExportCrashMap>()
+            .add_event::<CrashMapExported>()
+            .add_systems(Startup, load_crash_log)
+            .add_systems(
+                Update,
+                (
+                    record_crashes.run_if(keeps_run),
+                    export_crash_map.run_if(on_event::<ExportCrashMap>),
+                ),
+            );
+    }
+
+    /// every crash from every player, rather than a profile's own, since it's for level design
+    const CRASH_LOG_FILE: &str = "saves/crash_log.csv";
+    const CRASH_LOG_HEADER: &str = "map,mode,difficulty,cause,x,z,building,model,entity";
+    /// where exported files are written
+    pub const EXPORT_DIR: &str = "exports";
+    /// the heatmap's size in pixels, and how much of the island it covers either side of the middle
+    const HEATMAP_SIZE: u32 = 512;
+    const HEATMAP_EXTENT: f32 = 220.0;
+    /// how far the heat from a crash spreads
+    const HEAT_SPREAD: f32 = 6.0;
+
+    /// asks for the crash log to be exported
+    #[derive(Event)]
+    pub struct ExportCrashMap;
+    /// how the export went, with what went wrong if it failed
+    #[derive(Event)]
+    pub struct CrashMapExported(pub Result<(), String>);
+
+    struct CrashRecord {
+        cause: CrashCause,
+        x: f32,
+        z: f32,
+        /// the building in the island's layout that was hit, see `BUILDING_MODELS`
+        building: Option<usize>,
+    }
+    #[derive(Resource, Default)]
+    struct CrashLog(Vec<CrashRecord>);
+
+    fn load_crash_log(mut log: ResMut<CrashLog>) {
+        let contents = fs::read_to_string(CRASH_LOG_FILE).unwrap_or_default();
+        log.0 = contents
+            .lines()
+            .skip(1)
+            .filter_map(|line| {
+                let columns: Vec<&str> = line.split(',').collect();
+                let cause = CrashCause::ALL
+                    .into_iter()
+                    .find(|cause| Some(&cause.key().as_str()) == columns.get(3))?;
+                Some(CrashRecord {
+                    cause,
+                    x: columns.get(4)?.parse().ok()?,
+                    z: columns.get(5)?.parse().ok()?,
+                    building: columns.get(6).and_then(|slot| slot.parse().ok()),
+                })
+            })
+            .collect();
+    }
+    fn record_crashes(
+        mut crash_events: EventReader<RobotCrashed>,
+        mut log: ResMut<CrashLog>,
+        slot_query: Query<&BuildingSlot>,
+        (game_mode, round_length, difficulty, selected_map): (
+            Res<GameMode>,
+            Res<RoundLength>,
+            Res<Difficulty>,
+            Res<SelectedMap>,
+        ),
+    ) {
+        for crash in crash_events.read() {
+            let building = crash
+                .building
+                .and_then(|entity| slot_query.get(entity).ok())
+                .map(|slot| slot.0);
+            let record = CrashRecord {
+                cause: crash.cause,
+                x: crash.at.x,
+                z: crash.at.z,
+                building,
+            };
+            let line = format!(
+                "{},{},{:?},{},{:.1},{:.1},{},{},{}",
+                selected_map.info().name,
+                game_mode.label(*round_length),
+                *difficulty,
+                record.cause.key(),
+                record.x,
+                record.z,
+                building.map(|slot| slot.to_string()).unwrap_or_default(),
+                building
+                    .map(|slot| BUILDING_MODELS[slot])
+                    .unwrap_or_default(),
+                crash
+                    .building
+                    .map(|entity| entity.to_string())
+                    .unwrap_or_default(),
+            );
+            append_line(CRASH_LOG_FILE, &line);
+            log.0.push(record);
+        }
+    }
+    /// adds a line to the end of a CSV file, starting it with the header if it's new
+    fn append_line(path: &str, line: &str) {
+        if let Some(parent) = Path::new(path).parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        let new = !Path::new(path).exists();
+        let result = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| {
+                if new {
+                    writeln!(file, "{CRASH_LOG_HEADER}")?;
+                }
+                writeln!(file, "{line}")
+            });
+        if let Err(error) = result {
+            warn!("could not log crash to {path}: {error}");
+        }
+    }
+    fn export_crash_map(log: Res<CrashLog>, mut exported_events: EventWriter<CrashMapExported>) {
+        let result = write_crash_map(&log.0);
+        match &result {
+            Ok(()) => info!("exported {} crashes to {EXPORT_DIR}/", log.0.len()),
+            Err(error) => warn!("{error}"),
+        }
+        exported_events.send(CrashMapExported(result));
+    }
+    fn write_crash_map(records: &[CrashRecord]) -> Result<(), String> {
+        fs::create_dir_all(EXPORT_DIR)
+            .map_err(|error| format!("could not create {EXPORT_DIR}: {error}"))?;
+        let hotspots_path = format!("{EXPORT_DIR}/crash_hotspots.csv");
+        fs::write(&hotspots_path, hotspots_csv(records))
+            .map_err(|error| format!("could not export {hotspots_path}: {error}"))?;
+        let heatmap_path = format!("{EXPORT_DIR}/crash_heatmap.png");
+        heatmap(records)
+            .try_into_dynamic()
+            .map_err(|error| error.to_string())
+            .and_then(|image| image.save(&heatmap_path).map_err(|error| error.to_string()))
+            .map_err(|error| format!("could not export {heatmap_path}: {error}"))
+    }
+    /// crashes added up for each building, worst first, then for everything else that was hit
+    fn hotspots_csv(records: &[CrashRecord]) -> String {
+        let mut buildings: Vec<(usize, usize)> = (0..BUILDING_MODELS.len())
+            .map(|slot| {
+                let crashes = records
+                    .iter()
+                    .filter(|record| record.building == Some(slot))
+                    .count();
+                (slot, crashes)
+            })
+            .filter(|(_, crashes)| *crashes > 0)
+            .collect();
+        buildings.sort_by_key(|(slot, crashes)| (std::cmp::Reverse(*crashes), *slot));
+
+        let mut csv = String::from("cause,building,model,x,z,crashes\n");
+        for (slot, crashes) in buildings {
+            let coords = BUILDING_COORDS[slot];
+            csv.push_str(&format!(
+                "{},{slot},{},{},{},{crashes}\n",
+                CrashCause::Building.key(),
+                BUILDING_MODELS[slot],
+                coords.0,
+                coords.2
+            ));
+        }
+        for cause in CrashCause::ALL {
+            let crashes = records
+                .iter()
+                .filter(|record| record.cause == cause && record.building.is_none())
+                .count();
+            if crashes > 0 {
+                csv.push_str(&format!("{},,,,,{crashes}\n", cause.key()));
+            }
+        }
+        csv
+    }
+    /// the island seen from above, with the buildings and the ring wall drawn in
+    /// and the crashes glowing over them, hotter where more of them bunch up
+    fn heatmap(records: &[CrashRecord]) -> Image {
+        let size = HEATMAP_SIZE as usize;
+        let world_per_pixel = HEATMAP_EXTENT * 2.0 / HEATMAP_SIZE as f32;
+        let to_world = |pixel: usize| (pixel as f32 + 0.5) * world_per_pixel - HEATMAP_EXTENT;
+
+        let mut heat = vec![0.0f32; size * size];
+        let reach = (HEAT_SPREAD * 3.0 / world_per_pixel).ceil() as isize;
+        for record in records {
+            let centre_x = ((record.x + HEATMAP_EXTENT) / world_per_pixel) as isize;
+            let centre_y = ((record.z + HEATMAP_EXTENT) / world_per_pixel) as isize;
+            for y in (centre_y - reach).max(0)..(centre_y + reach + 1).min(size as isize) {
+                for x in (centre_x - reach).max(0)..(centre_x + reach + 1).min(size as isize) {
+                    let offset = Vec2::new(
+                        to_world(x as usize) - record.x,
+                        to_world(y as usize) - record.z,
+                    );
+                    heat[y as usize * size + x as usize] +=
+                        (-offset.length_squared() / (2.0 * HEAT_SPREAD * HEAT_SPREAD)).exp();
+                }
+            }
+        }
+        let hottest = heat.iter().copied().fold(0.0, f32::max);
+
+        let mut data = Vec::with_capacity(size * size * 4);
+        for y in 0..size {
+            for x in 0..size {
+                let point = Vec2::new(to_world(x), to_world(y));
+                let distance = point.length();
+                let mut color = if (distance - WALL_RADIUS).abs() < world_per_pixel * 1.5 {
+                    Vec3::new(0.85, 0.85, 0.85)
+                } else if distance > WALL_RADIUS {
+                    Vec3::new(0.08, 0.16, 0.28)
+                } else {
+                    Vec3::new(0.24, 0.36, 0.24)
+                };
+                if BUILDING_COORDS.iter().any(|coords| {
+                    point.distance(Vec2::new(coords.0, coords.2)) < BUILDING_CRASH_RADIUS
+                }) {
+                    color = Vec3::new(0.5, 0.5, 0.55);
+                }
+                if hottest > 0.0 {
+                    // yellow where there are a few crashes, through to red where they're worst
+                    let amount = heat[y * size + x] / hottest;
+                    let glow = Vec3::new(1.0, 1.0 - amount, 0.0);
+                    color = color.lerp(glow, (amount * 1.5).min(1.0));
+                }
+                let [red, green, blue] = (color * 255.0).to_array().map(|channel| channel as u8);
+                data.extend_from_slice(&[red, green, blue, 255]);
+            }
+        }
+        Image::new(
+            Extent3d {
+                width: HEATMAP_SIZE,
+                height: HEATMAP_SIZE,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::default(),
+        )
+    }
+}
+
+mod telemetry {
+    use super::{Difficulty, GameMode, GameState, RoundLength, Telemetry};
+    use crate::game::{
+        keeps_run, DeliveryMade, LastRun, ParcelPickedUp, RobotCrashed, RunInProgress, SelectedMap,
+    };
+    use crate::settings::Preferences;
+    use bevy::prelude::*;
+    use rand::random;
+    use std::{
+        fs::{self, File, OpenOptions},
+        io::Write,
+        path::Path,
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    // This plugin writes what happens during play to a local log, one JSON object a line, for
+    // players who turn on Telemetry in the settings. With it off, which is the default, nothing
+    // is recorded and no file is made. Lines go to sinks, so somewhere other than the local file,
+    // like a collector over HTTP, can be added with `TelemetryLog::add_sink`
+    pub fn telemetry_plugin(app: &mut App) {
+        let mut log = TelemetryLog::default();
+        log.add_sink(JsonLinesFile::new(TELEMETRY_FILE));
+        app.insert_resource(log)
+            .add_systems(
+                Update,
+                (
+                    log_settings_changes,
+                    log_run_start.run_if(resource_added::<RunInProgress>),
+                    // not just during the run, the crash that ends one arrives after it's left
+                    log_gameplay,
+                    log_run_end.run_if(resource_exists_and_changed::<LastRun>),
+                )
+                    .chain()
+                    .run_if(resource_equals(Telemetry(true)).and(keeps_run)),
+            )
+            .add_systems(
+                OnEnter(GameState::Pause),
+                log_pause.run_if(resource_equals(Telemetry(true))),
+            )
+            .add_systems(
+                OnTransition {
+                    exited: GameState::Pause,
+                    entered: GameState::Game,
+                },
+                log_resume.run_if(resource_equals(Telemetry(true))),
+            );
+    }
+
+    const TELEMETRY_FILE: &str = "saves/telemetry.jsonl";
+
+    /// somewhere telemetry ends up, given each event as a line of JSON
+    pub trait TelemetrySink: Send + Sync {
+        fn send(&mut self, line: &str);
+    }
+    /// appends events to a local file, which isn't made until the first one arrives
+    pub struct JsonLinesFile {
+        path: String,
+        file: Option<File>,
+    }
+    impl JsonLinesFile {
+        pub fn new(path: &str) -> Self {
+            Self {
+                path: path.to_string(),
+                file: None,
+            }
+        }
+    }
+    impl TelemetrySink for JsonLinesFile {
+        fn send(&mut self, line: &str) {
+            if self.file.is_none() {
+                if let Some(parent) = Path::new(&self.path).parent() {
+                    let _ = fs::create_dir_all(parent);
+                }
+                match OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)
+                {
+                    Ok(file) => self.file = Some(file),
+                    Err(error) => {
+                        warn!("could not open {}: {error}", self.path);
+                        return;
+                    }
+                }
+            }
+            if let Some(file) = self.file.as_mut() {
+                if let Err(error) = writeln!(file, "{line}") {
+                    warn!("could not write to {}: {error}", self.path);
+                }
+            }
+        }
+    }
+
+    /// a value in an event
+    pub enum Field {
+        Text(String),
+        Number(f64),
+        Flag(bool),
+    }
+    impl Field {
+        fn json(&self) -> String {
+            match self {
+                Field::Text(text) => json_string(text),
+                Field::Number(number) if number.is_finite() => number.to_string(),
+                Field::Number(_) => "null".to_string(),
+                Field::Flag(flag) => flag.to_string(),
+            }
+        }
+    }
+    fn json_string(text: &str) -> String {
+        let mut json = String::from('"');
+        for c in text.chars() {
+            match c {
+                '"' => json.push_str("\\\""),
+                '\\' => json.push_str("\\\\"),
+                '\n' => json.push_str("\\n"),
+                c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+                c => json.push(c),
+            }
+        }
+        json.push('"');
+        json
+    }
+
+    /// hands events to the sinks, stamped with the time, the session and the run they belong to
+    #[derive(Resource)]
+    pub struct TelemetryLog {
+        sinks: Vec<Box<dyn TelemetrySink>>,
+        session: String,
+        run: Option<String>,
+        /// whether the run under way ended in a crash, for the reason it ended
+        crashed: bool,
+    }
+    impl Default for TelemetryLog {
+        fn default() -> Self {
+            Self {
+                sinks: Vec::new(),
+                session: format!("{:016x}", random::<u64>()),
+                run: None,
+                crashed: false,
+            }
+        }
+    }
+    impl TelemetryLog {
+        pub fn add_sink(&mut self, sink: impl TelemetrySink + 'static) {
+            self.sinks.push(Box::new(sink));
+        }
+        pub fn record(&mut self, event: &str, fields: &[(&str, Field)]) {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|time| time.as_millis())
+                .unwrap_or_default();
+            let run = self
+                .run
+                .as_deref()
+                .map(json_string)
+                .unwrap_or_else(|| "null".to_string());
+            let mut line = format!(
+                "{{\"ts\":{timestamp},\"session\":{},\"run\":{run},\"event\":{}",
+                json_string(&self.session),
+                json_string(event)
+            );
+            for (key, value) in fields {
+                line.push_str(&format!(",{}:{}", json_string(key), value.json()));
+            }
+            line.push('}');
+            for sink in self.sinks.iter_mut() {
+                sink.send(&line);
+            }
+        }
+    }
+
+    fn log_settings_changes(
+        mut log: ResMut<TelemetryLog>,
+        preferences: Preferences,
+        mut previous: Local<Vec<(String, String)>>,
+    ) {
+        if !preferences.is_changed() && !previous.is_empty() {
+            return;
+        }
+        let entries = preferences.entries();
+        // the first look is just what the settings were when telemetry was turned on
+        if !previous.is_empty() {
+            for (key, value) in entries.iter().filter(|entry| !previous.contains(entry)) {
+                log.record(
+                    "setting_changed",
+                    &[
+                        ("setting", Field::Text(key.clone())),
+                        ("value", Field::Text(value.clone())),
+                    ],
+                );
+            }
+        }
+        *previous = entries;
+    }
+    fn log_run_start(
+        mut log: ResMut<TelemetryLog>,
+        selected_map: Res<SelectedMap>,
+        (game_mode, round_length, difficulty): (Res<GameMode>, Res<RoundLength>, Res<Difficulty>),
+    ) {
+        log.run = Some(format!("{:016x}", random::<u64>()));
+        log.crashed = false;
+        log.record(
+            "run_start",
+            &[
+                ("map", Field::Text(selected_map.info().name.to_string())),
+                ("mode", Field::Text(game_mode.label(*round_length))),
+                ("difficulty", Field::Text(format!("{:?}", *difficulty))),
+            ],
+        );
+    }
+    fn log_gameplay(
+        mut log: ResMut<TelemetryLog>,
+        mut pickup_events: EventReader<ParcelPickedUp>,
+        mut delivery_events: EventReader<DeliveryMade>,
+        mut crash_events: EventReader<RobotCrashed>,
+    ) {
+        for pickup in pickup_events.read() {
+            log.record(
+                "pickup",
+                &[
+                    ("x", Field::Number(pickup.at.x as f64)),
+                    ("z", Field::Number(pickup.at.z as f64)),
+                ],
+            );
+        }
+        for delivery in delivery_events.read() {
+            log.record(
+                "delivery",
+                &[
+                    ("secs", Field::Number(delivery.secs as f64)),
+                    ("late", Field::Flag(delivery.late)),
+                ],
+            );
+        }
+        for crash in crash_events.read() {
+            log.crashed = true;
+            log.record(
+                "crash",
+                &[
+                    ("cause", Field::Text(crash.cause.key())),
+                    ("x", Field::Number(crash.at.x as f64)),
+                    ("z", Field::Number(crash.at.z as f64)),
+                ],
+            );
+        }
+    }
+    fn log_run_end(mut log: ResMut<TelemetryLog>, last_run: Res<LastRun>) {
+        let reason = if last_run.out_of_time {
+            "out_of_time"
+        } else if log.crashed {
+            "crashed"
+        } else {
+            "quit"
+        };
+        log.record(
+            "run_end",
+            &[
+                ("reason", Field::Text(reason.to_string())),
+                ("score", Field::Number(last_run.score as f64)),
+                ("new_best", Field::Flag(last_run.new_best)),
+            ],
+        );
+        log.run = None;
+    }
+    fn log_pause(mut log: ResMut<TelemetryLog>) {
+        log.record("pause", &[]);
+    }
+    fn log_resume(mut log: ResMut<TelemetryLog>) {
+        log.record("resume", &[]);
+    }
+}
+
+mod console {
+    use super::GameState;
+    use crate::cli::debug_tools;
+    use crate::game::CheatedRun;
+    use bevy::{
+        input::{
+            keyboard::{Key, KeyboardInput},
+            ButtonState, InputSystem,
+        },
+        prelude::*,
+    };
+    use std::collections::VecDeque;
+
+    // This plugin is a console for trying things out during a run, opened and closed with the
+    // backtick key when the game is started with --debug. While it's open the keys type into it
+    // instead of driving the robot. Each module adds its own commands through
+    // `AppConsoleExt::add_console_command`, and `help` lists all of them. Any command that
+    // goes through marks the run as cheated, so none of it is saved
+    pub fn console_plugin(app: &mut App) {
+        app.init_resource::<Console>()
+            .init_resource::<ConsoleCommands>()
+            .add_systems(
+                PreUpdate,
+                hold_game_keys
+                    .after(InputSystem)
+                    .run_if(|console: Res<Console>| console.open),
+            )
+            .add_systems(
+                Update,
+                (type_console, run_console_commands, update_console)
+                    .chain()
+                    .run_if(in_state(GameState::Game).and(debug_tools)),
+            )
+            .add_systems(OnExit(GameState::Game), close_console);
+    }
+
+    /// how many lines of earlier commands and their replies stay on screen
+    const HISTORY_LINES: usize = 12;
+
+    /// something that can be typed into the console, the first word picks the command and the
+    /// rest are handed to it
+    pub trait ConsoleCommand: Send + Sync {
+        fn name(&self) -> &'static str;
+        /// what goes after the name, for `help`
+        fn usage(&self) -> &'static str;
+        /// carries out the command, returning what to print back
+        fn run(&self, args: &[&str], world: &mut World) -> Result<String, String>;
+    }
+    pub trait AppConsoleExt {
+        fn add_console_command(&mut self, command: impl ConsoleCommand + 'static) -> &mut Self;
+    }
+    impl AppConsoleExt for App {
+        fn add_console_command(&mut self, command: impl ConsoleCommand + 'static) -> &mut Self {
+            // plugins can add commands before or after the console plugin is added
+            self.world_mut()
+                .get_resource_or_insert_with(ConsoleCommands::default)
+                .0
+                .push(Box::new(command));
+            self
+        }
+    }
+
+    #[derive(Resource, Default)]
+    struct ConsoleCommands(Vec<Box<dyn ConsoleCommand>>);
+    #[derive(Resource, Default)]
+    struct Console {
+        open: bool,
+        input: String,
+        history: VecDeque<String>,
+        /// entered lines waiting to be run
+        pending: Vec<String>,
+    }
+    impl Console {
+        fn print(&mut self, line: String) {
+            self.history.push_back(line);
+            while self.history.len() > HISTORY_LINES {
+                self.history.pop_front();
+            }
+        }
+    }
+    #[derive(Component)]
+    struct ConsolePanel;
+
+    /// keeps the keys typed into the console from also reaching the game
+    fn hold_game_keys(mut keyboard_input: ResMut<ButtonInput<KeyCode>>) {
+        keyboard_input.reset_all();
+    }
+    fn type_console(mut keyboard_events: EventReader<KeyboardInput>, mut console: ResMut<Console>) {
+        for event in keyboard_events.read() {
+            if event.state != ButtonState::Pressed {
+                continue;
+            }
+            if event.key_code == KeyCode::Backquote {
+                console.open = !console.open;
+                continue;
+            }
+            if !console.open {
+                continue;
+            }
+            match &event.logical_key {
+                Key::Character(characters) => console.input.push_str(characters),
+                Key::Space => console.input.push(' '),
+                Key::Backspace => {
+                    console.input.pop();
+                }
+                Key::Escape => console.open = false,
+                Key::Enter => {
+                    let line = std::mem::take(&mut console.input);
+                    if !line.trim().is_empty() {
+                        console.pending.push(line);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    fn run_console_commands(world: &mut World) {
+        let pending = std::mem::take(&mut world.resource_mut::<Console>().pending);
+        if pending.is_empty() {
+            return;
+        }
+        world.resource_scope(|world, commands: Mut<ConsoleCommands>| {
+            for line in pending {
+                let words: Vec<&str> = line.split_whitespace().collect();
+                let reply = match words.split_first() {
+                    Some((&"help", _)) => Ok(commands
+                        .0
+                        .iter()
+                        .map(|command| format!("{} {}", command.name(), command.usage()))
+                        .collect::<Vec<_>>()
+                        .join(", ")),
+                    Some((name, args)) => {
+                        match commands.0.iter().find(|command| command.name() == *name) {
+                            Some(command) => {
+                                let reply = command.run(args, world);
+                                if reply.is_ok() {
+                                    world.insert_resource(CheatedRun);
+                                }
+                                reply
+                            }
+                            None => Err(format!("no command called {name}, try help")),
+                        }
+                    }
+                    None => continue,
+                };
+                let mut console = world.resource_mut::<Console>();
+                console.print(format!("> {line}"));
+                console.print(reply.unwrap_or_else(|error| format!("error: {error}")));
+            }
+        });
+    }
+    fn update_console(
+        mut commands: Commands,
+        console: Res<Console>,
+        mut panel_query: Query<(Entity, &mut Text), With<ConsolePanel>>,
+    ) {
+        let panel = panel_query.get_single_mut();
+        if !console.open {
+            if let Ok((entity, _)) = panel {
+                commands.entity(entity).despawn_recursive();
+            }
+            return;
+        }
+        let mut contents: String = console
+            .history
+            .iter()
+            .map(|line| format!("{line}\n"))
+            .collect();
+        contents.push_str(&format!("> {}_", console.input));
+        match panel {
+            Ok((_, mut text)) => {
+                if text.0 != contents {
+                    text.0 = contents;
+                }
+            }
+            Err(_) => {
+                commands.spawn((
+                    ConsolePanel,
+                    Text::new(contents),
+                    TextFont {
+                        font_size: 20.0,
+                        ..default()
+                    },
+                    TextColor(Color::srgb(0.8, 1.0, 0.8)),
+                    Node {
+                        position_type: PositionType::Absolute,
+                        top: Val::Px(0.0),
+                        left: Val::Px(0.0),
+                        width: Val::Percent(100.0),
+                        padding: UiRect::all(Val::Px(10.0)),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.8)),
+                    GlobalZIndex(20),
+                ));
+            }
+        }
+    }
+    fn close_console(
+        mut commands: Commands,
+        mut console: ResMut<Console>,
+        panel_query: Query<Entity, With<ConsolePanel>>,
+    ) {
+        console.open = false;
+        console.input.clear();
+        for entity in panel_query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+mod debug_overlay {
+    use super::{GameState, RotatableCamera, TXT_CLR};
+    use crate::cli::debug_tools;
+    use crate::difficulty::Obstacle;
+    use crate::dispatch::{NavGrid, WideNavGrid, CELL, DELIVERY_SPOTS};
+    use crate::game::{
+        BuildingModel, BuildingSlot, CheckPointCube, PeopleModel, PlayerModel,
+        BUILDING_CRASH_RADIUS, BUILDING_MODELS, WALL_RADIUS,
+    };
+    use crate::upgrades::Progress;
+    use bevy::{
+        diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin},
+        ecs::system::SystemParam,
+        input::ButtonInput,
+        prelude::*,
+        utils::HashMap,
+    };
+    use std::f32::consts::FRAC_PI_2;
+
+    // This plugin is an overlay for tuning the game, toggled with F3 during a run when the game is
+    // started with --debug. It draws the distances collisions and pickups happen at, the ring
+    // wall and the navigation grid, puts a label on everything that matters, and shows the frame
+    // rate along with how many of each kind of entity there are
+    pub fn debug_overlay_plugin(app: &mut App) {
+        app.init_resource::<DebugOverlay>()
+            .init_gizmo_group::<DebugGizmos>()
+            .add_systems(Startup, configure_debug_gizmos)
+            .add_systems(
+                Update,
+                (
+                    toggle_overlay.run_if(debug_tools),
+                    (
+                        draw_collision_gizmos,
+                        draw_nav_gizmos,
+                        update_labels,
+                        update_panel,
+                    )
+                        .run_if(|overlay: Res<DebugOverlay>| overlay.shown),
+                    hide_overlay.run_if(|overlay: Res<DebugOverlay>| !overlay.shown),
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Game)),
+            )
+            .add_systems(OnExit(GameState::Game), hide_overlay);
+    }
+
+    /// lifted off the ground a little so the circles aren't lost in the road
+    const GIZMO_HEIGHT: f32 = 1.5;
+    const COLLISION_COLOR: Color = Color::srgb(1.0, 0.2, 0.2);
+    const TRIGGER_COLOR: Color = Color::srgb(0.2, 1.0, 0.4);
+    const BLOCKED_COLOR: Color = Color::srgba(1.0, 0.5, 0.1, 0.35);
+    const WIDE_BLOCKED_COLOR: Color = Color::srgba(1.0, 0.9, 0.1, 0.25);
+
+    #[derive(Resource, Default)]
+    struct DebugOverlay {
+        shown: bool,
+    }
+    #[derive(Default, Reflect, GizmoConfigGroup)]
+    struct DebugGizmos;
+    #[derive(Component)]
+    struct DebugPanel;
+    /// names the entity it follows on screen
+    #[derive(Component)]
+    struct DebugLabel(Entity);
+    /// everything the robot picks up from or drops off at by driving close
+    type TriggerQuery<'w, 's> =
+        Query<'w, 's, &'static Transform, Or<(With<PeopleModel>, With<CheckPointCube>)>>;
+    /// everything that gets a label, with what's needed to name it
+    type LabelTargetQuery<'w, 's> = Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static GlobalTransform,
+            Option<&'static BuildingSlot>,
+            Option<&'static CheckPointCube>,
+            Option<&'static Obstacle>,
+            Has<PlayerModel>,
+        ),
+        Or<(
+            With<PlayerModel>,
+            With<PeopleModel>,
+            With<BuildingModel>,
+            With<CheckPointCube>,
+            With<Obstacle>,
+        )>,
+    >;
+    type OverlayQuery<'w, 's> = Query<'w, 's, Entity, Or<(With<DebugPanel>, With<DebugLabel>)>>;
+
+    /// how many entities there are of each kind the game keeps track of
+    #[derive(SystemParam)]
+    struct EntityCounts<'w, 's> {
+        all: Query<'w, 's, ()>,
+        robots: Query<'w, 's, (), With<PlayerModel>>,
+        people: Query<'w, 's, (), With<PeopleModel>>,
+        buildings: Query<'w, 's, (), With<BuildingModel>>,
+        drop_offs: Query<'w, 's, (), With<CheckPointCube>>,
+        obstacles: Query<'w, 's, (), With<Obstacle>>,
+    }
+    impl EntityCounts<'_, '_> {
+        fn lines(&self) -> String {
+            format!(
+                "entities: {}\nrobot: {}\npeople: {}\nbuildings: {}\ndrop-offs: {}\nobstacles: {}",
+                self.all.iter().count(),
+                self.robots.iter().count(),
+                self.people.iter().count(),
+                self.buildings.iter().count(),
+                self.drop_offs.iter().count(),
+                self.obstacles.iter().count()
+            )
+        }
+    }
+
+    fn configure_debug_gizmos(mut config_store: ResMut<GizmoConfigStore>) {
+        let (config, _) = config_store.config_mut::<DebugGizmos>();
+        config.line_width = 2.0;
+        // drawn over the buildings so nothing is hidden behind them
+        config.depth_bias = -1.0;
+    }
+    fn toggle_overlay(
+        keyboard_input: Res<ButtonInput<KeyCode>>,
+        mut overlay: ResMut<DebugOverlay>,
+    ) {
+        if keyboard_input.just_pressed(KeyCode::F3) {
+            overlay.shown = !overlay.shown;
+        }
+    }
+    /// a flat circle on the ground around a point
+    fn ground_circle(gizmos: &mut Gizmos<DebugGizmos>, center: Vec3, radius: f32, color: Color) {
+        let isometry = Isometry3d::new(
+            center.with_y(GIZMO_HEIGHT),
+            Quat::from_rotation_x(FRAC_PI_2),
+        );
+        gizmos.circle(isometry, radius, color).resolution(48);
+    }
+    fn draw_collision_gizmos(
+        mut gizmos: Gizmos<DebugGizmos>,
+        progress: Res<Progress>,
+        player_query: Query<&Transform, With<PlayerModel>>,
+        building_query: Query<&Transform, With<BuildingModel>>,
+        trigger_query: TriggerQuery,
+        obstacle_query: Query<(&Transform, &Obstacle)>,
+    ) {
+        // the ring wall
+        ground_circle(&mut gizmos, Vec3::ZERO, WALL_RADIUS, COLLISION_COLOR);
+        for transform in building_query.iter() {
+            ground_circle(
+                &mut gizmos,
+                transform.translation,
+                BUILDING_CRASH_RADIUS,
+                COLLISION_COLOR,
+            );
+        }
+        for (transform, obstacle) in obstacle_query.iter() {
+            ground_circle(
+                &mut gizmos,
+                transform.translation,
+                obstacle.radius(),
+                COLLISION_COLOR,
+            );
+        }
+        // senders and drop-offs are reached from this far away
+        for transform in trigger_query.iter() {
+            ground_circle(
+                &mut gizmos,
+                transform.translation,
+                progress.magnet_range(),
+                TRIGGER_COLOR,
+            );
+        }
+        if let Ok(transform) = player_query.get_single() {
+            let isometry = Isometry3d::from_translation(transform.translation.with_y(GIZMO_HEIGHT));
+            gizmos.cross(isometry, 4.0, TRIGGER_COLOR);
+        }
+    }
+    fn draw_nav_gizmos(
+        mut gizmos: Gizmos<DebugGizmos>,
+        grid: Option<Res<NavGrid>>,
+        wide_grid: Option<Res<WideNavGrid>>,
+    ) {
+        let flat = Quat::from_rotation_x(FRAC_PI_2);
+        if let Some(grid) = grid {
+            for center in grid.blocked_cells() {
+                let isometry = Isometry3d::new(center.with_y(GIZMO_HEIGHT), flat);
+                gizmos.rect(isometry, Vec2::splat(CELL * 0.8), BLOCKED_COLOR);
+            }
+            // the extra cells an oversized parcel can't get through
+            if let Some(wide_grid) = wide_grid {
+                for center in wide_grid.blocked_cells() {
+                    if !grid.blocks(center) {
+                        let isometry = Isometry3d::new(center.with_y(GIZMO_HEIGHT), flat);
+                        gizmos.rect(isometry, Vec2::splat(CELL * 0.5), WIDE_BLOCKED_COLOR);
+                    }
+                }
+            }
+        }
+        for spot in DELIVERY_SPOTS {
+            let isometry = Isometry3d::from_translation(spot.with_y(GIZMO_HEIGHT));
+            gizmos.cross(isometry, 3.0, TRIGGER_COLOR);
+        }
+    }
+    fn update_labels(
+        mut commands: Commands,
+        camera_query: Query<(&Camera, &GlobalTransform), With<RotatableCamera>>,
+        target_query: LabelTargetQuery,
+        mut label_query: Query<(Entity, &DebugLabel, &mut Node, &mut Visibility)>,
+    ) {
+        let Ok((camera, camera_transform)) = camera_query.get_single() else {
+            return;
+        };
+        let mut labels: HashMap<Entity, Entity> = HashMap::default();
+        for (label, target, _, _) in label_query.iter() {
+            if target_query.contains(target.0) {
+                labels.insert(target.0, label);
+            } else {
+                commands.entity(label).despawn_recursive();
+            }
+        }
+        for (entity, transform, slot, cube, obstacle, robot) in target_query.iter() {
+            let spot = camera
+                .world_to_viewport(camera_transform, transform.translation() + Vec3::Y * 15.0)
+                .ok();
+            let Some(&label) = labels.get(&entity) else {
+                let name = match (slot, cube, obstacle) {
+                    (Some(slot), _, _) => format!(
+                        "building {} {}",
+                        slot.0,
+                        BUILDING_MODELS[slot.0]
+                            .trim_start_matches("Models/")
+                            .trim_end_matches(".glb#Scene0")
+                    ),
+                    (_, Some(cube), _) => format!("drop-off ({})", cube.parcel().label()),
+                    (_, _, Some(obstacle)) => obstacle.label().to_string(),
+                    _ if robot => "robot".to_string(),
+                    _ => "sender".to_string(),
+                };
+                commands.spawn((
+                    DebugLabel(entity),
+                    Text::new(format!("{name} #{}", entity.index())),
+                    TextFont {
+                        font_size: 14.0,
+                        ..default()
+                    },
+                    TextColor(TXT_CLR),
+                    Node {
+                        position_type: PositionType::Absolute,
+                        ..default()
+                    },
+                    Visibility::Hidden,
+                ));
+                continue;
+            };
+            let Ok((_, _, mut node, mut visibility)) = label_query.get_mut(label) else {
+                continue;
+            };
+            match spot {
+                Some(spot) => {
+                    node.left = Val::Px(spot.x);
+                    node.top = Val::Px(spot.y);
+                    visibility.set_if_neq(Visibility::Inherited);
+                }
+                None => {
+                    visibility.set_if_neq(Visibility::Hidden);
+                }
+            }
+        }
+    }
+    fn update_panel(
+        mut commands: Commands,
+        diagnostics: Res<DiagnosticsStore>,
+        counts: EntityCounts,
+        mut panel_query: Query<&mut Text, With<DebugPanel>>,
+    ) {
+        let fps = diagnostics
+            .get(&FrameTimeDiagnosticsPlugin::FPS)
+            .and_then(|fps| fps.smoothed())
+            .unwrap_or_default();
+        let frame_time = diagnostics
+            .get(&FrameTimeDiagnosticsPlugin::FRAME_TIME)
+            .and_then(|frame_time| frame_time.smoothed())
+            .unwrap_or_default();
+        let contents = format!(
+            "fps: {fps:.0}\nframe time: {frame_time:.2} ms\n{}",
+            counts.lines()
+        );
+        let Ok(mut text) = panel_query.get_single_mut() else {
+            commands.spawn((
+                DebugPanel,
+                Text::new(contents),
+                TextFont {
+                    font_size: 18.0,
+                    ..default()
+                },
+                TextColor(TXT_CLR),
+                Node {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(10.0),
+                    right: Val::Px(10.0),
+                    padding: UiRect::all(Val::Px(8.0)),
+                    ..default()
+                },
+                BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.7)),
+                GlobalZIndex(15),
+            ));
+            return;
+        };
+        if text.0 != contents {
+            text.0 = contents;
+        }
+    }
+    fn hide_overlay(mut commands: Commands, overlay_query: OverlayQuery) {
+        for entity in overlay_query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+mod replay {
+    use super::{Difficulty, GameMode, GameState, RoundLength, TimeOfDay, WeatherChoice};
+    use crate::cli::LaunchOptions;
+    use crate::game::{keeps_run, LastRun, RunInProgress, RunSeed, SelectedMap, MAPS};
+    use crate::upgrades::{Progress, UPGRADES};
+    use bevy::{
+        app::AppExit,
+        input::{ButtonInput, InputSystem},
+        prelude::*,
+        time::TimeUpdateStrategy,
+    };
+    use std::{fs, path::Path, time::Duration};
+
+    // This plugin records every run so it can be watched again with --replay. A replay keeps
+    // the run's seed, settings and upgrade levels along with the keys held down and the length
+    // of every frame, and plays back by feeding those same keys and frame lengths to the game.
+    // It only plays on a profile with the same upgrades, and nothing from a run played back is
+    // saved. Pausing clears the island away and puts it back, which can't be played back the
+    // same, so a run that was paused is saved but won't play
+    pub fn replay_plugin(app: &mut App) {
+        app.init_resource::<ReplayRecorder>()
+            .init_resource::<ReplayPlayer>()
+            .add_systems(PreUpdate, play_keys.after(InputSystem).run_if(playing))
+            .add_systems(OnEnter(GameState::Pause), mark_paused)
+            .add_systems(
+                Update,
+                (
+                    start_recording.run_if(resource_added::<RunInProgress>.and(not(playing))),
+                    // console commands aren't recorded, so a cheated run wouldn't play back
+                    save_recording.run_if(resource_exists_and_changed::<LastRun>.and(keeps_run)),
+                )
+                    .chain(),
+            )
+            .add_systems(
+                Last,
+                (
+                    record_frame
+                        .run_if(in_state(GameState::Game).and(resource_exists::<RunInProgress>)),
+                    advance_playback.run_if(playing),
+                ),
+            );
+    }
+
+    /// there while a replay is played and until the next run starts, so none of it is saved
+    #[derive(Resource)]
+    pub struct ReplayRun;
+
+    /// where the last run's replay is saved
+    pub const LAST_REPLAY_FILE: &str = "saves/replays/last.replay";
+    /// every key the game reads during a run, bar Escape since a replay doesn't pause
+    const REPLAY_KEYS: [KeyCode; 17] = [
+        KeyCode::KeyW,
+        KeyCode::KeyA,
+        KeyCode::KeyS,
+        KeyCode::KeyD,
+        KeyCode::ArrowUp,
+        KeyCode::ArrowDown,
+        KeyCode::ArrowLeft,
+        KeyCode::ArrowRight,
+        KeyCode::KeyC,
+        KeyCode::KeyO,
+        KeyCode::KeyJ,
+        KeyCode::Digit1,
+        KeyCode::Digit2,
+        KeyCode::Digit3,
+        KeyCode::Digit4,
+        KeyCode::ShiftLeft,
+        KeyCode::ShiftRight,
+    ];
+
+    /// one frame of a run, how long it lasted and which of `REPLAY_KEYS` were held, one bit each
+    struct ReplayFrame {
+        secs: f32,
+        keys: u32,
+    }
+    /// everything needed to play a run again
+    pub struct Replay {
+        pub seed: u64,
+        pub mode: GameMode,
+        pub round_length: RoundLength,
+        pub difficulty: Difficulty,
+        pub map: SelectedMap,
+        pub time_of_day: TimeOfDay,
+        pub weather: WeatherChoice,
+        /// the level of each upgrade, in `UPGRADES` order
+        upgrades: [u32; UPGRADES.len()],
+        /// whether the run was paused at some point
+        paused: bool,
+        frames: Vec<ReplayFrame>,
+    }
+    impl Replay {
+        /// whether the run was recorded with the upgrades the profile has now
+        pub fn has_upgrades_of(&self, progress: &Progress) -> bool {
+            self.upgrades == UPGRADES.map(|upgrade| progress.level(upgrade))
+        }
+        pub fn load(path: &str) -> Result<Self, String> {
+            let contents = fs::read_to_string(path)
+                .map_err(|error| format!("could not read {path}: {error}"))?;
+            let (header, frames) = contents
+                .split_once("frames\n")
+                .ok_or(format!("{path} isn't a replay"))?;
+            let values: Vec<(&str, &str)> = header
+                .lines()
+                .filter_map(|line| line.split_once('='))
+                .collect();
+            let get = |key: &str| {
+                values
+                    .iter()
+                    .find(|(name, _)| *name == key)
+                    .map(|(_, value)| value.trim())
+                    .ok_or(format!("{path} is missing {key}"))
+            };
+            if get("paused") == Ok("true") {
+                return Err(format!(
+                    "{path} was paused partway, so it can't be played back"
+                ));
+            }
+            let mode = match get("mode")? {
+                "Timed" => GameMode::Timed,
+                "Endless" => GameMode::Endless,
+                "Zen" => GameMode::Zen,
+                "Survival" => GameMode::Survival,
+                other => return Err(format!("unknown mode {other} in {path}")),
+            };
+            let difficulty = match get("difficulty")? {
+                "Easy" => Difficulty::Easy,
+                "Normal" => Difficulty::Normal,
+                "Hard" => Difficulty::Hard,
+                other => return Err(format!("unknown difficulty {other} in {path}")),
+            };
+            let time_of_day = match get("time_of_day")? {
+                "Cycle" => TimeOfDay::Cycle,
+                "Dawn" => TimeOfDay::Dawn,
+                "Noon" => TimeOfDay::Noon,
+                "Dusk" => TimeOfDay::Dusk,
+                "Night" => TimeOfDay::Night,
+                other => return Err(format!("unknown time of day {other} in {path}")),
+            };
+            let weather = match get("weather")? {
+                "Forecast" => WeatherChoice::Forecast,
+                "Clear" => WeatherChoice::Clear,
+                "Rain" => WeatherChoice::Rain,
+                "Fog" => WeatherChoice::Fog,
+                "Wind" => WeatherChoice::Wind,
+                other => return Err(format!("unknown weather {other} in {path}")),
+            };
+            let map = get("map")?;
+            let map = MAPS
+                .iter()
+                .position(|info| info.name == map)
+                .ok_or(format!("unknown map {map} in {path}"))?;
+            let mut upgrades = [0; UPGRADES.len()];
+            for (level, upgrade) in upgrades.iter_mut().zip(UPGRADES) {
+                *level = get(&format!("upgrade.{}", upgrade.key()))?
+                    .parse()
+                    .map_err(|_| format!("bad {} level in {path}", upgrade.key()))?;
+            }
+            let frames = frames
+                .lines()
+                .map(|line| {
+                    let (secs, keys) = line.split_once(' ')?;
+                    Some(ReplayFrame {
+                        secs: secs.parse().ok()?,
+                        keys: keys.parse().ok()?,
+                    })
+                })
+                .collect::<Option<Vec<_>>>()
+                .ok_or(format!("{path} has a broken frame"))?;
+            Ok(Replay {
+                seed: get("seed")?
+                    .parse()
+                    .map_err(|_| format!("bad seed in {path}"))?,
+                mode,
+                round_length: RoundLength(
+                    get("round_length")?
+                        .parse()
+                        .map_err(|_| format!("bad round length in {path}"))?,
+                ),
+                difficulty,
+                map: SelectedMap(map),
+                time_of_day,
+                weather,
+                upgrades,
+                paused: false,
+                frames,
+            })
+        }
+        fn save(&self, path: &str) {
+            if let Some(parent) = Path::new(path).parent() {
+                let _ = fs::create_dir_all(parent);
+            }
+            let mut contents = format!(
+                "seed={}\nmode={:?}\nround_length={}\ndifficulty={:?}\nmap={}\ntime_of_day={:?}\n\
+                 weather={:?}\npaused={}\n",
+                self.seed,
+                self.mode,
+                self.round_length.0,
+                self.difficulty,
+                self.map.info().name,
+                self.time_of_day,
+                self.weather,
+                self.paused
+            );
+            for (level, upgrade) in self.upgrades.iter().zip(UPGRADES) {
+                contents.push_str(&format!("upgrade.{}={level}\n", upgrade.key()));
+            }
+            contents.push_str("frames\n");
+            for frame in &self.frames {
+                contents.push_str(&format!("{} {}\n", frame.secs, frame.keys));
+            }
+            if let Err(error) = fs::write(path, contents) {
+                warn!("could not save {path}: {error}");
+            }
+        }
+    }
+
+    /// the run being recorded, if there is one
+    #[derive(Resource, Default)]
+    struct ReplayRecorder(Option<Replay>);
+    /// the replay being played, and the frame it's up to
+    #[derive(Resource, Default)]
+    pub struct ReplayPlayer {
+        replay: Option<Replay>,
+        /// the frame about to run, or None before the first one
+        frame: Option<usize>,
+    }
+    impl ReplayPlayer {
+        /// starts playing a replay, the run itself has to be started from its settings too
+        pub fn play(&mut self, replay: Replay) {
+            self.replay = Some(replay);
+            self.frame = None;
+        }
+        fn current(&self) -> Option<&ReplayFrame> {
+            self.replay.as_ref()?.frames.get(self.frame?)
+        }
+    }
+    fn playing(player: Res<ReplayPlayer>) -> bool {
+        player.replay.is_some()
+    }
+
+    fn start_recording(
+        mut commands: Commands,
+        mut recorder: ResMut<ReplayRecorder>,
+        (run_seed, progress): (Res<RunSeed>, Res<Progress>),
+        (game_mode, round_length, difficulty, selected_map): (
+            Res<GameMode>,
+            Res<RoundLength>,
+            Res<Difficulty>,
+            Res<SelectedMap>,
+        ),
+        (time_of_day, weather): (Res<TimeOfDay>, Res<WeatherChoice>),
+    ) {
+        recorder.0 = Some(Replay {
+            seed: run_seed.current,
+            mode: *game_mode,
+            round_length: *round_length,
+            difficulty: *difficulty,
+            map: *selected_map,
+            time_of_day: *time_of_day,
+            weather: *weather,
+            upgrades: UPGRADES.map(|upgrade| progress.level(upgrade)),
+            paused: false,
+            frames: Vec::new(),
+        });
+        // a run of the player's own after a replay is kept again
+        commands.remove_resource::<ReplayRun>();
+    }
+    fn mark_paused(mut recorder: ResMut<ReplayRecorder>) {
+        if let Some(replay) = recorder.0.as_mut() {
+            replay.paused = true;
+        }
+    }
+    fn record_frame(
+        mut recorder: ResMut<ReplayRecorder>,
+        time: Res<Time>,
+        keyboard_input: Res<ButtonInput<KeyCode>>,
+    ) {
+        let Some(replay) = recorder.0.as_mut() else {
+            return;
+        };
+        let keys = REPLAY_KEYS
+            .iter()
+            .enumerate()
+            .filter(|(_, key)| keyboard_input.pressed(**key))
+            .fold(0, |keys, (bit, _)| keys | 1 << bit);
+        replay.frames.push(ReplayFrame {
+            secs: time.delta_secs(),
+            keys,
+        });
+    }
+    fn save_recording(mut recorder: ResMut<ReplayRecorder>) {
+        if let Some(replay) = recorder.0.take() {
+            replay.save(LAST_REPLAY_FILE);
+        }
+    }
+    /// swaps the keys actually held for the ones held in the replay
+    fn play_keys(
+        player: Res<ReplayPlayer>,
+        mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+        mut held: Local<u32>,
+    ) {
+        let Some(frame) = player.current() else {
+            return;
+        };
+        keyboard_input.reset_all();
+        for (bit, key) in REPLAY_KEYS.iter().enumerate() {
+            if frame.keys & 1 << bit != 0 {
+                keyboard_input.press(*key);
+                // only the first frame a key is held counts as pressing it
+                if *held & 1 << bit != 0 {
+                    keyboard_input.clear_just_pressed(*key);
+                }
+            }
+        }
+        *held = frame.keys;
+    }
+    /// moves on to the next frame and sets how long it will be, ending the replay when the frames
+    /// run out or the run does
+    fn advance_playback(
+        mut player: ResMut<ReplayPlayer>,
+        mut time_update: ResMut<TimeUpdateStrategy>,
+        state: Res<State<GameState>>,
+        options: Res<LaunchOptions>,
+        mut exit_events: EventWriter<AppExit>,
+    ) {
+        let next = player.frame.map_or(0, |frame| frame + 1);
+        let run_over = player.frame.is_some() && *state.get() != GameState::Game;
+        player.frame = Some(next);
+        match player.current().map(|frame| frame.secs) {
+            Some(secs) if !run_over => {
+                *time_update = TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(secs));
+            }
+            _ => {
+                info!("replay finished after {next} frames");
+                player.replay = None;
+                *time_update = TimeUpdateStrategy::Automatic;
+                if options.headless {
+                    exit_events.send(AppExit::Success);
+                }
+            }
+        }
+    }
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn sample() -> Replay {
+            Replay {
+                seed: 42,
+                mode: GameMode::Survival,
+                round_length: RoundLength(120),
+                difficulty: Difficulty::Hard,
+                map: SelectedMap(0),
+                time_of_day: TimeOfDay::Night,
+                weather: WeatherChoice::Rain,
+                upgrades: [2, 0, 5, 1, 3],
+                paused: false,
+                frames: vec![
+                    ReplayFrame {
+                        secs: 1.0 / 60.0,
+                        keys: 0,
+                    },
+                    ReplayFrame {
+                        secs: 0.016_823,
+                        keys: 1 | 1 << 16,
+                    },
+                ],
+            }
+        }
+        /// a file of its own in the temp folder for each test, since they run side by side
+        fn temp_path(name: &str) -> String {
+            std::env::temp_dir()
+                .join(format!("mail-replay-{}-{name}.replay", std::process::id()))
+                .to_string_lossy()
+                .into_owned()
+        }
+        fn load_text(name: &str, contents: &str) -> Result<Replay, String> {
+            let path = temp_path(name);
+            fs::write(&path, contents).unwrap();
+            let replay = Replay::load(&path);
+            let _ = fs::remove_file(&path);
+            replay
+        }
+        const HEADER: &str = "seed=7\nmode=Timed\nround_length=60\ndifficulty=Easy\nmap=island\n\
+            time_of_day=Dawn\nweather=Clear\nupgrade.speed=0\nupgrade.handling=0\n\
+            upgrade.battery=0\nupgrade.cargo=0\nupgrade.magnet=0\n";
+
+        #[test]
+        fn save_then_load_gives_back_the_same_replay() {
+            let path = temp_path("round-trip");
+            let saved = sample();
+            saved.save(&path);
+            let loaded = Replay::load(&path);
+            let _ = fs::remove_file(&path);
+            let loaded = loaded.unwrap();
+            assert_eq!(loaded.seed, saved.seed);
+            assert_eq!(loaded.mode, saved.mode);
+            assert_eq!(loaded.round_length, saved.round_length);
+            assert_eq!(loaded.difficulty, saved.difficulty);
+            assert_eq!(loaded.map.0, saved.map.0);
+            assert_eq!(loaded.time_of_day, saved.time_of_day);
+            assert_eq!(loaded.weather, saved.weather);
+            assert_eq!(loaded.upgrades, saved.upgrades);
+            assert_eq!(loaded.frames.len(), saved.frames.len());
+            for (loaded, saved) in loaded.frames.iter().zip(&saved.frames) {
+                assert_eq!(loaded.secs, saved.secs);
+                assert_eq!(loaded.keys, saved.keys);
+            }
+        }
+        #[test]
+        fn a_paused_replay_is_refused() {
+            let path = temp_path("paused");
+            let mut replay = sample();
+            replay.paused = true;
+            replay.save(&path);
+            let loaded = Replay::load(&path);
+            let _ = fs::remove_file(&path);
+            assert!(loaded.err().unwrap_or_default().contains("paused"));
+        }
+        #[test]
+        fn a_replay_only_matches_the_upgrades_it_was_recorded_with() {
+            let progress = Progress::default();
+            let replay = load_text("no-upgrades", &format!("{HEADER}frames\n")).unwrap();
+            assert!(replay.has_upgrades_of(&progress));
+            assert!(!sample().has_upgrades_of(&progress));
+        }
+        #[test]
+        fn a_replay_with_no_frames_loads() {
+            let replay = load_text("no-frames", &format!("{HEADER}frames\n")).unwrap();
+            assert!(replay.frames.is_empty());
+        }
+        #[test]
+        fn broken_replays_are_refused() {
+            let cases = [
+                ("missing-file", None, "could not read"),
+                (
+                    "not-a-replay",
+                    Some("hello\n".to_string()),
+                    "isn't a replay",
+                ),
+                (
+                    "missing-key",
+                    Some("seed=7\nframes\n".to_string()),
+                    "is missing",
+                ),
+                (
+                    "bad-seed",
+                    Some(HEADER.replace("seed=7", "seed=seven") + "frames\n"),
+                    "bad seed",
+                ),
+                (
+                    "bad-mode",
+                    Some(HEADER.replace("Timed", "Sprint") + "frames\n"),
+                    "unknown mode",
+                ),
+                (
+                    "bad-map",
+                    Some(HEADER.replace("island", "moon") + "frames\n"),
+                    "unknown map",
+                ),
+                (
+                    "bad-weather",
+                    Some(HEADER.replace("Clear", "Snow") + "frames\n"),
+                    "unknown weather",
+                ),
+                (
+                    "bad-upgrade",
+                    Some(HEADER.replace("cargo=0", "cargo=lots") + "frames\n"),
+                    "bad cargo level",
+                ),
+                (
+                    "no-upgrades",
+                    Some(HEADER.replace("upgrade.magnet=0\n", "") + "frames\n"),
+                    "is missing upgrade.magnet",
+                ),
+                (
+                    "bad-frame",
+                    Some(format!("{HEADER}frames\n0.016 1\n0.016\n")),
+                    "broken frame",
+                ),
+                (
+                    "bad-keys",
+                    Some(format!("{HEADER}frames\n0.016 -1\n")),
+                    "broken frame",
+                ),
+            ];
+            for (name, contents, error) in cases {
+                let loaded = match contents {
+                    Some(contents) => load_text(name, &contents),
+                    None => Replay::load(&temp_path(name)),
+                };
+                let message = loaded.err().unwrap_or_default();
+                assert!(message.contains(error), "{name}: {message:?}");
+            }
+        }
+    }
+}
+
+mod cli {
+    use super::{GameMode, GameState, RoundLength, TimeOfDay, WeatherChoice};
+    use crate::game::{LastRun, RunSeed, SelectedMap, MAPS};
+    use crate::replay::{Replay, ReplayPlayer, ReplayRun};
+    use crate::upgrades::Progress;
+    use bevy::{
+        app::{PluginGroupBuilder, ScheduleRunnerPlugin},
+        prelude::*,
+        render::{
+            settings::{RenderCreation, WgpuSettings},
+            RenderPlugin,
+        },
+        window::{ExitCondition, MonitorSelection, PresentMode, WindowMode, WindowTheme},
+        winit::WinitPlugin,
+    };
+    use std::time::Duration;
+
+    // This plugin carries out the options the game was launched with. They're read in `main`
+    // before the app is built, since the window can't change afterwards, and the rest are put
+    // into effect once the profile and its settings have loaded. Settings they change only last
+    // for the run they start, the player's saved ones come back once it's over
+    pub fn cli_plugin(app: &mut App) {
+        app.add_systems(PostUpdate, apply_launch_options.run_if(run_once))
+            .add_systems(
+                Update,
+                end_launch_override.run_if(
+                    resource_exists::<LaunchOverride>.and(resource_exists_and_changed::<LastRun>),
+                ),
+            );
+    }
+
+    pub const USAGE: &str = "\
+Usage: The-Future-of-Mail [OPTIONS]
+
+Options:
+  --resolution WIDTHxHEIGHT  window size, 1280x720 by default
+  --fullscreen               fill the screen instead of opening a window
+  --vsync, --no-vsync        wait for the display between frames or not, on by default
+  --skip-splash              go straight to the profile screen
+  --mode MODE                start a run straight away: timed, endless, zen or survival
+  --map NAME                 start a run straight away on this map
+  --seed N                   start a run straight away from this seed
+  --replay FILE              play a recorded run, the last run is saved to saves/replays/last.replay
+  --headless                 run without a window or rendering, it quits when a replay ends
+  --debug                    turn on the developer console (`) and debug overlay (F3)
+  -h, --help                 show this help";
+
+    /// how the game was asked to start
+    #[derive(Resource)]
+    pub struct LaunchOptions {
+        pub resolution: (u32, u32),
+        pub fullscreen: bool,
+        pub vsync: bool,
+        pub skip_splash: bool,
+        pub mode: Option<GameMode>,
+        pub map: Option<usize>,
+        pub seed: Option<u64>,
+        pub replay: Option<String>,
+        pub headless: bool,
+        pub debug: bool,
+    }
+    impl Default for LaunchOptions {
+        fn default() -> Self {
+            Self {
+                resolution: (1280, 720),
+                fullscreen: false,
+                vsync: true,
+                skip_splash: false,
+                mode: None,
+                map: None,
+                seed: None,
+                replay: None,
+                headless: false,
+                debug: false,
+            }
+        }
+    }
+    impl LaunchOptions {
+        /// reads the command line, or None when it asks for the help
+        pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Option<Self>, String> {
+            let mut options = Self::default();
+            while let Some(arg) = args.next() {
+                // values can be given as `--flag value` or `--flag=value`
+                let (flag, inline) = match arg.split_once('=') {
+                    Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                    None => (arg, None),
+                };
+                let mut value = || {
+                    inline
+                        .clone()
+                        .or_else(|| args.next())
+                        .ok_or(format!("{flag} needs a value"))
+                };
+                match flag.as_str() {
+                    "-h" | "--help" => return Ok(None),
+                    "--resolution" => {
+                        let size = value()?;
+                        options.resolution = size
+                            .split_once('x')
+                            .and_then(|(width, height)| {
+                                Some((width.parse().ok()?, height.parse().ok()?))
+                            })
+                            .filter(|(width, height)| *width > 0 && *height > 0)
+                            .ok_or(format!("{size} isn't a resolution like 1920x1080"))?;
                     }
-                    MenuButtonAction::BackToMainMenu => {
-                        game_state.set(GameState::Menu);
-                        menu_state.set(MenuState::Main);
+                    "--fullscreen" => options.fullscreen = true,
+                    "--vsync" => options.vsync = true,
+                    "--no-vsync" => options.vsync = false,
+                    "--skip-splash" => options.skip_splash = true,
+                    "--mode" => {
+                        let mode = value()?;
+                        options.mode = Some(match mode.to_lowercase().as_str() {
+                            "timed" => GameMode::Timed,
+                            "endless" => GameMode::Endless,
+                            "zen" => GameMode::Zen,
+                            "survival" => GameMode::Survival,
+                            _ => return Err(format!("unknown mode {mode}")),
+                        });
                     }
-                    MenuButtonAction::Help => {
-                        game_state.set(GameState::Help);
-                        menu_state.set(MenuState::Help);
+                    "--map" => {
+                        let map = value()?;
+                        options.map = Some(
+                            MAPS.iter()
+                                .position(|info| info.name == map)
+                                .ok_or(format!("unknown map {map}"))?,
+                        );
+                    }
+                    "--seed" => {
+                        let seed = value()?;
+                        options.seed =
+                            Some(seed.parse().map_err(|_| format!("{seed} isn't a seed"))?);
                     }
+                    "--replay" => options.replay = Some(value()?),
+                    "--headless" => options.headless = true,
+                    "--debug" => options.debug = true,
+                    _ => return Err(format!("unknown option {flag}")),
+                }
+                if inline.is_some()
+                    && !matches!(
+                        flag.as_str(),
+                        "--resolution" | "--mode" | "--map" | "--seed" | "--replay"
+                    )
+                {
+                    return Err(format!("{flag} doesn't take a value"));
+                }
+            }
+            Ok(Some(options))
+        }
+        /// whether the options ask for a run to start without going through the menus
+        fn starts_run(&self) -> bool {
+            self.mode.is_some() || self.map.is_some() || self.seed.is_some()
+        }
+        /// Bevy's plugins set up for a window of the size asked for, or for no window and no
+        /// rendering at all when headless
+        pub fn default_plugins(&self) -> PluginGroupBuilder {
+            if self.headless {
+                return DefaultPlugins
+                    .build()
+                    .set(WindowPlugin {
+                        primary_window: None,
+                        exit_condition: ExitCondition::DontExit,
+                        ..default()
+                    })
+                    .set(RenderPlugin {
+                        render_creation: RenderCreation::Automatic(WgpuSettings {
+                            backends: None,
+                            ..default()
+                        }),
+                        ..default()
+                    })
+                    .disable::<WinitPlugin>()
+                    .add(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(
+                        1.0 / 60.0,
+                    )));
+            }
+            DefaultPlugins.set(WindowPlugin {
+                primary_window: Some(Window {
+                    title: "The Future of Mail".into(),
+                    name: Some("bevy.app".into()),
+                    resolution: (self.resolution.0 as f32, self.resolution.1 as f32).into(),
+                    present_mode: if self.vsync {
+                        PresentMode::AutoVsync
+                    } else {
+                        PresentMode::AutoNoVsync
+                    },
+                    mode: if self.fullscreen {
+                        WindowMode::BorderlessFullscreen(MonitorSelection::Current)
+                    } else {
+                        WindowMode::Windowed
+                    },
+                    window_theme: Some(WindowTheme::Dark),
+                    ..default()
+                }),
+                ..default()
+            })
+        }
+    }
+
+    /// there while the settings hold values from the command line or a replay instead of the
+    /// player's own, so they don't get saved over them
+    #[derive(Resource)]
+    pub struct LaunchOverride;
+
+    /// a run condition for the developer tools, which are only there with --debug
+    pub fn debug_tools(options: Res<LaunchOptions>) -> bool {
+        options.debug
+    }
+
+    fn apply_launch_options(
+        mut commands: Commands,
+        options: Res<LaunchOptions>,
+        mut next_state: ResMut<NextState<GameState>>,
+        mut run_seed: ResMut<RunSeed>,
+        (mut player, progress): (ResMut<ReplayPlayer>, Res<Progress>),
+        (mut game_mode, mut round_length, mut difficulty, mut selected_map): (
+            ResMut<GameMode>,
+            ResMut<RoundLength>,
+            ResMut<super::Difficulty>,
+            ResMut<SelectedMap>,
+        ),
+        (mut time_of_day, mut weather): (ResMut<TimeOfDay>, ResMut<WeatherChoice>),
+    ) {
+        if let Some(path) = &options.replay {
+            let replay = Replay::load(path).and_then(|replay| {
+                if replay.has_upgrades_of(&progress) {
+                    Ok(replay)
+                } else {
+                    Err(format!(
+                        "{path} was recorded with other upgrades than this profile has, so it \
+                         wouldn't play back the same"
+                    ))
+                }
+            });
+            match replay {
+                Ok(replay) => {
+                    *game_mode = replay.mode;
+                    *round_length = replay.round_length;
+                    *difficulty = replay.difficulty;
+                    *selected_map = replay.map;
+                    *time_of_day = replay.time_of_day;
+                    *weather = replay.weather;
+                    run_seed.next = Some(replay.seed);
+                    player.play(replay);
+                    commands.insert_resource(LaunchOverride);
+                    commands.insert_resource(ReplayRun);
+                    next_state.set(GameState::Game);
+                    return;
                 }
+                Err(error) => error!("{error}"),
+            }
+        }
+        if let Some(mode) = options.mode {
+            *game_mode = mode;
+        }
+        if let Some(map) = options.map {
+            *selected_map = SelectedMap(map);
+        }
+        if options.mode.is_some() || options.map.is_some() {
+            commands.insert_resource(LaunchOverride);
+        }
+        run_seed.next = options.seed;
+        if options.starts_run() {
+            next_state.set(GameState::Game);
+        } else if options.skip_splash || options.headless {
+            next_state.set(GameState::Profiles);
+        }
+    }
+    /// the run the launch options started is over, so `settings` loads the player's own back
+    fn end_launch_override(mut commands: Commands) {
+        commands.remove_resource::<LaunchOverride>();
+    }
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn parse(args: &[&str]) -> Result<Option<LaunchOptions>, String> {
+            LaunchOptions::parse(args.iter().map(|arg| arg.to_string()))
+        }
+        fn parse_ok(args: &[&str]) -> LaunchOptions {
+            parse(args).unwrap().unwrap()
+        }
+
+        #[test]
+        fn no_options_gives_the_defaults() {
+            let options = parse_ok(&[]);
+            assert_eq!(options.resolution, (1280, 720));
+            assert!(options.vsync);
+            assert!(!options.fullscreen && !options.headless && !options.debug);
+            assert!(!options.starts_run());
+        }
+        #[test]
+        fn help_asks_for_the_usage() {
+            assert!(parse(&["--help"]).unwrap().is_none());
+            assert!(parse(&["--debug", "-h"]).unwrap().is_none());
+        }
+        #[test]
+        fn values_can_follow_or_be_joined_on() {
+            let options = parse_ok(&["--resolution", "1920x1080", "--seed=42", "--map", "island"]);
+            assert_eq!(options.resolution, (1920, 1080));
+            assert_eq!(options.seed, Some(42));
+            assert_eq!(options.map, Some(0));
+            assert!(options.starts_run());
+            let options = parse_ok(&["--resolution=800x600", "--replay=runs/best.replay"]);
+            assert_eq!(options.resolution, (800, 600));
+            assert_eq!(options.replay.as_deref(), Some("runs/best.replay"));
+        }
+        #[test]
+        fn flags_are_switched() {
+            let options = parse_ok(&[
+                "--fullscreen",
+                "--no-vsync",
+                "--skip-splash",
+                "--headless",
+                "--debug",
+            ]);
+            assert!(options.fullscreen && options.skip_splash && options.headless);
+            assert!(options.debug && !options.vsync);
+            assert!(parse_ok(&["--no-vsync", "--vsync"]).vsync);
+        }
+        #[test]
+        fn modes_are_read_in_any_case() {
+            assert_eq!(parse_ok(&["--mode", "ZEN"]).mode, Some(GameMode::Zen));
+            assert_eq!(parse_ok(&["--mode=timed"]).mode, Some(GameMode::Timed));
+        }
+        #[test]
+        fn bad_command_lines_are_refused() {
+            let cases: [(&[&str], &str); 12] = [
+                (&["--resolution", "1920"], "isn't a resolution"),
+                (&["--resolution", "0x600"], "isn't a resolution"),
+                (&["--resolution", "widexhigh"], "isn't a resolution"),
+                (&["--resolution"], "needs a value"),
+                (&["--seed"], "needs a value"),
+                (&["--seed", "lucky"], "isn't a seed"),
+                (&["--mode", "sprint"], "unknown mode"),
+                (&["--map", "moon"], "unknown map"),
+                (&["--fullscreen=yes"], "doesn't take a value"),
+                (&["--debug=1"], "doesn't take a value"),
+                (&["--turbo"], "unknown option"),
+                (&["island"], "unknown option"),
+            ];
+            for (args, error) in cases {
+                let message = parse(args).err().unwrap_or_default();
+                assert!(message.contains(error), "{args:?}: {message:?}");
+            }
+        }
+    }
+}
+
+mod settings {
+    use super::{
+        Difficulty, DisplayQuality, GameMode, InvertMouse, MouseSensitivity, RoundLength,
+        RouteGuidance, Subtitles, Telemetry, TimeOfDay, Volume, WeatherChoice,
+    };
+    use crate::camera::{CameraMode, CameraSettings, OcclusionMode};
+    use crate::cli::LaunchOverride;
+    use crate::game::SelectedMap;
+    use crate::profiles::ActiveProfile;
+    use bevy::{ecs::system::SystemParam, prelude::*};
+    use std::{collections::HashMap, fs, path::Path};
+
+    // This plugin keeps the player's preferences on disk so they carry over between sessions,
+    // they are loaded whenever a profile is picked and written back whenever one of them
+    // changes. A new profile starts out with the settings of the one before it. Nothing is
+    // saved while launch options stand in for some of them, and they're loaded again after
+    pub fn settings_plugin(app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                load_settings.run_if(
+                    resource_changed::<ActiveProfile>.or(resource_removed::<LaunchOverride>),
+                ),
+                save_settings.run_if(
+                    resource_changed::<Volume>
+                        .or(resource_changed::<DisplayQuality>)
+                        .or(resource_changed::<MouseSensitivity>)
+                        .or(resource_changed::<InvertMouse>)
+                        .or(resource_changed::<TimeOfDay>)
+                        .or(resource_changed::<WeatherChoice>)
+                        .or(resource_changed::<Subtitles>)
+                        .or(resource_changed::<GameMode>)
+                        .or(resource_changed::<RoundLength>)
+                        .or(resource_changed::<Difficulty>)
+                        .or(resource_changed::<RouteGuidance>)
+                        .or(resource_changed::<Telemetry>)
+                        .or(resource_changed::<CameraSettings>)
+                        .and(not(resource_exists::<LaunchOverride>)),
+                ),
+            )
+                .chain(),
+        );
+    }
+
+    const SETTINGS_FILE: &str = "settings.cfg";
+
+    /// every saved setting, read together so they can be written out or compared
+    #[derive(SystemParam)]
+    pub struct Preferences<'w> {
+        volume: Res<'w, Volume>,
+        display_quality: Res<'w, DisplayQuality>,
+        mouse_sensitivity: Res<'w, MouseSensitivity>,
+        invert_mouse: Res<'w, InvertMouse>,
+        subtitles: Res<'w, Subtitles>,
+        route_guidance: Res<'w, RouteGuidance>,
+        telemetry: Res<'w, Telemetry>,
+        game_mode: Res<'w, GameMode>,
+        round_length: Res<'w, RoundLength>,
+        difficulty: Res<'w, Difficulty>,
+        time_of_day: Res<'w, TimeOfDay>,
+        weather_choice: Res<'w, WeatherChoice>,
+        selected_map: Res<'w, SelectedMap>,
+        camera_settings: Res<'w, CameraSettings>,
+    }
+    impl Preferences<'_> {
+        /// the settings as they're saved, with the weather kept under the current map's name
+        pub fn entries(&self) -> Vec<(String, String)> {
+            let mut entries = vec![
+                ("volume".into(), self.volume.0.to_string()),
+                (
+                    "display_quality".into(),
+                    format!("{:?}", *self.display_quality),
+                ),
+                (
+                    "mouse_sensitivity".into(),
+                    self.mouse_sensitivity.0.to_string(),
+                ),
+                ("invert_mouse".into(), self.invert_mouse.0.to_string()),
+                ("subtitles".into(), self.subtitles.0.to_string()),
+                ("route_guidance".into(), self.route_guidance.0.to_string()),
+                ("telemetry".into(), self.telemetry.0.to_string()),
+                ("game_mode".into(), format!("{:?}", *self.game_mode)),
+                ("round_length".into(), self.round_length.0.to_string()),
+                ("difficulty".into(), format!("{:?}", *self.difficulty)),
+                ("time_of_day".into(), format!("{:?}", *self.time_of_day)),
+                (
+                    format!("weather.{}", self.selected_map.info().name),
+                    format!("{:?}", *self.weather_choice),
+                ),
+                (
+                    "camera.occlusion".into(),
+                    format!("{:?}", self.camera_settings.occlusion),
+                ),
+            ];
+            for mode in CameraMode::ALL {
+                let settings = self.camera_settings.mode(mode);
+                entries.push((
+                    format!("camera.{}.zoom", mode.key()),
+                    format!("{:.2}", settings.zoom),
+                ));
+                entries.push((
+                    format!("camera.{}.fov", mode.key()),
+                    settings.fov_degrees().round().to_string(),
+                ));
+            }
+            entries
+        }
+        pub fn is_changed(&self) -> bool {
+            self.volume.is_changed()
+                || self.display_quality.is_changed()
+                || self.mouse_sensitivity.is_changed()
+                || self.invert_mouse.is_changed()
+                || self.subtitles.is_changed()
+                || self.route_guidance.is_changed()
+                || self.telemetry.is_changed()
+                || self.game_mode.is_changed()
+                || self.round_length.is_changed()
+                || self.difficulty.is_changed()
+                || self.time_of_day.is_changed()
+                || self.weather_choice.is_changed()
+                || self.selected_map.is_changed()
+                || self.camera_settings.is_changed()
+        }
+    }
+
+    /// reads a file of `key=value` lines, a missing or unreadable file just has no entries
+    pub fn read_key_values(path: &str) -> HashMap<String, String> {
+        fs::read_to_string(path)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+            .collect()
+    }
+    /// writes `key=value` lines, creating the save folder if needed
+    pub fn write_key_values(path: &str, entries: &[(&str, String)]) {
+        if let Some(parent) = Path::new(path).parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        let contents: String = entries
+            .iter()
+            .map(|(key, value)| format!("{key}={value}\n"))
+            .collect();
+        if let Err(error) = fs::write(path, contents) {
+            warn!("could not save {path}: {error}");
+        }
+    }
+    fn load_settings(
+        mut volume: ResMut<Volume>,
+        mut display_quality: ResMut<DisplayQuality>,
+        mut mouse_sensitivity: ResMut<MouseSensitivity>,
+        mut invert_mouse: ResMut<InvertMouse>,
+        (mut subtitles, mut route_guidance, mut telemetry): (
+            ResMut<Subtitles>,
+            ResMut<RouteGuidance>,
+            ResMut<Telemetry>,
+        ),
+        (mut time_of_day, mut weather_choice, selected_map, profile): (
+            ResMut<TimeOfDay>,
+            ResMut<WeatherChoice>,
+            Res<SelectedMap>,
+            Res<ActiveProfile>,
+        ),
+        (mut game_mode, mut round_length, mut difficulty, mut camera_settings): (
+            ResMut<GameMode>,
+            ResMut<RoundLength>,
+            ResMut<Difficulty>,
+            ResMut<CameraSettings>,
+        ),
+    ) {
+        let values = read_key_values(&profile.path(SETTINGS_FILE));
+        let get = |key: &str| values.get(key).map(String::as_str);
+        // anything missing goes back to what a new profile starts with, rather than keeping
+        // whatever the last profile had picked
+        volume.0 = get("volume")
+            .and_then(|value| value.parse::<u32>().ok())
+            .map_or(7, |value| value.min(9));
+        *display_quality = DisplayQuality::Medium;
+        mouse_sensitivity.0 = get("mouse_sensitivity")
+            .and_then(|value| value.parse().ok())
+            .map_or(5, |value| u32::clamp(value, 1, 10));
+        let flag = |key: &str, default: bool| {
+            get(key)
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(default)
+        };
+        invert_mouse.0 = flag("invert_mouse", false);
+        subtitles.0 = flag("subtitles", true);
+        route_guidance.0 = flag("route_guidance", true);
+        telemetry.0 = flag("telemetry", false);
+        *time_of_day = match get("time_of_day") {
+            Some("Dawn") => TimeOfDay::Dawn,
+            Some("Noon") => TimeOfDay::Noon,
+            Some("Dusk") => TimeOfDay::Dusk,
+            Some("Night") => TimeOfDay::Night,
+            _ => TimeOfDay::Cycle,
+        };
+        *game_mode = match get("game_mode") {
+            Some("Endless") => GameMode::Endless,
+            Some("Zen") => GameMode::Zen,
+            Some("Survival") => GameMode::Survival,
+            _ => GameMode::Timed,
+        };
+        round_length.0 = get("round_length")
+            .and_then(|value| value.parse::<u32>().ok())
+            .map_or(60, |value| value.clamp(30, 600));
+        *difficulty = match get("difficulty") {
+            Some("Easy") => Difficulty::Easy,
+            Some("Hard") => Difficulty::Hard,
+            _ => Difficulty::Normal,
+        };
+        // the weather is picked separately for each map
+        let map = selected_map.info();
+        *weather_choice = match get(&format!("weather.{}", map.name)) {
+            Some("Forecast") => WeatherChoice::Forecast,
+            Some("Clear") => WeatherChoice::Clear,
+            Some("Rain") => WeatherChoice::Rain,
+            Some("Fog") => WeatherChoice::Fog,
+            Some("Wind") => WeatherChoice::Wind,
+            _ => map.weather,
+        };
+        // each camera mode keeps its own zoom and field of view, missing ones go back to default
+        let defaults = CameraSettings::default();
+        camera_settings.occlusion = match get("camera.occlusion") {
+            Some("Off") => OcclusionMode::Off,
+            Some("Fade") => OcclusionMode::Fade,
+            _ => OcclusionMode::PullIn,
+        };
+        for mode in CameraMode::ALL {
+            let settings = camera_settings.mode_mut(mode);
+            *settings = *defaults.mode(mode);
+            let key = mode.key();
+            if let Some(value) =
+                get(&format!("camera.{key}.zoom")).and_then(|value| value.parse().ok())
+            {
+                settings.set_zoom(value);
+            }
+            if let Some(value) =
+                get(&format!("camera.{key}.fov")).and_then(|value| value.parse().ok())
+            {
+                settings.set_fov_degrees(value);
             }
         }
     }
+    fn save_settings(preferences: Preferences, profile: Res<ActiveProfile>) {
+        let path = profile.path(SETTINGS_FILE);
+        let mut entries = preferences.entries();
+        // hang on to the weather picked for the other maps
+        let other_maps: Vec<(String, String)> = read_key_values(&path)
+            .into_iter()
+            .filter(|(key, _)| {
+                key.starts_with("weather.") && !entries.iter().any(|(saved, _)| saved == key)
+            })
+            .collect();
+        entries.extend(other_maps);
+        let entries: Vec<(&str, String)> = entries
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.clone()))
+            .collect();
+        write_key_values(&path, &entries);
+    }
+}
+
+/// where an asset ends up on disk, for checking on or reading files outside the asset server
+fn asset_file(path: &str) -> std::path::PathBuf {
+    bevy::asset::io::file::FileAssetReader::get_base_path()
+        .join("assets")
+        .join(path)
 }
 
 // Generic system that takes a component as a parameter, and will despawn all entities with that component